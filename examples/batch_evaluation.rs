@@ -6,7 +6,7 @@ fn main() {
 
     let mut evaluator = Evaluator::new(100);
 
-    let contexts = vec![
+    let contexts = [
         [("price".to_string(), 120.0), ("volume".to_string(), 3000.0)]
             .iter()
             .cloned()
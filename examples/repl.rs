@@ -0,0 +1,104 @@
+use quantixis_rs::ast::{ASTNode, Evaluator, Parser};
+use quantixis_rs::functions::register_functions;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+  set <name> = <expression>   bind a variable
+  unset <name>                remove a variable
+  :vars                       list bound variables
+  :engine ast|compiled        switch evaluation backend
+  :ast                        print the AST of the last expression
+  :help                       show this message
+  :quit                       exit
+Anything else is evaluated as an expression.";
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    Ast,
+    Compiled,
+}
+
+fn evaluate(
+    evaluator: &mut Evaluator,
+    engine: Engine,
+    ast: &ASTNode,
+    context: &HashMap<String, f64>,
+) -> Result<f64, String> {
+    match engine {
+        Engine::Ast => evaluator.evaluate_ast(ast, context),
+        Engine::Compiled => evaluator.compile(ast)?.evaluate(context),
+    }
+}
+
+fn main() {
+    pretty_env_logger::init();
+
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+
+    let mut context: HashMap<String, f64> = HashMap::new();
+    let mut engine = Engine::Ast;
+    let mut last_ast: Option<ASTNode> = None;
+
+    println!("quantixis repl, type :help for commands");
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let line = line.trim();
+
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => {}
+            [":quit"] | [":q"] => break,
+            [":help"] => println!("{}", HELP),
+            [":vars"] => {
+                let mut vars: Vec<_> = context.iter().collect();
+                vars.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, value) in vars {
+                    println!("{} = {}", name, value);
+                }
+            }
+            [":engine", "ast"] => engine = Engine::Ast,
+            [":engine", "compiled"] => engine = Engine::Compiled,
+            [":engine"] => println!("{:?}", engine),
+            [":ast"] => match &last_ast {
+                Some(ast) => println!("{:#?}", ast),
+                None => println!("No expression evaluated yet"),
+            },
+            ["unset", name] => {
+                context.remove(*name);
+            }
+            ["set", ..] => {
+                let Some((name, expression)) = line["set".len()..].split_once('=') else {
+                    println!("Usage: set <name> = <expression>");
+                    continue;
+                };
+                let result = Parser::parse_expression(expression.trim())
+                    .and_then(|ast| evaluate(&mut evaluator, engine, &ast, &context));
+                match result {
+                    Ok(value) => {
+                        context.insert(name.trim().to_string(), value);
+                    }
+                    Err(err) => println!("{}", err),
+                }
+            }
+            _ => {
+                let result = Parser::parse_expression(line).and_then(|ast| {
+                    let value = evaluate(&mut evaluator, engine, &ast, &context);
+                    last_ast = Some(ast);
+                    value
+                });
+                match result {
+                    Ok(value) => println!("{}", value),
+                    Err(err) => println!("{}", err),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! A rules microservice exposing the engine over HTTP with JSON bodies.
+//!
+//! ```sh
+//! cargo run --example server --features server
+//!
+//! curl -s localhost:3000/validate -d '{"expression": "sma(data: close) > 100"}' -H 'content-type: application/json'
+//! curl -s localhost:3000/compile -d '{"expression": "price > 100 AND volume < 5000"}' -H 'content-type: application/json'
+//! curl -s localhost:3000/evaluate -d '{"id": 0, "contexts": [{"price": 120, "volume": 3000}, {"price": 80}]}' -H 'content-type: application/json'
+//! ```
+//!
+//! Compiled programs are cached by expression text, so compiling the same rule twice returns
+//! the same id. A batch of contexts is split across threads and evaluated concurrently.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use quantixis_rs::ast::{CompiledExpression, Evaluator};
+use quantixis_rs::functions::register_functions;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+type Context = HashMap<String, f64>;
+type Response = (StatusCode, Json<Value>);
+
+struct AppState {
+    evaluator: Evaluator,
+    programs: RwLock<Programs>,
+}
+
+#[derive(Default)]
+struct Programs {
+    compiled: Vec<Arc<CompiledExpression>>,
+    ids: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+struct ExpressionRequest {
+    expression: String,
+}
+
+#[derive(Deserialize)]
+struct EvaluateRequest {
+    /// Id returned by `/compile`.
+    id: Option<usize>,
+    /// Expression to compile, or reuse from the cache, if no id is given.
+    expression: Option<String>,
+    contexts: Vec<Context>,
+}
+
+impl AppState {
+    /// Returns the id of the compiled expression, compiling it on first use.
+    fn compile(&self, expression: &str) -> Result<usize, String> {
+        if let Some(&id) = self.programs.read().unwrap().ids.get(expression) {
+            return Ok(id);
+        }
+        let compiled = Arc::new(self.evaluator.compile_expression(expression)?);
+
+        let mut programs = self.programs.write().unwrap();
+        // Another request may have compiled it in the meantime
+        if let Some(&id) = programs.ids.get(expression) {
+            return Ok(id);
+        }
+        let id = programs.compiled.len();
+        programs.compiled.push(compiled);
+        programs.ids.insert(expression.to_string(), id);
+        Ok(id)
+    }
+
+    fn program(&self, id: usize) -> Result<Arc<CompiledExpression>, String> {
+        self.programs
+            .read()
+            .unwrap()
+            .compiled
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown program id {}", id))
+    }
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message })))
+}
+
+async fn validate(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ExpressionRequest>,
+) -> Response {
+    match state.evaluator.validate(&request.expression) {
+        Ok(()) => (StatusCode::OK, Json(json!({ "valid": true }))),
+        Err(err) => (
+            StatusCode::OK,
+            Json(json!({ "valid": false, "error": err })),
+        ),
+    }
+}
+
+async fn compile(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ExpressionRequest>,
+) -> Response {
+    match state.compile(&request.expression) {
+        Ok(id) => (StatusCode::OK, Json(json!({ "id": id }))),
+        Err(err) => error(StatusCode::BAD_REQUEST, err),
+    }
+}
+
+async fn evaluate(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EvaluateRequest>,
+) -> Response {
+    let id = match (request.id, &request.expression) {
+        (Some(id), _) => Ok(id),
+        (None, Some(expression)) => state.compile(expression),
+        (None, None) => Err("Expected an id or an expression".to_string()),
+    };
+    let program = match id.and_then(|id| state.program(id)) {
+        Ok(program) => program,
+        Err(err) => return error(StatusCode::BAD_REQUEST, err),
+    };
+
+    let contexts = request.contexts;
+    let results = tokio::task::spawn_blocking(move || evaluate_batch(&program, &contexts))
+        .await
+        .expect("evaluation task panicked");
+    (StatusCode::OK, Json(json!({ "results": results })))
+}
+
+/// Evaluates the contexts in chunks, one thread per available core.
+fn evaluate_batch(program: &CompiledExpression, contexts: &[Context]) -> Vec<Value> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = contexts.len().div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = contexts
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|context| match program.evaluate(context) {
+                            Ok(value) => json!({ "value": value }),
+                            Err(err) => json!({ "error": err }),
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("evaluation thread panicked"))
+            .collect()
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+    let state = Arc::new(AppState {
+        evaluator,
+        programs: RwLock::new(Programs::default()),
+    });
+
+    let app = Router::new()
+        .route("/validate", post(validate))
+        .route("/compile", post(compile))
+        .route("/evaluate", post(evaluate))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .expect("failed to bind 127.0.0.1:3000");
+    println!("Listening on http://127.0.0.1:3000");
+    axum::serve(listener, app).await.expect("server error");
+}
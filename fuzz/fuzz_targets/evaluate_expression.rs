@@ -0,0 +1,46 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use quantixis_rs::ast::{AsyncEvaluator, Evaluator, Parser};
+use quantixis_rs::functions::register_functions;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::task::{Context, Waker};
+
+fn evaluator() -> &'static AsyncEvaluator {
+    static EVALUATOR: OnceLock<AsyncEvaluator> = OnceLock::new();
+    EVALUATOR.get_or_init(|| {
+        let mut evaluator = Evaluator::new(100);
+        register_functions(&mut evaluator);
+        AsyncEvaluator::new(evaluator)
+    })
+}
+
+fuzz_target!(|input: &str| {
+    let Ok(ast) = Parser::parse_expression(input) else {
+        return;
+    };
+    let context = HashMap::from([
+        ("a".to_string(), 1.0),
+        ("b".to_string(), 0.0),
+        ("close".to_string(), 101.5),
+    ]);
+    let async_evaluator = evaluator();
+    let evaluator = async_evaluator.evaluator();
+    if let Ok(compiled) = evaluator.compile(&ast) {
+        let _ = compiled.evaluate(&context);
+    }
+    let _ = evaluator.explain(&ast, &context);
+    let (a, b) = ([1.0, f64::NAN], [0.0, -0.0]);
+    let columns = HashMap::from([("a".to_string(), &a[..]), ("b".to_string(), &b[..])]);
+    let _ = evaluator.evaluate_columns(&ast, &columns);
+    let _ = evaluator.evaluate_columns(&ast, &HashMap::new());
+    let _ = evaluator.evaluate_cross_section(input, &[context.clone(), HashMap::new()]);
+
+    // No async functions are registered, so the future completes on its first poll
+    let mut future = std::pin::pin!(async_evaluator.evaluate(&ast, &context));
+    let _ = future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()));
+});
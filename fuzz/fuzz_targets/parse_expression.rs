@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use quantixis_rs::ast::Parser;
+
+fuzz_target!(|input: &str| {
+    let _ = Parser::parse_expression(input);
+    let _ = Parser::name_refs(input);
+});
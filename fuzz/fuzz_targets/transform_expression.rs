@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use quantixis_rs::ast::{to_sql, Evaluator, Parser, SqlDialect};
+use std::collections::HashMap;
+
+fuzz_target!(|input: &str| {
+    let Ok(ast) = Parser::parse_expression(input) else {
+        return;
+    };
+    let _ = to_sql(&ast, SqlDialect::Postgres);
+    let _ = to_sql(&ast, SqlDialect::ClickHouse);
+    let _ = ast.canonicalize().fingerprint();
+    let _ = ast.partial_eval(&HashMap::from([("a".to_string(), 1.0)]));
+    let _ = ast.variables();
+    let _ = Parser::parse_expression(&ast.to_string());
+    let evaluator = Evaluator::new(100);
+    let _ = evaluator.generate_rust(&ast, "rule");
+    let _ = evaluator.estimated_cost(&ast);
+});
@@ -0,0 +1,34 @@
+/* C interface to quantixis-rs. Build the crate with `--features capi`. */
+
+#ifndef QUANTIXIS_H
+#define QUANTIXIS_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A compiled expression together with its bound variables. */
+typedef struct QuantixisExpression QuantixisExpression;
+
+/* Parses and compiles an expression. Returns NULL on failure. */
+QuantixisExpression *quantixis_compile(const char *expression);
+
+/* Binds a numeric variable used by subsequent evaluations. Returns 0 on success, -1 on failure. */
+int32_t quantixis_bind_f64(QuantixisExpression *expression, const char *name, double value);
+
+/* Evaluates the expression and writes the result to `out`. Returns 0 on success, -1 on failure. */
+int32_t quantixis_eval(const QuantixisExpression *expression, double *out);
+
+/* Returns the last error raised on this thread, or NULL. Valid until the next failing call. */
+const char *quantixis_last_error(void);
+
+/* Frees an expression returned by quantixis_compile. Passing NULL is a no-op. */
+void quantixis_free(QuantixisExpression *expression);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* QUANTIXIS_H */
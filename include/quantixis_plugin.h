@@ -0,0 +1,51 @@
+/* ABI for quantixis-rs plugins, loaded with `Plugin::load` when the crate is built with
+ * `--features plugins`. */
+
+#ifndef QUANTIXIS_PLUGIN_H
+#define QUANTIXIS_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define QUANTIXIS_PLUGIN_ABI_VERSION 1
+
+/* Size of the buffer a failing function may write its error message to. */
+#define QUANTIXIS_PLUGIN_ERROR_CAPACITY 256
+
+/* A named argument: `len` numbers starting at `values`. A number is an array of length 1. */
+typedef struct QuantixisPluginArg {
+    const char *name;
+    const double *values;
+    size_t len;
+} QuantixisPluginArg;
+
+/* Writes the result to `out` and returns 0, or returns -1 after optionally writing a
+ * NUL-terminated message to `error`. May be called from several threads at once. */
+typedef int32_t (*QuantixisPluginFunction)(void *user_data,
+                                           const QuantixisPluginArg *args,
+                                           size_t len,
+                                           double *out,
+                                           char *error);
+
+typedef struct QuantixisPluginRegistrar {
+    uint32_t abi_version;
+    void *context;
+    /* Registers `function` under `name`, e.g. "mypack.ema". Returns 0 on success. */
+    int32_t (*register_function)(void *context,
+                                 const char *name,
+                                 QuantixisPluginFunction function,
+                                 void *user_data);
+} QuantixisPluginRegistrar;
+
+/* Exported by every plugin. Returns 0 on success. */
+int32_t quantixis_plugin_register(const QuantixisPluginRegistrar *registrar);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* QUANTIXIS_PLUGIN_H */
@@ -0,0 +1,290 @@
+//! Compile-time expressions for **Quantixis-rs**.
+//!
+//! `quantixis_expr!` parses an expression while the crate compiles and expands into a
+//! native Rust function, so hard-coded rules need no parsing at runtime and syntax errors
+//! fail the build instead of surfacing in production. `#[derive(IntoContext)]` turns plain
+//! structs into contexts.
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+use quantixis_rs::ast::Evaluator;
+use quantixis_rs::functions::register_functions;
+use std::iter::Peekable;
+
+/// Compiles an expression into a function reading variables from a `VariableProvider`.
+///
+/// An optional list declares the variables the expression may use; any other variable is
+/// a compile error. Only the built-in functions can be called, and expressions that call
+/// them take an `Evaluator` with those functions registered as a first argument.
+///
+/// ```
+/// use quantixis_macros::quantixis_expr;
+/// use std::collections::HashMap;
+///
+/// let rule = quantixis_expr!("price > 100 AND volume < 5000", [price, volume]);
+///
+/// let context = HashMap::from([("price".to_string(), 120.0), ("volume".to_string(), 3000.0)]);
+/// assert_eq!(rule(&context), Ok(1.0));
+/// ```
+///
+/// ```compile_fail
+/// use quantixis_macros::quantixis_expr;
+///
+/// // `volume` is not declared
+/// let rule = quantixis_expr!("price > 100 AND volume < 5000", [price]);
+/// ```
+#[proc_macro]
+pub fn quantixis_expr(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(tokens) => tokens,
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, String> {
+    let (expression, declared) = parse_input(input)?;
+
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+    let ast = evaluator.parse_expression(&expression)?;
+
+    if let Some(declared) = declared {
+        let used = evaluator.variables(&ast);
+        if let Some(name) = used.iter().find(|name| !declared.contains(name)) {
+            return Err(format!(
+                "Variable '{}' is not declared in [{}]",
+                name,
+                declared.join(", ")
+            ));
+        }
+    }
+
+    let source = evaluator.generate_rust(&ast, "__quantixis_expr")?;
+    format!("{{ {} __quantixis_expr }}", source)
+        .parse()
+        .map_err(|err| format!("Invalid generated code: {:?}", err))
+}
+
+/// Implements `IntoContext` and `VariableProvider` for a struct with named fields, so it can
+/// be flattened into a context or evaluated against directly.
+///
+/// Every field must implement `IntoContext`: numbers, booleans, options and other structs
+/// deriving it, whose fields are read with property access such as `quote.bid`.
+///
+/// ```
+/// use quantixis_macros::IntoContext;
+/// use quantixis_rs::ast::{Evaluator, IntoContext};
+///
+/// #[derive(IntoContext)]
+/// struct Quote {
+///     bid: f64,
+///     ask: f64,
+/// }
+///
+/// #[derive(IntoContext)]
+/// struct Tick {
+///     price: f64,
+///     size: u32,
+///     quote: Quote,
+/// }
+///
+/// let tick = Tick { price: 100.0, size: 10, quote: Quote { bid: 99.5, ask: 100.5 } };
+/// let evaluator = Evaluator::new(100);
+/// let spread = evaluator.evaluate_expression_with("quote.ask - quote.bid", &tick);
+/// assert_eq!(spread, Ok(1.0));
+/// assert_eq!(tick.to_context()["quote.bid"], 99.5);
+/// ```
+#[proc_macro_derive(IntoContext)]
+pub fn derive_into_context(input: TokenStream) -> TokenStream {
+    match expand_into_context(input) {
+        Ok(tokens) => tokens,
+        Err(message) => format!("compile_error!({:?});", message).parse().unwrap(),
+    }
+}
+
+fn expand_into_context(input: TokenStream) -> Result<TokenStream, String> {
+    let usage = "IntoContext can only be derived for structs with named fields";
+    let mut tokens = input.into_iter().peekable();
+    skip_attributes_and_visibility(&mut tokens);
+
+    match tokens.next() {
+        Some(TokenTree::Ident(keyword)) if keyword.to_string() == "struct" => {}
+        _ => return Err(usage.to_string()),
+    }
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(name)) => name.to_string(),
+        _ => return Err(usage.to_string()),
+    };
+    let fields = match tokens.next() {
+        Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => {
+            field_names(body.stream())?
+        }
+        Some(TokenTree::Punct(open)) if open.as_char() == '<' => {
+            return Err("IntoContext cannot be derived for generic structs".to_string())
+        }
+        _ => return Err(usage.to_string()),
+    };
+
+    let mut writes = String::new();
+    let mut lookups = String::new();
+    for field in &fields {
+        let key = field.strip_prefix("r#").unwrap_or(field);
+        writes.push_str(&format!(
+            "::quantixis_rs::ast::IntoContext::write_context(&self.{field}, &::quantixis_rs::ast::context_path(path, {key:?}), context);",
+        ));
+        lookups.push_str(&format!(
+            "({key:?}, rest) => ::quantixis_rs::ast::IntoContext::lookup(&self.{field}, rest),",
+        ));
+    }
+    if fields.is_empty() {
+        writes.push_str("let _ = (path, context);");
+    }
+
+    format!(
+        "impl ::quantixis_rs::ast::IntoContext for {name} {{
+            fn write_context(
+                &self,
+                path: &str,
+                context: &mut ::std::collections::HashMap<::std::string::String, f64>,
+            ) {{
+                {writes}
+            }}
+
+            fn lookup(&self, path: &str) -> ::std::option::Option<f64> {{
+                match path.split_once('.').unwrap_or((path, \"\")) {{
+                    {lookups}
+                    _ => ::std::option::Option::None,
+                }}
+            }}
+        }}
+
+        impl ::quantixis_rs::ast::VariableProvider for {name} {{
+            fn get(&self, name: &str) -> ::std::option::Option<f64> {{
+                ::quantixis_rs::ast::IntoContext::lookup(self, name)
+            }}
+        }}"
+    )
+    .parse()
+    .map_err(|err| format!("Invalid generated code: {:?}", err))
+}
+
+/// Skips outer attributes such as `#[derive(..)]` and a visibility such as `pub(crate)`.
+fn skip_attributes_and_visibility(tokens: &mut Peekable<proc_macro::token_stream::IntoIter>) {
+    loop {
+        match tokens.peek() {
+            Some(TokenTree::Punct(hash)) if hash.as_char() == '#' => {
+                tokens.next();
+                tokens.next();
+            }
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+                tokens.next();
+                if matches!(tokens.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis)
+                {
+                    tokens.next();
+                }
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Returns the names of the fields in the body of a struct, e.g. `price` and `size` in
+/// `pub price: f64, size: Option<u32>`.
+fn field_names(body: TokenStream) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    let mut tokens = body.into_iter().peekable();
+    while tokens.peek().is_some() {
+        skip_attributes_and_visibility(&mut tokens);
+        match tokens.next() {
+            Some(TokenTree::Ident(name)) => names.push(name.to_string()),
+            _ => return Err("Expected a field name".to_string()),
+        }
+        // Skip the type up to the next comma outside of generic arguments
+        let mut depth = 0;
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(punct) = token {
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Splits the input into the expression string and the declared variables, if any.
+fn parse_input(input: TokenStream) -> Result<(String, Option<Vec<String>>), String> {
+    let usage = "Expected a string literal, optionally followed by a list of variables, e.g. `quantixis_expr!(\"a + b > c\", [a, b, c])`";
+    let mut tokens = input.into_iter();
+
+    let expression = match tokens.next() {
+        Some(TokenTree::Literal(literal)) => string_value(&literal.to_string()).ok_or(usage)?,
+        _ => return Err(usage.to_string()),
+    };
+
+    let declared = match tokens.next() {
+        None => None,
+        Some(TokenTree::Punct(comma)) if comma.as_char() == ',' => match tokens.next() {
+            None => None,
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket => {
+                Some(declared_names(group.stream()))
+            }
+            _ => return Err(usage.to_string()),
+        },
+        _ => return Err(usage.to_string()),
+    };
+    match tokens.next() {
+        None => Ok((expression, declared)),
+        Some(TokenTree::Punct(comma)) if comma.as_char() == ',' && tokens.next().is_none() => {
+            Ok((expression, declared))
+        }
+        _ => Err(usage.to_string()),
+    }
+}
+
+/// Splits `a, bar.close, c` into names, joining the tokens between commas.
+fn declared_names(stream: TokenStream) -> Vec<String> {
+    let mut names = vec![String::new()];
+    for token in stream {
+        match token {
+            TokenTree::Punct(comma) if comma.as_char() == ',' => names.push(String::new()),
+            token => names.last_mut().unwrap().push_str(&token.to_string()),
+        }
+    }
+    names.retain(|name| !name.is_empty());
+    names
+}
+
+/// Returns the value of a string literal token, e.g. `"a \"b\""` or `r#"a "b""#`.
+fn string_value(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let quoted = &raw[hashes..raw.len() - hashes];
+        return Some(quoted.strip_prefix('"')?.strip_suffix('"')?.to_string());
+    }
+
+    let quoted = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            '0' => value.push('\0'),
+            '\n' => {
+                // A line continuation skips the newline and leading whitespace
+                chars = chars.as_str().trim_start().chars();
+            }
+            c @ ('\\' | '"' | '\'') => value.push(c),
+            _ => return None,
+        }
+    }
+    Some(value)
+}
@@ -0,0 +1,64 @@
+use quantixis_macros::IntoContext;
+use quantixis_rs::ast::{Evaluator, IntoContext, VariableProvider};
+use std::collections::HashMap;
+
+#[derive(IntoContext)]
+pub(crate) struct Quote {
+    pub bid: f64,
+    pub ask: f32,
+}
+
+/// A trade with its quote
+#[derive(IntoContext)]
+struct Tick {
+    price: f64,
+    size: u64,
+    halted: bool,
+    quote: Quote,
+    r#type: i32,
+    last: Option<Quote>,
+    history: Option<HashMap<String, f64>>,
+}
+
+#[derive(IntoContext)]
+struct Empty {}
+
+#[test]
+fn test_derive_into_context() {
+    let tick = Tick {
+        price: 100.0,
+        size: 10,
+        halted: false,
+        quote: Quote {
+            bid: 99.5,
+            ask: 100.5,
+        },
+        r#type: 2,
+        last: None,
+        history: Some(HashMap::from([("open".to_string(), 99.0)])),
+    };
+
+    assert_eq!(
+        tick.to_context(),
+        HashMap::from([
+            ("price".to_string(), 100.0),
+            ("size".to_string(), 10.0),
+            ("halted".to_string(), 0.0),
+            ("quote.bid".to_string(), 99.5),
+            ("quote.ask".to_string(), 100.5),
+            ("type".to_string(), 2.0),
+            ("history.open".to_string(), 99.0),
+        ])
+    );
+    assert_eq!(tick.get("quote.ask"), Some(100.5));
+    assert_eq!(tick.get("quote"), None);
+    assert_eq!(tick.get("last.bid"), None);
+    assert_eq!(tick.get("history.open"), Some(99.0));
+    assert!(Empty {}.to_context().is_empty());
+
+    let evaluator = Evaluator::new(100);
+    assert_eq!(
+        evaluator.evaluate_expression_with("price > quote.bid AND NOT halted", &tick),
+        Ok(1.0)
+    );
+}
@@ -0,0 +1,41 @@
+use quantixis_macros::quantixis_expr;
+use quantixis_rs::ast::Evaluator;
+use quantixis_rs::functions::register_functions;
+use std::collections::HashMap;
+
+#[test]
+fn test_quantixis_expr() {
+    let rule = quantixis_expr!("(price > 100 AND volume < 5000) OR volume >= 3000");
+    let context = HashMap::from([("price".to_string(), 80.0), ("volume".to_string(), 4000.0)]);
+    assert_eq!(rule(&context), Ok(1.0));
+
+    let rule = quantixis_expr!(
+        r#"bar.close - bar.open > 2% * bar.open"#,
+        [bar.close, bar.open],
+    );
+    let context = HashMap::from([
+        ("bar.open".to_string(), 100.0),
+        ("bar.close".to_string(), 103.0),
+    ]);
+    assert_eq!(rule(&context), Ok(1.0));
+    assert_eq!(
+        rule(&HashMap::new()),
+        Err("Identifier 'bar.close' not found in context".to_string())
+    );
+}
+
+#[test]
+fn test_quantixis_expr_with_functions() {
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+
+    let rule = quantixis_expr!(
+        "abs(value: change) > 2 AND hour(ts: time) >= 9",
+        [change, time]
+    );
+    let context = HashMap::from([
+        ("change".to_string(), -3.5),
+        ("time".to_string(), 10.0 * 3_600.0),
+    ]);
+    assert_eq!(rule(&evaluator, &context), Ok(1.0));
+}
@@ -0,0 +1,109 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyFloat};
+use quantixis_rs::ast::{
+    CompiledExpression, Evaluator, FunctionArgValue, FunctionArgs, FunctionResult, Parser,
+};
+use quantixis_rs::functions::register_functions;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Evaluator with the built-in function library, shared by every call.
+fn evaluator() -> &'static Evaluator {
+    static EVALUATOR: OnceLock<Evaluator> = OnceLock::new();
+    EVALUATOR.get_or_init(|| {
+        let mut evaluator = Evaluator::new(100);
+        register_functions(&mut evaluator);
+        evaluator
+    })
+}
+
+/// An expression parsed and compiled once, ready to evaluate against many contexts.
+#[pyclass(frozen)]
+struct Expression {
+    source: String,
+    compiled: CompiledExpression,
+}
+
+#[pymethods]
+impl Expression {
+    /// Evaluates the expression against a dict of numeric variables.
+    fn evaluate(&self, context: HashMap<String, f64>) -> PyResult<f64> {
+        self.compiled
+            .evaluate(&context)
+            .map_err(PyValueError::new_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Expression({:?})", self.source)
+    }
+}
+
+/// Parses and compiles an expression.
+#[pyfunction]
+fn compile(expression: &str) -> PyResult<Expression> {
+    let ast = Parser::parse_expression(expression).map_err(PyValueError::new_err)?;
+    let compiled = evaluator().compile(&ast).map_err(PyValueError::new_err)?;
+    Ok(Expression {
+        source: expression.to_string(),
+        compiled,
+    })
+}
+
+/// Evaluates an expression against a dict of numeric variables.
+#[pyfunction]
+fn evaluate(expression: &str, context: HashMap<String, f64>) -> PyResult<f64> {
+    compile(expression)?.evaluate(context)
+}
+
+/// Lists the names of the built-in functions.
+#[pyfunction]
+fn functions() -> Vec<String> {
+    evaluator()
+        .functions()
+        .iter()
+        .map(|info| info.name.clone())
+        .collect()
+}
+
+/// Calls a built-in function directly, e.g. `call("simple_moving_average", values=closes, period=20)`.
+///
+/// Arguments may be numbers, dicts of floats, or any sequence of floats, including numpy
+/// arrays.
+/// Returns a float, or a dict for functions with named outputs.
+#[pyfunction]
+#[pyo3(signature = (name, **kwargs))]
+fn call(py: Python<'_>, name: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<PyObject> {
+    let mut args = FunctionArgs::new();
+    if let Some(kwargs) = kwargs {
+        for (key, value) in kwargs.iter() {
+            let key: String = key.extract()?;
+            let value = if value.is_instance_of::<PyFloat>() || value.extract::<i64>().is_ok() {
+                FunctionArgValue::Number(value.extract()?)
+            } else if value.is_instance_of::<PyDict>() {
+                FunctionArgValue::KeyValue(value.extract()?)
+            } else {
+                FunctionArgValue::Array(value.extract()?)
+            };
+            args.insert(&key, value);
+        }
+    }
+
+    let result = evaluator()
+        .call_function(name, &args)
+        .map_err(PyValueError::new_err)?;
+    match result {
+        FunctionResult::UnnamedF64(value) => Ok(value.into_pyobject(py)?.into_any().unbind()),
+        FunctionResult::NamedF64Map(map) => Ok(map.into_pyobject(py)?.into_any().unbind()),
+    }
+}
+
+#[pymodule]
+fn quantixis(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Expression>()?;
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(functions, m)?)?;
+    m.add_function(wrap_pyfunction!(call, m)?)?;
+    Ok(())
+}
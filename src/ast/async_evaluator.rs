@@ -0,0 +1,405 @@
+use crate::ast::{
+    evaluator::{bind_args, bound_identifiers, pop},
+    property_path, unknown_function, unknown_identifier, ASTNode, Evaluator, FunctionArgs,
+    FunctionInfo, FunctionResult,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A boxed, sendable future, as returned by async functions.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub type AsyncFunction =
+    Arc<dyn Fn(FunctionArgs) -> BoxFuture<'static, Result<FunctionResult, String>> + Send + Sync>;
+
+/// An `Evaluator` that can also call `async` functions, e.g. to fetch the latest quote from
+/// a network service while evaluating.
+///
+/// Evaluation returns a future that awaits each async call in turn. It does not depend on
+/// any particular runtime, so it runs on tokio, async-std or a hand-written executor alike.
+/// Functions registered on the wrapped `Evaluator` remain callable and run synchronously.
+///
+/// Async functions are subject to the wrapped evaluator's `set_max_capability` and
+/// deterministic mode like any other. Declare what they do with
+/// `AsyncEvaluator::register_function_with_info`, e.g. `Capability::Network` for a quote
+/// service; those registered without `FunctionInfo` count as pure.
+///
+/// ```
+/// use quantixis_rs::ast::{AsyncEvaluator, Capability, Evaluator, FunctionInfo, FunctionResult};
+/// use std::collections::HashMap;
+///
+/// let mut evaluator = AsyncEvaluator::new(Evaluator::new(100));
+/// let info = FunctionInfo::new("latest_quote")
+///     .symbol("symbol")
+///     .capability(Capability::Network);
+/// evaluator.register_function_with_info(info, |args| async move {
+///     let symbol = args.get_string("symbol")?.to_string();
+///     // e.g. `let price = client.quote(&symbol).await?;`
+///     let price = if symbol == "AAPL" { 190.0 } else { 0.0 };
+///     Ok(FunctionResult::UnnamedF64(price))
+/// });
+///
+/// let context = HashMap::from([("limit".to_string(), 200.0)]);
+/// let result = evaluator.evaluate_expression("latest_quote(symbol: AAPL) < limit", &context);
+/// # let result = futures_lite_block_on(result);
+/// # fn futures_lite_block_on<F: std::future::Future>(future: F) -> F::Output {
+/// #     let mut future = std::pin::pin!(future);
+/// #     let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+/// #     loop {
+/// #         if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+/// #             return output;
+/// #         }
+/// #     }
+/// # }
+/// assert_eq!(result.unwrap(), 1.0);
+/// ```
+pub struct AsyncEvaluator {
+    evaluator: Evaluator,
+    functions: HashMap<String, AsyncFunction>,
+    function_info: HashMap<String, FunctionInfo>,
+}
+
+impl AsyncEvaluator {
+    /// Wraps an evaluator, keeping its functions, operators and settings.
+    pub fn new(evaluator: Evaluator) -> Self {
+        Self {
+            evaluator,
+            functions: HashMap::new(),
+            function_info: HashMap::new(),
+        }
+    }
+
+    /// Returns the wrapped evaluator.
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    /// Registers an async function. It takes precedence over a synchronous function of the
+    /// same name.
+    pub fn register_function<F, Fut>(&mut self, name: &str, function: F)
+    where
+        F: Fn(FunctionArgs) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<FunctionResult, String>> + Send + 'static,
+    {
+        self.register_function_with_info(FunctionInfo::new(name), function);
+    }
+
+    /// Registers an async function along with its signature metadata, including its
+    /// `Capability`.
+    pub fn register_function_with_info<F, Fut>(&mut self, info: FunctionInfo, function: F)
+    where
+        F: Fn(FunctionArgs) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<FunctionResult, String>> + Send + 'static,
+    {
+        self.functions.insert(
+            info.name.clone(),
+            Arc::new(move |args| Box::pin(function(args))),
+        );
+        self.function_info.insert(info.name.clone(), info);
+    }
+
+    /// Parses an expression and evaluates it against a context.
+    pub async fn evaluate_expression(
+        &self,
+        expression: &str,
+        context: &HashMap<String, f64>,
+    ) -> Result<f64, String> {
+        let ast = self.evaluator.parse_expression(expression)?;
+        self.evaluate(&ast, context).await
+    }
+
+    /// Evaluates an AST against a context, awaiting async function calls as they are
+    /// reached. Operands are evaluated left to right, one at a time.
+    ///
+    /// The tree is walked with an explicit stack inside a single future, so deep trees
+    /// cannot overflow the stack while it is polled.
+    pub fn evaluate<'a>(
+        &'a self,
+        ast: &'a ASTNode,
+        context: &'a HashMap<String, f64>,
+    ) -> BoxFuture<'a, Result<f64, String>> {
+        Box::pin(async move {
+            self.evaluator.check_units(ast)?;
+            let mut values: Vec<f64> = Vec::new();
+            // Each operation is visited twice: first to queue its operands, then, with
+            // `true`, to apply it to their values
+            let mut tasks = vec![(ast, false)];
+            while let Some((node, operands_done)) = tasks.pop() {
+                let value = match node {
+                    ASTNode::Group(inner) => {
+                        tasks.push((inner, false));
+                        continue;
+                    }
+                    ASTNode::BinaryOperation { .. }
+                    | ASTNode::LogicalOperation { .. }
+                    | ASTNode::CustomOperation { .. }
+                    | ASTNode::NotOperation(_)
+                    | ASTNode::Negate(_)
+                        if !operands_done =>
+                    {
+                        tasks.push((node, true));
+                        tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                        continue;
+                    }
+                    ASTNode::Number(n) => *n,
+                    ASTNode::Identifier(ident) => context
+                        .get(ident.as_str())
+                        .copied()
+                        .ok_or_else(|| unknown_identifier(ident, context.keys()))?,
+                    ASTNode::BinaryOperation { operator, .. } => {
+                        let right = pop(&mut values)?;
+                        let left = pop(&mut values)?;
+                        operator.apply_with_tolerance(left, right, self.evaluator.epsilon)?
+                    }
+                    ASTNode::LogicalOperation { operator, .. } => {
+                        let right = pop(&mut values)?;
+                        let left = pop(&mut values)?;
+                        operator.apply(left, right)?
+                    }
+                    ASTNode::CustomOperation { operator, .. } => {
+                        let function = self.evaluator.custom_operator(operator)?;
+                        let right = pop(&mut values)?;
+                        let left = pop(&mut values)?;
+                        function(left, right)?
+                    }
+                    ASTNode::NotOperation(_) => (pop(&mut values)? == 0.0) as i32 as f64,
+                    ASTNode::Negate(_) => -pop(&mut values)?,
+                    ASTNode::FunctionCall { name, args } => {
+                        match self.call(name, args, context).await? {
+                            FunctionResult::UnnamedF64(value) => value,
+                            FunctionResult::NamedF64Map(_) => {
+                                return Err("Expected single value, got multi-value".to_string())
+                            }
+                        }
+                    }
+                    ASTNode::PropertyAccess { base, property } => {
+                        match property_path(base, property) {
+                            (ASTNode::FunctionCall { name, args }, path) => {
+                                match self.call(name, args, context).await? {
+                                    FunctionResult::NamedF64Map(map) => {
+                                        map.get(&path).copied().ok_or_else(|| {
+                                            format!("Property {} not found in result", path)
+                                        })?
+                                    }
+                                    FunctionResult::UnnamedF64(_) => {
+                                        return Err(
+                                            "Expected multi-value, got single value".to_string()
+                                        )
+                                    }
+                                }
+                            }
+                            (ASTNode::Identifier(name), path) => {
+                                let key = format!("{}.{}", name, path);
+                                context
+                                    .get(&key)
+                                    .copied()
+                                    .ok_or_else(|| unknown_identifier(&key, context.keys()))?
+                            }
+                            _ => {
+                                return Err("Base must be a function call or identifier".to_string())
+                            }
+                        }
+                    }
+                };
+                values.push(value);
+            }
+            Ok(self.evaluator.normalize_result(pop(&mut values)?))
+        })
+    }
+
+    async fn call(
+        &self,
+        name: &str,
+        args: &FunctionArgs,
+        context: &HashMap<String, f64>,
+    ) -> Result<FunctionResult, String> {
+        let bind = |info: Option<&FunctionInfo>| {
+            let identifiers = bound_identifiers(info, args);
+            bind_args(args, &identifiers, |ident| context.get(ident).copied())
+                .map_err(|ident| unknown_identifier(ident, context.keys()))
+        };
+        if let Some(function) = self.functions.get(name) {
+            let info = &self.function_info[name];
+            self.evaluator.check_allowed(name, info.capability)?;
+            return function(bind(Some(info))?).await;
+        }
+        if self.evaluator.resolve_function(name).is_err() {
+            return Err(unknown_function(
+                name,
+                self.functions.keys().chain(self.evaluator.functions.keys()),
+            ));
+        }
+        let function = self.evaluator.function(name)?;
+        function(&bind(self.evaluator.function_info(name))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Capability;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    /// Polls a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Returns `Pending` once before completing, like a call waiting on I/O.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn setup_evaluator(calls: Arc<AtomicUsize>) -> AsyncEvaluator {
+        let evaluator = Evaluator::builder()
+            .with_function("double", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 2.0))
+            })
+            .build();
+        let mut evaluator = AsyncEvaluator::new(evaluator);
+        let info = FunctionInfo::new("quote").symbol("symbol");
+        evaluator.register_function_with_info(info, move |args| {
+            let calls = calls.clone();
+            async move {
+                YieldOnce(false).await;
+                calls.fetch_add(1, Ordering::SeqCst);
+                let price = match args.get_string("symbol")? {
+                    "AAPL" => 190.0,
+                    "MSFT" => 410.0,
+                    symbol => return Err(format!("No quote for {}", symbol)),
+                };
+                Ok(FunctionResult::NamedF64Map(HashMap::from([
+                    ("bid".to_string(), price - 0.5),
+                    ("ask".to_string(), price + 0.5),
+                ])))
+            }
+        });
+        evaluator
+    }
+
+    #[test]
+    fn test_async_evaluation() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let evaluator = setup_evaluator(calls.clone());
+        let context = HashMap::from([("size".to_string(), 10.0)]);
+
+        let result = block_on(evaluator.evaluate_expression(
+            "quote(symbol: MSFT).ask - quote(symbol: AAPL).bid > double(value: size) * 10",
+            &context,
+        ));
+        assert_eq!(result.unwrap(), 1.0);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let err = block_on(evaluator.evaluate_expression("quote(symbol: TSLA).bid", &context));
+        assert_eq!(err.unwrap_err(), "No quote for TSLA");
+
+        let err = block_on(evaluator.evaluate_expression("quotes(symbol: AAPL).bid", &context));
+        assert!(err.unwrap_err().ends_with("Did you mean 'quote'?"));
+
+        let err = block_on(evaluator.evaluate_expression("double(value: sise)", &context));
+        assert_eq!(
+            err.unwrap_err(),
+            "Identifier 'sise' not found in context. Did you mean 'size'?"
+        );
+    }
+
+    #[test]
+    fn test_async_capabilities() {
+        let mut sync = Evaluator::new(100);
+        sync.set_max_capability(Capability::ReadsClock);
+        let mut evaluator = AsyncEvaluator::new(sync);
+        evaluator.register_function_with_info(
+            FunctionInfo::new("fetch").capability(Capability::Network),
+            |_| async { Ok(FunctionResult::UnnamedF64(1.0)) },
+        );
+        evaluator.register_function("offset", |_| async { Ok(FunctionResult::UnnamedF64(2.0)) });
+        let context = HashMap::new();
+
+        assert_eq!(
+            block_on(evaluator.evaluate_expression("fetch() > 0", &context)),
+            Err(
+                "Function 'fetch' is network, which is not allowed (at most reads-clock)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            block_on(evaluator.evaluate_expression("offset() > 0", &context)),
+            Ok(1.0)
+        );
+
+        let mut evaluator = AsyncEvaluator::new(Evaluator::builder().with_deterministic().build());
+        evaluator.register_function_with_info(
+            FunctionInfo::new("fetch").capability(Capability::Network),
+            |_| async { Ok(FunctionResult::UnnamedF64(1.0)) },
+        );
+        assert!(block_on(evaluator.evaluate_expression("fetch()", &context))
+            .unwrap_err()
+            .contains("not allowed in deterministic mode"));
+    }
+
+    #[test]
+    fn test_async_deterministic_result() {
+        let mut evaluator = AsyncEvaluator::new(Evaluator::builder().with_deterministic().build());
+        evaluator.register_function("odd_nan", |_| async {
+            Ok(FunctionResult::UnnamedF64(f64::from_bits(
+                0xfff8_0000_0000_0001,
+            )))
+        });
+        let context = HashMap::from([("a".to_string(), 0.0)]);
+
+        let result = block_on(evaluator.evaluate_expression("-a", &context));
+        assert_eq!(result.unwrap().to_bits(), 0.0f64.to_bits());
+        let result = block_on(evaluator.evaluate_expression("odd_nan()", &context));
+        assert_eq!(result.unwrap().to_bits(), f64::NAN.to_bits());
+    }
+
+    #[test]
+    fn test_async_deep_expression() {
+        let evaluator = setup_evaluator(Arc::new(AtomicUsize::new(0)));
+        let expression = (0..50_000)
+            .map(|i| format!("(price > {} OR NOT volume)", i % 100))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let context = HashMap::from([("price".to_string(), 100.0), ("volume".to_string(), 1.0)]);
+        assert_eq!(
+            block_on(evaluator.evaluate_expression(&expression, &context)),
+            Ok(1.0)
+        );
+    }
+
+    #[test]
+    fn test_async_matches_sync_evaluation() {
+        let mut evaluator = setup_evaluator(Arc::new(AtomicUsize::new(0)));
+        let context = HashMap::from([("a".to_string(), 3.0), ("b".to_string(), -2.0)]);
+
+        for input in [
+            "a + b * 2 > 0 AND NOT b > 0",
+            "-(a % 2) ~= -1 OR a / b",
+            "double(value: a) - double(value: b)",
+        ] {
+            let ast = evaluator.evaluator().parse_expression(input).unwrap();
+            let expected = evaluator.evaluator.evaluate_ast(&ast, &context);
+            assert_eq!(block_on(evaluator.evaluate(&ast, &context)), expected);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use crate::ast::{
+    take, ASTNode, FunctionArgValue, FunctionArgs, LogicalOperator, Operator, Symbol,
+};
+use std::fmt::{self, Write};
+
+impl ASTNode {
+    /// Rewrites the expression into a canonical form, so expressions that differ only in
+    /// spelling compare equal:
+    ///
+    /// - groups are removed, since the tree already encodes precedence
+    /// - operands of `+`, `*`, `==`, `!=`, `~=`, `AND` and `OR` are flattened and sorted
+    /// - `<` and `<=` are rewritten as `>` and `>=` with swapped operands
+    /// - negated literals are folded, double negations removed, and `-0` becomes `0`
+    ///
+    /// Reordering `+` and `*` chains can change the last bits of a floating-point result,
+    /// so the canonical form is meant for comparing and de-duplicating rules rather than
+    /// for evaluation.
+    pub fn canonicalize(&self) -> ASTNode {
+        // Each operation is visited twice: first to queue its operands, then, with their
+        // count, to build its canonical form from theirs. The operands of a chain of `+`,
+        // `*`, `AND` or `OR` are queued all at once, so the chain is flattened in one pass.
+        let mut tasks = vec![(self, None)];
+        let mut canonical: Vec<ASTNode> = Vec::new();
+        while let Some((node, operand_count)) = tasks.pop() {
+            let Some(count) = operand_count else {
+                let operands = match node {
+                    ASTNode::Number(value) => {
+                        canonical.push(ASTNode::Number(normalize_zero(*value)));
+                        continue;
+                    }
+                    ASTNode::Identifier(_) => {
+                        canonical.push(node.clone());
+                        continue;
+                    }
+                    ASTNode::FunctionCall { name, args } => {
+                        canonical.push(canonical_call(name.clone(), args));
+                        continue;
+                    }
+                    ASTNode::BinaryOperation {
+                        operator: Operator::Add | Operator::Multiply,
+                        ..
+                    }
+                    | ASTNode::LogicalOperation { .. } => chain_operands(node),
+                    _ => node.operands().collect(),
+                };
+                tasks.push((node, Some(operands.len())));
+                tasks.extend(operands.into_iter().rev().map(|operand| (operand, None)));
+                continue;
+            };
+
+            let mut operands = canonical.split_off(canonical.len() - count);
+            let node = match node {
+                ASTNode::Group(_) => take_last(&mut operands),
+                ASTNode::Negate(_) => match take_last(&mut operands) {
+                    ASTNode::Number(value) => ASTNode::Number(normalize_zero(-value)),
+                    mut inner => match &mut inner {
+                        ASTNode::Negate(double) => take(double),
+                        _ => ASTNode::Negate(Box::new(inner)),
+                    },
+                },
+                ASTNode::NotOperation(_) => {
+                    ASTNode::NotOperation(Box::new(take_last(&mut operands)))
+                }
+                ASTNode::BinaryOperation { operator, .. } => match operator {
+                    Operator::LessThan | Operator::LessThanOrEqual => {
+                        let right = take_last(&mut operands);
+                        let left = take_last(&mut operands);
+                        let operator = match operator {
+                            Operator::LessThan => Operator::GreaterThan,
+                            _ => Operator::GreaterThanOrEqual,
+                        };
+                        binary(right, operator, left)
+                    }
+                    Operator::Add
+                    | Operator::Multiply
+                    | Operator::Equal
+                    | Operator::NotEqual
+                    | Operator::ApproxEqual => {
+                        let mut chain = Vec::new();
+                        for operand in operands {
+                            collect_chain(operand, *operator, &mut chain);
+                        }
+                        sort_operands(&mut chain);
+                        fold(chain, |left, right| binary(left, *operator, right))
+                    }
+                    _ => {
+                        let right = take_last(&mut operands);
+                        let left = take_last(&mut operands);
+                        binary(left, *operator, right)
+                    }
+                },
+                ASTNode::LogicalOperation { operator, .. } => {
+                    let mut chain = Vec::new();
+                    for operand in operands {
+                        collect_logical_chain(operand, *operator, &mut chain);
+                    }
+                    sort_operands(&mut chain);
+                    fold(chain, |left, right| ASTNode::LogicalOperation {
+                        left: Box::new(left),
+                        operator: *operator,
+                        right: Box::new(right),
+                    })
+                }
+                ASTNode::CustomOperation { operator, .. } => {
+                    let right = take_last(&mut operands);
+                    ASTNode::CustomOperation {
+                        left: Box::new(take_last(&mut operands)),
+                        operator: operator.clone(),
+                        right: Box::new(right),
+                    }
+                }
+                ASTNode::PropertyAccess { property, .. } => ASTNode::PropertyAccess {
+                    base: Box::new(take_last(&mut operands)),
+                    property: property.clone(),
+                },
+                ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {
+                    unreachable!("nodes without operands are built when first visited")
+                }
+            };
+            canonical.push(node);
+        }
+        canonical
+            .pop()
+            .expect("the root is built after its operands")
+    }
+
+    /// Returns a 64-bit hash of the canonical form, for use as a cache or de-duplication key.
+    ///
+    /// Expressions that differ only in whitespace, named-argument order, operator spelling
+    /// (`&&` or `AND`) or anything else `canonicalize` normalizes share a fingerprint. The
+    /// value is FNV-1a over the canonical text, so it is stable across processes, platforms
+    /// and releases.
+    pub fn fingerprint(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        // Hashes the canonical text as it is written, without building it
+        struct Fnv(u64);
+
+        impl fmt::Write for Fnv {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                for byte in s.bytes() {
+                    self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(PRIME);
+                }
+                Ok(())
+            }
+        }
+
+        let mut hash = Fnv(OFFSET_BASIS);
+        write!(hash, "{}", self.canonicalize()).expect("hashing cannot fail");
+        hash.0
+    }
+}
+
+/// Returns whether two expressions are equal after canonicalization, e.g.
+/// `a + b > 1 AND c` and `(c) AND 1 < b + a`.
+///
+/// This is a syntactic check on the canonical form: it does not prove equivalences that
+/// need algebra, such as `a * 2` and `a + a`.
+pub fn semantically_equal(a: &ASTNode, b: &ASTNode) -> bool {
+    a.canonicalize() == b.canonicalize()
+}
+
+/// Returns a function call with `-0` arguments written as `0`.
+fn canonical_call(name: Symbol, args: &FunctionArgs) -> ASTNode {
+    ASTNode::FunctionCall {
+        name,
+        args: FunctionArgs {
+            args: args
+                .args
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        FunctionArgValue::Number(value) => {
+                            FunctionArgValue::Number(normalize_zero(*value))
+                        }
+                        value => value.clone(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        },
+    }
+}
+
+fn normalize_zero(value: f64) -> f64 {
+    if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+fn binary(left: ASTNode, operator: Operator, right: ASTNode) -> ASTNode {
+    ASTNode::BinaryOperation {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+/// Collects the operands of a chain of `operator`. Only `+` and `*` are associative; for
+/// `==`, `!=` and `~=` the two operands are collected as they are.
+fn collect_chain(node: ASTNode, operator: Operator, operands: &mut Vec<ASTNode>) {
+    let mut nodes = vec![node];
+    while let Some(mut node) = nodes.pop() {
+        match &mut node {
+            ASTNode::BinaryOperation {
+                left,
+                operator: inner,
+                right,
+            } if *inner == operator && matches!(operator, Operator::Add | Operator::Multiply) => {
+                nodes.extend([take(right), take(left)]);
+            }
+            _ => operands.push(node),
+        }
+    }
+}
+
+fn collect_logical_chain(node: ASTNode, operator: LogicalOperator, operands: &mut Vec<ASTNode>) {
+    let mut nodes = vec![node];
+    while let Some(mut node) = nodes.pop() {
+        match &mut node {
+            ASTNode::LogicalOperation {
+                left,
+                operator: inner,
+                right,
+            } if *inner == operator => nodes.extend([take(right), take(left)]),
+            _ => operands.push(node),
+        }
+    }
+}
+
+/// Returns the operands of the chain of `node`'s operator that `node` heads, in order and
+/// looking through groups, e.g. `a`, `b` and `c` for `a + (b + c)`.
+fn chain_operands(node: &ASTNode) -> Vec<&ASTNode> {
+    let same_operator = |other: &ASTNode| match (node, other) {
+        (
+            ASTNode::BinaryOperation { operator: a, .. },
+            ASTNode::BinaryOperation { operator: b, .. },
+        ) => a == b,
+        (
+            ASTNode::LogicalOperation { operator: a, .. },
+            ASTNode::LogicalOperation { operator: b, .. },
+        ) => a == b,
+        _ => false,
+    };
+
+    let mut operands = Vec::new();
+    let mut nodes = vec![node];
+    while let Some(mut current) = nodes.pop() {
+        while let ASTNode::Group(inner) = current {
+            current = inner;
+        }
+        if same_operator(current) {
+            nodes.extend(current.operands().rev());
+        } else {
+            operands.push(current);
+        }
+    }
+    operands
+}
+
+/// Removes the last of the operands of a node being built.
+fn take_last(operands: &mut Vec<ASTNode>) -> ASTNode {
+    operands.pop().expect("every operand is built")
+}
+
+/// Orders operands by their printed form, which is deterministic for canonical trees.
+fn sort_operands(operands: &mut [ASTNode]) {
+    operands.sort_by_cached_key(|operand| operand.to_string());
+}
+
+/// Rebuilds a left-associative chain from its operands.
+fn fold(operands: Vec<ASTNode>, combine: impl Fn(ASTNode, ASTNode) -> ASTNode) -> ASTNode {
+    let mut operands = operands.into_iter();
+    let first = operands.next().expect("a chain has at least two operands");
+    operands.fold(first, combine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Parser;
+
+    fn canonical(input: &str) -> String {
+        Parser::parse_expression(input)
+            .unwrap()
+            .canonicalize()
+            .to_string()
+    }
+
+    fn equal(a: &str, b: &str) -> bool {
+        semantically_equal(
+            &Parser::parse_expression(a).unwrap(),
+            &Parser::parse_expression(b).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        assert_eq!(canonical("b + a"), "a + b");
+        assert_eq!(canonical("c * (b * a)"), "a * b * c");
+        assert_eq!(canonical("volume < price"), "price > volume");
+        assert_eq!(canonical("1 <= x"), "x >= 1");
+        assert_eq!(canonical("b - a"), "b - a");
+        assert_eq!(canonical("--x"), "x");
+        assert_eq!(canonical("-(0)"), "0");
+        assert_eq!(canonical("z OR (y OR x)"), "x OR y OR z");
+        assert_eq!(canonical("NOT (b == a)"), "NOT a == b");
+        assert_eq!(
+            canonical("rsi(period: 14) < 30 AND close > sma"),
+            "30 > rsi(period: 14) AND close > sma"
+        );
+    }
+
+    #[test]
+    fn test_semantically_equal() {
+        assert!(equal("a + b > 1 AND c", "(c) AND 1 < b + a"));
+        assert!(equal(
+            "macd(short: 12, long: 26).signal > 0",
+            "0 < macd(long: 26, short: 12).signal"
+        ));
+        assert!(equal("x * -1", "-1 * x"));
+        assert!(equal("a OR b OR c", "c OR (b OR a)"));
+
+        assert!(!equal("a - b", "b - a"));
+        assert!(!equal("a / b", "b / a"));
+        assert!(!equal("a AND b OR c", "a AND (b OR c)"));
+        assert!(!equal("a * 2", "a + a"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = |input: &str| Parser::parse_expression(input).unwrap().fingerprint();
+
+        let base = fingerprint("price > 100 AND rsi(period: 14, source: close) < 30");
+        assert_eq!(
+            fingerprint("price>100 && rsi(source: close, period: 14)<30"),
+            base
+        );
+        assert_eq!(
+            fingerprint("30 > rsi(period: 14, source: close) AND (price > 100)"),
+            base
+        );
+        assert_ne!(
+            fingerprint("price > 101 AND rsi(period: 14, source: close) < 30"),
+            base
+        );
+        assert_ne!(
+            fingerprint("price > 100 OR rsi(period: 14, source: close) < 30"),
+            base
+        );
+
+        // Pinned so that an accidental change to the hash or canonical form is caught
+        assert_eq!(fingerprint("a + b"), 0xe223_da09_ebd5_bc3f);
+    }
+
+    #[test]
+    fn test_canonicalize_is_idempotent() {
+        for input in [
+            "b + a * c > d OR NOT e < f",
+            "(x + y) * (y + x) == -(z)",
+            "f(b: y, a: -0) + g(x: 1).v",
+        ] {
+            let once = Parser::parse_expression(input).unwrap().canonicalize();
+            assert_eq!(once.canonicalize(), once);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use crate::ast::{
+    property_path, ASTNode, Evaluator, FunctionArgValue, FunctionArgs, LogicalOperator, Operator,
+    DEFAULT_EPSILON,
+};
+use std::collections::HashMap;
+
+impl Evaluator {
+    /// Generates the source of a Rust function computing the expression, e.g. from a build
+    /// script for a fixed rule set, so rules run as native code with no parsing at runtime.
+    ///
+    /// The function reads variables from a `VariableProvider`. If the expression calls
+    /// functions, it also takes the `Evaluator` to call them through:
+    ///
+    /// ```text
+    /// pub fn name(variables: &dyn quantixis_rs::ast::VariableProvider) -> Result<f64, String>
+    /// pub fn name(evaluator: &quantixis_rs::ast::Evaluator, variables: &dyn quantixis_rs::ast::VariableProvider) -> Result<f64, String>
+    /// ```
+    ///
+    /// Function calls are checked against the registered functions, and the equality
+    /// tolerance is fixed at generation time. Custom operators are not supported.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::Evaluator;
+    ///
+    /// let evaluator = Evaluator::new(100);
+    /// let ast = evaluator.parse_expression("price > 100 AND volume < 5000").unwrap();
+    /// let source = evaluator.generate_rust(&ast, "liquid_breakout").unwrap();
+    /// assert!(source.starts_with("pub fn liquid_breakout(variables: &dyn"));
+    /// ```
+    pub fn generate_rust(&self, ast: &ASTNode, name: &str) -> Result<String, String> {
+        if !is_identifier(name) {
+            return Err(format!("Invalid function name: {}", name));
+        }
+        let mut codegen = Codegen {
+            evaluator: self,
+            lines: Vec::new(),
+            variables: HashMap::new(),
+            temporaries: 0,
+            calls_functions: false,
+        };
+        let result = codegen.emit(ast)?;
+
+        let mut source = String::new();
+        if codegen.calls_functions {
+            source.push_str(&format!(
+                "pub fn {}(\n    evaluator: &quantixis_rs::ast::Evaluator,\n    variables: &dyn quantixis_rs::ast::VariableProvider,\n) -> Result<f64, String> {{\n",
+                name
+            ));
+            source.push_str(
+                "    use quantixis_rs::ast::{FunctionArgValue, FunctionArgs, FunctionResult};\n",
+            );
+            source.push_str("    use std::collections::HashMap;\n\n");
+        } else {
+            source.push_str(&format!(
+                "pub fn {}(variables: &dyn quantixis_rs::ast::VariableProvider) -> Result<f64, String> {{\n",
+                name
+            ));
+        }
+        for line in &codegen.lines {
+            source.push_str("    ");
+            source.push_str(line);
+            source.push('\n');
+        }
+        source.push_str(&format!("    Ok({})\n}}\n", result));
+        Ok(source)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Pops the expression for an operand, which the traversal order guarantees is there.
+fn pop(values: &mut Vec<String>) -> String {
+    values
+        .pop()
+        .expect("operands are emitted before their operation")
+}
+
+/// Formats a number as a Rust `f64` expression.
+fn literal(n: f64) -> String {
+    if n.is_nan() {
+        "f64::NAN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 {
+            "f64::INFINITY"
+        } else {
+            "f64::NEG_INFINITY"
+        }
+        .to_string()
+    } else if n < 0.0 {
+        format!("({:?})", n)
+    } else {
+        format!("{:?}", n)
+    }
+}
+
+struct Codegen<'a> {
+    evaluator: &'a Evaluator,
+    /// Statements of the function body, one per line.
+    lines: Vec<String>,
+    /// Variables already read, by name, so each is looked up once.
+    variables: HashMap<String, String>,
+    temporaries: usize,
+    calls_functions: bool,
+}
+
+impl Codegen<'_> {
+    /// Emits statements computing `ast` and returns an expression for its value, either a
+    /// literal or a local variable.
+    ///
+    /// Statements are emitted in post-order from an explicit stack, so deep trees cannot
+    /// overflow the stack.
+    fn emit(&mut self, ast: &ASTNode) -> Result<String, String> {
+        // Each operation is visited twice: first to queue its operands, then, with `true`,
+        // to emit it from the expressions for their values
+        let mut tasks = vec![(ast, false)];
+        let mut values: Vec<String> = Vec::new();
+        while let Some((node, operands_done)) = tasks.pop() {
+            let value = match node {
+                ASTNode::Group(inner) => {
+                    tasks.push((inner, false));
+                    continue;
+                }
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_done =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                    continue;
+                }
+                ASTNode::Number(n) => literal(*n),
+                ASTNode::Identifier(name) => self.variable(name),
+                ASTNode::BinaryOperation {
+                    operator, right, ..
+                } => {
+                    let r = pop(&mut values);
+                    let l = pop(&mut values);
+                    let value = self.binary(
+                        *operator,
+                        &l,
+                        &r,
+                        matches!(**right, ASTNode::Number(n) if n != 0.0),
+                    );
+                    self.bind(value)
+                }
+                ASTNode::LogicalOperation { operator, .. } => {
+                    let r = pop(&mut values);
+                    let l = pop(&mut values);
+                    let symbol = match operator {
+                        LogicalOperator::And => "&&",
+                        LogicalOperator::Or => "||",
+                    };
+                    self.bind(format!(
+                        "({} != 0.0 {} {} != 0.0) as i32 as f64",
+                        l, symbol, r
+                    ))
+                }
+                ASTNode::CustomOperation { operator, .. } => {
+                    return Err(format!("Operator {} cannot be generated as Rust", operator))
+                }
+                ASTNode::NotOperation(_) => {
+                    let inner = pop(&mut values);
+                    self.bind(format!("({} == 0.0) as i32 as f64", inner))
+                }
+                ASTNode::Negate(_) => {
+                    let inner = pop(&mut values);
+                    self.bind(format!("-{}", inner))
+                }
+                ASTNode::FunctionCall { name, args } => {
+                    let call = self.call(name, args)?;
+                    self.bind(format!(
+                        "match {} {{ FunctionResult::UnnamedF64(value) => value, FunctionResult::NamedF64Map(_) => return Err(\"Expected single value, got multi-value\".to_string()) }}",
+                        call
+                    ))
+                }
+                ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                    (ASTNode::FunctionCall { name, args }, path) => {
+                        let call = self.call(name, args)?;
+                        self.bind(format!(
+                            "match {} {{ FunctionResult::NamedF64Map(map) => map.get({:?}).copied().ok_or_else(|| {:?}.to_string())?, FunctionResult::UnnamedF64(_) => return Err(\"Expected multi-value, got single value\".to_string()) }}",
+                            call,
+                            path,
+                            format!("Property {} not found in result", path)
+                        ))
+                    }
+                    (ASTNode::Identifier(name), path) => {
+                        self.variable(&format!("{}.{}", name, path))
+                    }
+                    _ => return Err("Base must be a function call or identifier".to_string()),
+                },
+            };
+            values.push(value);
+        }
+        Ok(pop(&mut values))
+    }
+
+    /// Binds `value` to a new local variable and returns its name.
+    fn bind(&mut self, value: String) -> String {
+        let name = format!("t{}", self.temporaries);
+        self.temporaries += 1;
+        self.lines.push(format!("let {} = {};", name, value));
+        name
+    }
+
+    fn variable(&mut self, name: &str) -> String {
+        if let Some(local) = self.variables.get(name) {
+            return local.clone();
+        }
+        let local = self.bind(format!(
+            "variables.get({:?}).ok_or_else(|| {:?}.to_string())?",
+            name,
+            format!("Identifier '{}' not found in context", name)
+        ));
+        self.variables.insert(name.to_string(), local.clone());
+        local
+    }
+
+    /// Returns the expression for a binary operation. Division checks for zero unless the
+    /// divisor is a non-zero constant.
+    fn binary(&self, operator: Operator, l: &str, r: &str, nonzero_divisor: bool) -> String {
+        let approx = |epsilon: f64| {
+            format!(
+                "({l} == {r} || ({l} - {r}).abs() <= {} * {l}.abs().max({r}.abs()).max(1.0)) as i32 as f64",
+                literal(epsilon),
+                l = l,
+                r = r
+            )
+        };
+        let checked = |symbol: &str, error: &str| {
+            if nonzero_divisor {
+                format!("{} {} {}", l, symbol, r)
+            } else {
+                format!(
+                    "if {r} == 0.0 {{ return Err({:?}.to_string()); }} else {{ {l} {} {r} }}",
+                    error,
+                    symbol,
+                    l = l,
+                    r = r
+                )
+            }
+        };
+        let epsilon = self.evaluator.epsilon;
+        match operator {
+            Operator::Add => format!("{} + {}", l, r),
+            Operator::Subtract => format!("{} - {}", l, r),
+            Operator::Multiply => format!("{} * {}", l, r),
+            Operator::Divide => checked("/", "Division by zero"),
+            Operator::Modulo => checked("%", "Modulo by zero"),
+            Operator::GreaterThan => format!("({} > {}) as i32 as f64", l, r),
+            Operator::LessThan => format!("({} < {}) as i32 as f64", l, r),
+            Operator::GreaterThanOrEqual => format!("({} >= {}) as i32 as f64", l, r),
+            Operator::LessThanOrEqual => format!("({} <= {}) as i32 as f64", l, r),
+            Operator::Equal => match epsilon {
+                Some(epsilon) => approx(epsilon),
+                None => format!("({} == {}) as i32 as f64", l, r),
+            },
+            Operator::NotEqual => match epsilon {
+                Some(epsilon) => format!("1.0 - {}", approx(epsilon)),
+                None => format!("({} != {}) as i32 as f64", l, r),
+            },
+            Operator::ApproxEqual => approx(epsilon.unwrap_or(DEFAULT_EPSILON)),
+        }
+    }
+
+    /// Emits the arguments of a call and returns the call expression, which yields a
+    /// `FunctionResult`.
+    fn call(&mut self, name: &str, args: &FunctionArgs) -> Result<String, String> {
+        self.evaluator.function(name)?;
+        self.calls_functions = true;
+
+        let mut entries: Vec<_> = args.args.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        let constants = entries
+            .iter()
+            .map(|(arg_name, value)| format!("({:?}.to_string(), {})", arg_name, arg_value(value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let args_name = format!("args{}", self.temporaries);
+        self.lines.push(format!(
+            "let mut {} = FunctionArgs::with_args(HashMap::from([{}]));",
+            args_name, constants
+        ));
+        // Identifiers bound in the context are passed as values, as by the evaluator
+        for (arg_name, value) in entries {
+            if let FunctionArgValue::Identifier(ident) = value {
+                self.lines.push(format!(
+                    "if let Some(value) = variables.get({:?}) {{ {}.insert({:?}, value); }}",
+                    ident, args_name, arg_name
+                ));
+            }
+        }
+        Ok(format!(
+            "evaluator.call_function({:?}, &{})?",
+            name, args_name
+        ))
+    }
+}
+
+fn arg_value(value: &FunctionArgValue) -> String {
+    match value {
+        FunctionArgValue::Number(n) => format!("FunctionArgValue::Number({})", literal(*n)),
+        FunctionArgValue::Identifier(ident) => {
+            format!("FunctionArgValue::Identifier({:?}.to_string())", ident)
+        }
+        FunctionArgValue::Array(values) => format!(
+            "FunctionArgValue::Array(vec![{}])",
+            values
+                .iter()
+                .map(|n| literal(*n))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        FunctionArgValue::KeyValue(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            format!(
+                "FunctionArgValue::KeyValue(HashMap::from([{}]))",
+                entries
+                    .iter()
+                    .map(|(key, n)| format!("({:?}.to_string(), {})", key, literal(**n)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+        FunctionArgValue::Boolean(b) => format!("FunctionArgValue::Boolean({})", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::FunctionResult;
+
+    mod generated {
+        include!("testdata/generated_rule.rs");
+    }
+
+    const RULE: &str = "(close - open) / open > 2% AND bands(data: close, width: 2).upper >= bar.high OR NOT volume";
+
+    fn setup_evaluator() -> Evaluator {
+        Evaluator::builder()
+            .with_function("bands", |args| {
+                let data = args.get_number("data")?;
+                let width = args.get_number("width")?;
+                Ok(FunctionResult::NamedF64Map(HashMap::from([
+                    ("upper".to_string(), data + width),
+                    ("lower".to_string(), data - width),
+                ])))
+            })
+            .build()
+    }
+
+    #[test]
+    fn test_generate_rust() {
+        let evaluator = setup_evaluator();
+        let ast = evaluator.parse_expression(RULE).unwrap();
+        let source = evaluator.generate_rust(&ast, "rule").unwrap();
+        assert_eq!(source, include_str!("testdata/generated_rule.rs"));
+    }
+
+    #[test]
+    fn test_generated_matches_evaluator() {
+        let mut evaluator = setup_evaluator();
+        let ast = evaluator.parse_expression(RULE).unwrap();
+
+        for (close, high, volume) in [
+            (103.0, 104.0, 0.0),
+            (103.0, 106.0, 1.0),
+            (101.0, 102.0, 5.0),
+        ] {
+            let context = HashMap::from([
+                ("open".to_string(), 100.0),
+                ("close".to_string(), close),
+                ("bar.high".to_string(), high),
+                ("volume".to_string(), volume),
+            ]);
+            assert_eq!(
+                generated::rule(&evaluator, &context),
+                evaluator.evaluate_ast(&ast, &context)
+            );
+        }
+
+        let context = HashMap::from([("open".to_string(), 0.0)]);
+        assert_eq!(
+            generated::rule(&evaluator, &context).unwrap_err(),
+            "Identifier 'close' not found in context"
+        );
+    }
+
+    #[test]
+    fn test_generate_rust_errors() {
+        let evaluator = setup_evaluator();
+        let ast = evaluator.parse_expression("band(data: 1).upper").unwrap();
+        assert_eq!(
+            evaluator.generate_rust(&ast, "rule").unwrap_err(),
+            "Function band not registered. Did you mean 'bands'?"
+        );
+        assert_eq!(
+            evaluator.generate_rust(&ast, "2rule").unwrap_err(),
+            "Invalid function name: 2rule"
+        );
+    }
+}
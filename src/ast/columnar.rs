@@ -0,0 +1,296 @@
+use crate::ast::{
+    approx_eq, cross_section,
+    deterministic::normalize,
+    evaluator::{bind_args, bound_identifiers},
+    property_path, unknown_identifier, ASTNode, Evaluator, FunctionArgs, FunctionResult,
+    LogicalOperator, Operator, DEFAULT_EPSILON,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Intermediate result of columnar evaluation. Literals stay scalar and are broadcast lazily.
+enum Column<'a> {
+    Scalar(f64),
+    Values(Cow<'a, [f64]>),
+}
+
+impl Column<'_> {
+    fn contains(&self, target: f64) -> bool {
+        match self {
+            Column::Scalar(value) => *value == target,
+            Column::Values(values) => values.contains(&target),
+        }
+    }
+
+    fn into_vec(self, rows: usize) -> Vec<f64> {
+        match self {
+            Column::Scalar(value) => vec![value; rows],
+            Column::Values(values) => values.into_owned(),
+        }
+    }
+}
+
+impl Evaluator {
+    /// Evaluates an expression for every row of a column-oriented context.
+    ///
+    /// Each variable maps to a column (`&[f64]`) and all columns must have the same length.
+    /// Operators run as tight loops over whole columns, so `close > sma * 1.02` over
+    /// thousands of symbols avoids walking the AST once per symbol. Function calls are still
+    /// invoked once per row.
+    pub fn evaluate_columns(
+        &self,
+        ast: &ASTNode,
+        columns: &HashMap<String, &[f64]>,
+    ) -> Result<Vec<f64>, String> {
+        let mut lengths = columns.values().map(|column| column.len());
+        let rows = lengths.next().unwrap_or(0);
+        if lengths.any(|len| len != rows) {
+            return Err("All columns must have the same length".to_string());
+        }
+        self.check_units(ast)?;
+
+        self.evaluate_rows(ast, columns, rows)
+    }
+
+    /// Evaluates an expression for `rows` rows, which also sizes the result when no column is
+    /// referenced.
+    pub(crate) fn evaluate_rows(
+        &self,
+        ast: &ASTNode,
+        columns: &HashMap<String, &[f64]>,
+        rows: usize,
+    ) -> Result<Vec<f64>, String> {
+        let mut values = self.evaluate_column(ast, columns, rows)?.into_vec(rows);
+        if self.deterministic {
+            for value in &mut values {
+                *value = normalize(*value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn evaluate_column<'a>(
+        &self,
+        ast: &ASTNode,
+        columns: &HashMap<String, &'a [f64]>,
+        rows: usize,
+    ) -> Result<Column<'a>, String> {
+        let mut values: Vec<Column<'a>> = Vec::new();
+        // Each operation is visited twice: first to queue its operands, then, with `true`,
+        // to apply it to their columns
+        let mut tasks = vec![(ast, false)];
+        while let Some((node, operands_done)) = tasks.pop() {
+            match node {
+                ASTNode::Group(inner) => tasks.push((inner, false)),
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::CustomOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_done =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                }
+                _ => {
+                    let value = self.column_step(node, &mut values, columns, rows)?;
+                    values.push(value);
+                }
+            }
+        }
+        pop(&mut values)
+    }
+
+    /// Computes the column of a leaf, or of an operation from the columns of its operands on
+    /// top of `values`.
+    fn column_step<'a>(
+        &self,
+        node: &ASTNode,
+        values: &mut Vec<Column<'a>>,
+        columns: &HashMap<String, &'a [f64]>,
+        rows: usize,
+    ) -> Result<Column<'a>, String> {
+        match node {
+            ASTNode::Number(n) => Ok(Column::Scalar(*n)),
+
+            ASTNode::Identifier(ident) => column(ident, columns),
+
+            ASTNode::BinaryOperation { operator, .. } => {
+                let right = pop(values)?;
+                let left = pop(values)?;
+                apply_operator(*operator, left, right, self.epsilon)
+            }
+
+            ASTNode::LogicalOperation { operator, .. } => {
+                let right = pop(values)?;
+                let left = pop(values)?;
+                Ok(match operator {
+                    LogicalOperator::And => {
+                        zip_map(left, right, |a, b| (a != 0.0 && b != 0.0) as i32 as f64)
+                    }
+                    LogicalOperator::Or => {
+                        zip_map(left, right, |a, b| (a != 0.0 || b != 0.0) as i32 as f64)
+                    }
+                })
+            }
+
+            ASTNode::CustomOperation { operator, .. } => {
+                let function = self.custom_operator(operator)?;
+                let right = pop(values)?.into_vec(rows);
+                let left = pop(values)?.into_vec(rows);
+                let values = left
+                    .into_iter()
+                    .zip(right)
+                    .map(|(a, b)| function(a, b))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                Ok(Column::Values(Cow::Owned(values)))
+            }
+
+            ASTNode::NotOperation(_) => Ok(map(pop(values)?, |value| (value == 0.0) as i32 as f64)),
+
+            ASTNode::Negate(_) => Ok(map(pop(values)?, |value| -value)),
+
+            ASTNode::Group(_) => unreachable!("groups are unwrapped by evaluate_column"),
+
+            ASTNode::FunctionCall { name, args } => {
+                if self.resolve_function(name).is_err() {
+                    if let Some(values) = cross_section::evaluate(name, args, columns, rows) {
+                        return Ok(Column::Values(Cow::Owned(values?)));
+                    }
+                }
+                let identifiers = bound_identifiers(self.function_info(name), args);
+                let values = (0..rows)
+                    .map(
+                        |row| match self.call_row(name, args, &identifiers, columns, row)? {
+                            FunctionResult::UnnamedF64(value) => Ok(value),
+                            FunctionResult::NamedF64Map(_) => {
+                                Err("Expected single value, got multi-value".to_string())
+                            }
+                        },
+                    )
+                    .collect::<Result<Vec<f64>, String>>()?;
+                Ok(Column::Values(Cow::Owned(values)))
+            }
+
+            ASTNode::PropertyAccess { base, property } => {
+                let (name, args, property) = match property_path(base, property) {
+                    (ASTNode::FunctionCall { name, args }, path) => (name, args, path),
+                    (ASTNode::Identifier(name), path) => {
+                        return column(&format!("{}.{}", name, path), columns);
+                    }
+                    _ => return Err("Base must be a function call or identifier".to_string()),
+                };
+                let identifiers = bound_identifiers(self.function_info(name), args);
+                let values = (0..rows)
+                    .map(
+                        |row| match self.call_row(name, args, &identifiers, columns, row)? {
+                            FunctionResult::NamedF64Map(map) => {
+                                map.get(&property).copied().ok_or_else(|| {
+                                    format!("Property {} not found in result", property)
+                                })
+                            }
+                            FunctionResult::UnnamedF64(_) => {
+                                Err("Expected multi-value, got single value".to_string())
+                            }
+                        },
+                    )
+                    .collect::<Result<Vec<f64>, String>>()?;
+                Ok(Column::Values(Cow::Owned(values)))
+            }
+        }
+    }
+
+    /// Calls a function with `identifiers`, its arguments read from the context, resolved
+    /// against a single row.
+    fn call_row(
+        &self,
+        name: &str,
+        args: &FunctionArgs,
+        identifiers: &[(String, String)],
+        columns: &HashMap<String, &[f64]>,
+        row: usize,
+    ) -> Result<FunctionResult, String> {
+        let function = self.function(name)?;
+        let row_args = bind_args(args, identifiers, |ident| {
+            columns.get(ident).map(|column| column[row])
+        })
+        .map_err(|ident| unknown_identifier(ident, columns.keys()))?;
+
+        function(&row_args)
+    }
+}
+
+/// Borrows the column of a variable.
+fn column<'a>(name: &str, columns: &HashMap<String, &'a [f64]>) -> Result<Column<'a>, String> {
+    columns
+        .get(name)
+        .map(|values| Column::Values(Cow::Borrowed(*values)))
+        .ok_or_else(|| unknown_identifier(name, columns.keys()))
+}
+
+/// Pops an operand column, which the traversal order guarantees is there.
+fn pop<'a>(values: &mut Vec<Column<'a>>) -> Result<Column<'a>, String> {
+    values
+        .pop()
+        .ok_or_else(|| "Unexpected end of expression".to_string())
+}
+
+fn apply_operator<'a>(
+    operator: Operator,
+    left: Column<'a>,
+    right: Column<'a>,
+    epsilon: Option<f64>,
+) -> Result<Column<'a>, String> {
+    // Check the divisor up front so the loops below stay branch-free
+    match operator {
+        Operator::Divide if right.contains(0.0) => return Err("Division by zero".to_string()),
+        Operator::Modulo if right.contains(0.0) => return Err("Modulo by zero".to_string()),
+        _ => {}
+    }
+
+    let bool_to_f64 = |condition: bool| condition as i32 as f64;
+    let tolerance = epsilon.unwrap_or(DEFAULT_EPSILON);
+    Ok(match operator {
+        Operator::Add => zip_map(left, right, |a, b| a + b),
+        Operator::Subtract => zip_map(left, right, |a, b| a - b),
+        Operator::Multiply => zip_map(left, right, |a, b| a * b),
+        Operator::Divide => zip_map(left, right, |a, b| a / b),
+        Operator::Modulo => zip_map(left, right, |a, b| a % b),
+        Operator::GreaterThan => zip_map(left, right, |a, b| bool_to_f64(a > b)),
+        Operator::LessThan => zip_map(left, right, |a, b| bool_to_f64(a < b)),
+        Operator::GreaterThanOrEqual => zip_map(left, right, |a, b| bool_to_f64(a >= b)),
+        Operator::LessThanOrEqual => zip_map(left, right, |a, b| bool_to_f64(a <= b)),
+        Operator::Equal if epsilon.is_some() => {
+            zip_map(left, right, |a, b| bool_to_f64(approx_eq(a, b, tolerance)))
+        }
+        Operator::Equal => zip_map(left, right, |a, b| bool_to_f64(a == b)),
+        Operator::NotEqual if epsilon.is_some() => {
+            zip_map(left, right, |a, b| bool_to_f64(!approx_eq(a, b, tolerance)))
+        }
+        Operator::NotEqual => zip_map(left, right, |a, b| bool_to_f64(a != b)),
+        Operator::ApproxEqual => {
+            zip_map(left, right, |a, b| bool_to_f64(approx_eq(a, b, tolerance)))
+        }
+    })
+}
+
+fn map<'a>(column: Column<'a>, f: impl Fn(f64) -> f64) -> Column<'a> {
+    match column {
+        Column::Scalar(value) => Column::Scalar(f(value)),
+        Column::Values(values) => {
+            Column::Values(Cow::Owned(values.iter().map(|v| f(*v)).collect()))
+        }
+    }
+}
+
+fn zip_map<'a>(left: Column<'a>, right: Column<'a>, f: impl Fn(f64, f64) -> f64) -> Column<'a> {
+    let values = match (&left, &right) {
+        (Column::Scalar(a), Column::Scalar(b)) => return Column::Scalar(f(*a, *b)),
+        (Column::Values(a), Column::Scalar(b)) => a.iter().map(|a| f(*a, *b)).collect(),
+        (Column::Scalar(a), Column::Values(b)) => b.iter().map(|b| f(*a, *b)).collect(),
+        (Column::Values(a), Column::Values(b)) => {
+            a.iter().zip(b.iter()).map(|(a, b)| f(*a, *b)).collect()
+        }
+    };
+    Column::Values(Cow::Owned(values))
+}
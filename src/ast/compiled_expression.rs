@@ -0,0 +1,293 @@
+use crate::ast::{
+    deterministic::normalize,
+    evaluator::{bind_args, bound_identifiers},
+    metrics::Recorder,
+    property_path, unknown_identifier, ASTNode, Evaluator, FunctionArgs, FunctionResult,
+    ProgramCache, VariableProvider,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A step of a compiled expression, which pops its operands from the stack and pushes its
+/// value.
+type Step = Box<dyn Fn(&dyn VariableProvider, &mut Vec<f64>) -> Result<(), String> + Send + Sync>;
+type CompiledCall =
+    Box<dyn Fn(&dyn VariableProvider) -> Result<FunctionResult, String> + Send + Sync>;
+
+/// An expression compiled into a sequence of Rust closures, each operation following its
+/// operands and passing values on a stack.
+///
+/// Function lookups and argument layout are resolved once at compile time, so evaluating
+/// only runs the closures and skips the AST match entirely. Running the steps in a loop
+/// rather than nesting them keeps deep trees from overflowing the stack.
+pub struct CompiledExpression {
+    steps: Vec<Step>,
+    /// Largest number of values on the stack at once
+    stack_size: usize,
+    /// Whether the result is normalized, see `Evaluator::set_deterministic`
+    deterministic: bool,
+    pub(crate) recorder: Option<Recorder>,
+}
+
+impl CompiledExpression {
+    /// Evaluates the compiled expression against a context.
+    pub fn evaluate(&self, context: &HashMap<String, f64>) -> Result<f64, String> {
+        self.evaluate_with(context)
+    }
+
+    /// Evaluates the compiled expression, fetching only the variables it uses from
+    /// `variables`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "execute", level = "trace", skip_all, err)
+    )]
+    pub fn evaluate_with(&self, variables: &dyn VariableProvider) -> Result<f64, String> {
+        let Some(recorder) = &self.recorder else {
+            return self.run(variables);
+        };
+        let started = Instant::now();
+        let result = self.run(variables);
+        recorder.record(started.elapsed());
+        result
+    }
+
+    fn run(&self, variables: &dyn VariableProvider) -> Result<f64, String> {
+        let mut stack = Vec::with_capacity(self.stack_size);
+        for step in &self.steps {
+            step(variables, &mut stack)?;
+        }
+        let value = pop(&mut stack);
+        Ok(if self.deterministic {
+            normalize(value)
+        } else {
+            value
+        })
+    }
+}
+
+/// Pops a value, which the order of the steps guarantees is there.
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack
+        .pop()
+        .expect("operands are computed before their operation")
+}
+
+/// A step pushing a value that does not depend on others.
+fn value<F>(compute: F) -> Step
+where
+    F: Fn(&dyn VariableProvider) -> Result<f64, String> + Send + Sync + 'static,
+{
+    Box::new(move |variables, stack| {
+        stack.push(compute(variables)?);
+        Ok(())
+    })
+}
+
+/// A step combining the top two values.
+fn binary<F>(apply: F) -> Step
+where
+    F: Fn(f64, f64) -> Result<f64, String> + Send + Sync + 'static,
+{
+    Box::new(move |_, stack| {
+        let right = pop(stack);
+        let left = pop(stack);
+        stack.push(apply(left, right)?);
+        Ok(())
+    })
+}
+
+/// A step replacing the top value.
+fn unary(apply: fn(f64) -> f64) -> Step {
+    Box::new(move |_, stack| {
+        let value = pop(stack);
+        stack.push(apply(value));
+        Ok(())
+    })
+}
+
+impl Evaluator {
+    /// Compiles an AST into a `CompiledExpression` bound to the currently registered functions.
+    ///
+    /// Unregistered functions are reported here rather than at evaluation time.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "compile", level = "debug", skip_all, err)
+    )]
+    pub fn compile(&self, ast: &ASTNode) -> Result<CompiledExpression, String> {
+        self.check_units(ast)?;
+        let mut steps = Vec::new();
+        let (mut stack, mut stack_size) = (0, 0);
+        // Each operation is visited twice: first to queue its operands, then, with `true`,
+        // to compile it
+        let mut tasks = vec![(ast, false)];
+        while let Some((node, operands_compiled)) = tasks.pop() {
+            match node {
+                ASTNode::Group(inner) => tasks.push((inner, false)),
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::CustomOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_compiled =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                }
+                _ => {
+                    steps.push(self.compile_step(node)?);
+                    // Each step pops its operands and pushes one value
+                    stack = match node {
+                        ASTNode::NotOperation(_) | ASTNode::Negate(_) => stack,
+                        ASTNode::BinaryOperation { .. }
+                        | ASTNode::LogicalOperation { .. }
+                        | ASTNode::CustomOperation { .. } => stack - 1,
+                        _ => stack + 1,
+                    };
+                    stack_size = stack_size.max(stack);
+                }
+            }
+        }
+        Ok(CompiledExpression {
+            steps,
+            stack_size,
+            deterministic: self.deterministic,
+            recorder: None,
+        })
+    }
+
+    /// Evaluates an expression against a `VariableProvider`, which is only asked for the
+    /// variables the expression uses. The compiled expression is reused from the program
+    /// cache.
+    pub fn evaluate_expression_with(
+        &self,
+        expression: &str,
+        variables: &dyn VariableProvider,
+    ) -> Result<f64, String> {
+        self.compile_cached(expression)?.evaluate_with(variables)
+    }
+
+    /// Returns the compiled expression from the program cache, which holds up to the
+    /// `max_cache_size` given to `Evaluator::new`, parsing and compiling it on a miss.
+    /// The cache is cleared whenever functions, operators or settings change.
+    pub fn compile_cached(&self, expression: &str) -> Result<Arc<CompiledExpression>, String> {
+        self.programs.get_or_compile(self, expression)
+    }
+
+    /// The cache used by `compile_cached`.
+    pub fn program_cache(&self) -> &ProgramCache {
+        &self.programs
+    }
+
+    /// Replaces the program cache with an empty one holding up to `max_cache_size`
+    /// compiled expressions.
+    pub fn set_cache_size(&mut self, max_cache_size: usize) {
+        self.programs = ProgramCache::new(max_cache_size);
+    }
+
+    /// Parses and compiles an expression.
+    ///
+    /// With metrics enabled, parse and compile times are recorded and the returned
+    /// `CompiledExpression` records its own executions under the same expression.
+    pub fn compile_expression(&self, expression: &str) -> Result<CompiledExpression, String> {
+        let started = Instant::now();
+        let ast = self.parse_expression(expression)?;
+        let parsed = Instant::now();
+        let mut compiled = self.compile(&ast)?;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_parse(expression, parsed - started);
+            metrics.record_compile(expression, parsed.elapsed());
+            compiled.recorder = Some(Recorder::new(metrics, expression, ast));
+        }
+        Ok(compiled)
+    }
+
+    /// Compiles a node whose operands, if any, precede it on the stack. Property accesses
+    /// are compiled whole.
+    fn compile_step(&self, ast: &ASTNode) -> Result<Step, String> {
+        Ok(match ast {
+            ASTNode::Number(n) => {
+                let n = *n;
+                value(move |_| Ok(n))
+            }
+
+            ASTNode::Identifier(ident) => {
+                let ident = ident.clone();
+                value(move |context| {
+                    context
+                        .get(&ident)
+                        .ok_or_else(|| unknown_identifier(&ident, context.names()))
+                })
+            }
+
+            ASTNode::BinaryOperation { operator, .. } => {
+                let (operator, epsilon) = (*operator, self.epsilon);
+                binary(move |left, right| operator.apply_with_tolerance(left, right, epsilon))
+            }
+
+            ASTNode::LogicalOperation { operator, .. } => {
+                let operator = *operator;
+                binary(move |left, right| operator.apply(left, right))
+            }
+
+            ASTNode::CustomOperation { operator, .. } => {
+                let function = self.custom_operator(operator)?.clone();
+                binary(move |left, right| function(left, right))
+            }
+
+            ASTNode::NotOperation(_) => unary(|value| (value == 0.0) as i32 as f64),
+
+            ASTNode::Negate(_) => unary(|value| -value),
+
+            ASTNode::Group(_) => unreachable!("groups compile to their content"),
+
+            ASTNode::FunctionCall { name, args } => {
+                let call = self.compile_call(name, args)?;
+                value(move |context| match call(context)? {
+                    FunctionResult::UnnamedF64(value) => Ok(value),
+                    FunctionResult::NamedF64Map(_) => {
+                        Err("Expected single value, got multi-value".to_string())
+                    }
+                })
+            }
+
+            ASTNode::PropertyAccess { base, property } => {
+                let (name, args, property) = match property_path(base, property) {
+                    (ASTNode::FunctionCall { name, args }, path) => (name, args, path),
+                    (ASTNode::Identifier(name), path) => {
+                        return self.compile_step(&ASTNode::Identifier(
+                            format!("{}.{}", name, path).into(),
+                        ))
+                    }
+                    _ => return Err("Base must be a function call or identifier".to_string()),
+                };
+                self.check_output(name, &property)?;
+                let call = self.compile_call(name, args)?;
+                value(move |context| match call(context)? {
+                    FunctionResult::NamedF64Map(map) => map
+                        .get(&property)
+                        .copied()
+                        .ok_or_else(|| format!("Property {} not found in result", property)),
+                    FunctionResult::UnnamedF64(_) => {
+                        Err("Expected multi-value, got single value".to_string())
+                    }
+                })
+            }
+        })
+    }
+
+    /// Resolves the function once and splits its arguments into constants and context lookups.
+    fn compile_call(&self, name: &str, args: &FunctionArgs) -> Result<CompiledCall, String> {
+        let function = self.function(name)?.clone();
+
+        let identifiers = bound_identifiers(self.function_info(name), args);
+        let constants = args.clone();
+
+        Ok(Box::new(move |context| {
+            let call_args = bind_args(&constants, &identifiers, |ident| context.get(ident))
+                .map_err(|ident| unknown_identifier(ident, context.names()))?;
+            function(&call_args)
+        }))
+    }
+}
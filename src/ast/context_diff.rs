@@ -0,0 +1,135 @@
+use std::collections::{BTreeSet, HashMap};
+
+/// The variables that changed between two snapshots of a context, for incremental
+/// re-evaluation with `RuleProgram::evaluate_incremental`.
+///
+/// ```
+/// use quantixis_rs::ast::ContextDiff;
+/// use std::collections::HashMap;
+///
+/// let old = HashMap::from([("close".to_string(), 100.0), ("volume".to_string(), 5e5)]);
+/// let new = HashMap::from([("close".to_string(), 100.5), ("volume".to_string(), 5e5)]);
+///
+/// let diff = ContextDiff::between(&old, &new);
+/// assert!(diff.touches("close") && !diff.touches("volume"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextDiff {
+    changed: HashMap<String, f64>,
+    removed: BTreeSet<String>,
+}
+
+impl ContextDiff {
+    /// Creates an empty diff, to be filled with `set` and `remove`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Computes the changes turning `old` into `new`. Values are compared bit for bit, so
+    /// a NaN that stays NaN is unchanged.
+    pub fn between(old: &HashMap<String, f64>, new: &HashMap<String, f64>) -> Self {
+        let changed = new
+            .iter()
+            .filter(|(name, value)| {
+                old.get(*name).map(|old| old.to_bits()) != Some(value.to_bits())
+            })
+            .map(|(name, value)| (name.clone(), *value))
+            .collect();
+        let removed = old
+            .keys()
+            .filter(|name| !new.contains_key(*name))
+            .cloned()
+            .collect();
+        Self { changed, removed }
+    }
+
+    /// Records a new value for `name`.
+    pub fn set(&mut self, name: &str, value: f64) {
+        self.removed.remove(name);
+        self.changed.insert(name.to_string(), value);
+    }
+
+    /// Records that `name` is no longer defined.
+    pub fn remove(&mut self, name: &str) {
+        self.changed.remove(name);
+        self.removed.insert(name.to_string());
+    }
+
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Returns whether `name` was set or removed.
+    pub fn touches(&self, name: &str) -> bool {
+        self.changed.contains_key(name) || self.removed.contains(name)
+    }
+
+    /// Iterates over the variables set, with their new values.
+    pub fn changed(&self) -> impl Iterator<Item = (&str, f64)> + '_ {
+        self.changed
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    /// Iterates over the variables removed.
+    pub fn removed(&self) -> impl Iterator<Item = &str> + '_ {
+        self.removed.iter().map(String::as_str)
+    }
+
+    /// Applies the changes to `context`, returning the names whose value actually changed.
+    pub fn apply(&self, context: &mut HashMap<String, f64>) -> BTreeSet<String> {
+        let mut touched = BTreeSet::new();
+        for (name, value) in &self.changed {
+            let previous = context.insert(name.clone(), *value);
+            if previous.map(f64::to_bits) != Some(value.to_bits()) {
+                touched.insert(name.clone());
+            }
+        }
+        for name in &self.removed {
+            if context.remove(name).is_some() {
+                touched.insert(name.clone());
+            }
+        }
+        touched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_diff() {
+        let old = HashMap::from([
+            ("a".to_string(), 1.0),
+            ("b".to_string(), f64::NAN),
+            ("c".to_string(), 3.0),
+        ]);
+        let new = HashMap::from([
+            ("a".to_string(), 2.0),
+            ("b".to_string(), f64::NAN),
+            ("d".to_string(), 4.0),
+        ]);
+
+        let diff = ContextDiff::between(&old, &new);
+        let mut changed: Vec<_> = diff.changed().collect();
+        changed.sort_by(|x, y| x.0.cmp(y.0));
+        assert_eq!(changed, [("a", 2.0), ("d", 4.0)]);
+        assert_eq!(diff.removed().collect::<Vec<_>>(), ["c"]);
+        assert!(!diff.touches("b"));
+
+        let mut context = old.clone();
+        let touched = diff.apply(&mut context);
+        assert_eq!(touched.into_iter().collect::<Vec<_>>(), ["a", "c", "d"]);
+        assert_eq!(context.len(), 3);
+        assert!(ContextDiff::between(&new, &context).is_empty());
+
+        let mut diff = ContextDiff::new();
+        diff.set("a", 2.0);
+        diff.remove("x");
+        assert!(diff.apply(&mut context).is_empty());
+        diff.set("x", 1.0);
+        assert_eq!(diff.removed().count(), 0);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::ast::{did_you_mean, unknown_identifier, ASTNode, Evaluator, VariableProvider};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The type of a field declared in a `ContextSchema`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    /// One value per evaluation, as in a `HashMap<String, f64>` context
+    Number,
+    /// A series of values, passed as a column to `Evaluator::evaluate_columns`
+    Array,
+    /// Nested fields, read with property access such as `user.age`
+    Map(ContextSchema),
+}
+
+/// Declares the variables a context provides, so that expressions can be checked against it
+/// before any data exists.
+///
+/// ```
+/// use quantixis_rs::ast::{ContextSchema, Evaluator};
+///
+/// let schema = ContextSchema::new()
+///     .number("price")
+///     .map("user", ContextSchema::new().number("age"));
+/// let evaluator = Evaluator::new(100);
+///
+/// assert!(evaluator.validate_with_schema("price > 10 AND user.age >= 18", &schema).is_ok());
+/// assert_eq!(
+///     evaluator.validate_with_schema("pricee > 10", &schema),
+///     Err("Identifier 'pricee' not found in context. Did you mean 'price'?".to_string())
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextSchema {
+    fields: BTreeMap<String, FieldType>,
+}
+
+impl ContextSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a number field.
+    pub fn number(self, name: &str) -> Self {
+        self.field(name, FieldType::Number)
+    }
+
+    /// Declares an array field.
+    pub fn array(self, name: &str) -> Self {
+        self.field(name, FieldType::Array)
+    }
+
+    /// Declares a map field with the nested fields of `schema`.
+    pub fn map(self, name: &str, schema: ContextSchema) -> Self {
+        self.field(name, FieldType::Map(schema))
+    }
+
+    /// Declares a field of any type, replacing an earlier field of the same name.
+    pub fn field(mut self, name: &str, field_type: FieldType) -> Self {
+        self.fields.insert(name.to_string(), field_type);
+        self
+    }
+
+    /// Derives a schema from the names a provider offers, each a number, with dotted names
+    /// such as `bar.close` becoming maps.
+    pub fn from_provider<P: VariableProvider + ?Sized>(provider: &P) -> Self {
+        let mut schema = Self::new();
+        for name in provider.names() {
+            let mut fields = &mut schema.fields;
+            let mut parts = name.split('.').peekable();
+            while let Some(part) = parts.next() {
+                if parts.peek().is_none() {
+                    fields.insert(part.to_string(), FieldType::Number);
+                    break;
+                }
+                let field = fields
+                    .entry(part.to_string())
+                    .or_insert_with(|| FieldType::Map(Self::new()));
+                if !matches!(field, FieldType::Map(_)) {
+                    *field = FieldType::Map(Self::new());
+                }
+                fields = match field {
+                    FieldType::Map(nested) => &mut nested.fields,
+                    _ => unreachable!("replaced by a map above"),
+                };
+            }
+        }
+        schema
+    }
+
+    /// Returns the type of a field, following dotted paths into maps.
+    pub fn get(&self, path: &str) -> Option<&FieldType> {
+        let mut schema = self;
+        let mut parts = path.split('.');
+        let mut field = schema.fields.get(parts.next()?)?;
+        for part in parts {
+            schema = match field {
+                FieldType::Map(nested) => nested,
+                _ => return None,
+            };
+            field = schema.fields.get(part)?;
+        }
+        Some(field)
+    }
+
+    /// Returns the dotted paths of every number and array field, in order.
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for (name, field) in &self.fields {
+            match field {
+                FieldType::Map(nested) => paths.extend(
+                    nested
+                        .paths()
+                        .into_iter()
+                        .map(|path| format!("{}.{}", name, path)),
+                ),
+                _ => paths.push(name.clone()),
+            }
+        }
+        paths
+    }
+
+    /// Checks that every variable `ast` reads is a declared number, for evaluation against
+    /// one context at a time. Identifiers passed to symbolic parameters of `evaluator`'s
+    /// functions are not variables.
+    pub fn check(&self, evaluator: &Evaluator, ast: &ASTNode) -> Result<(), String> {
+        self.check_variables(evaluator.variables(ast), FieldType::Number)
+    }
+
+    /// Checks that every variable `ast` reads is a declared array, for
+    /// `Evaluator::evaluate_columns`.
+    pub fn check_columns(&self, evaluator: &Evaluator, ast: &ASTNode) -> Result<(), String> {
+        self.check_variables(evaluator.variables(ast), FieldType::Array)
+    }
+
+    /// Checks that a context only binds declared numbers, e.g. before `ASTNode::partial_eval`,
+    /// where a misspelled name would otherwise silently stay unbound.
+    pub fn check_context<P: VariableProvider + ?Sized>(&self, context: &P) -> Result<(), String> {
+        for name in context.names() {
+            match self.get(name) {
+                Some(FieldType::Number) => {}
+                Some(field_type) => return Err(mismatch(name, field_type, &FieldType::Number)),
+                None => {
+                    let message = format!("Variable '{}' is not declared in the schema", name);
+                    let paths = self.paths();
+                    return Err(match did_you_mean(name, &paths) {
+                        Some(suggestion) => format!("{}. Did you mean '{}'?", message, suggestion),
+                        None => message,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_variables(&self, names: BTreeSet<String>, expected: FieldType) -> Result<(), String> {
+        for name in names {
+            match self.get(&name) {
+                Some(field_type) if *field_type == expected => {}
+                Some(field_type) => return Err(mismatch(&name, field_type, &expected)),
+                None => return Err(unknown_identifier(&name, &self.paths())),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn mismatch(name: &str, actual: &FieldType, expected: &FieldType) -> String {
+    match (actual, expected) {
+        (FieldType::Map(nested), _) => match nested.paths().first() {
+            Some(path) => format!(
+                "'{}' is a map, use one of its fields such as '{}.{}'",
+                name, name, path
+            ),
+            None => format!("'{}' is an empty map", name),
+        },
+        (FieldType::Array, _) => format!(
+            "'{}' is an array, which is only available to evaluate_columns",
+            name
+        ),
+        _ => format!(
+            "'{}' is a number, but evaluate_columns needs an array",
+            name
+        ),
+    }
+}
+
+impl Evaluator {
+    /// Checks an expression like `Evaluator::validate`, and that every variable it reads is a
+    /// number declared in `schema`.
+    pub fn validate_with_schema(
+        &self,
+        expression: &str,
+        schema: &ContextSchema,
+    ) -> Result<(), String> {
+        self.validate(expression)?;
+        schema.check(self, &self.parse_expression(expression)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionInfo, FunctionResult};
+    use std::collections::HashMap;
+
+    fn schema() -> ContextSchema {
+        ContextSchema::new().number("price").array("close").map(
+            "user",
+            ContextSchema::new()
+                .number("age")
+                .map("account", ContextSchema::new().number("balance")),
+        )
+    }
+
+    #[test]
+    fn test_lookup() {
+        let schema = schema();
+        assert_eq!(schema.get("price"), Some(&FieldType::Number));
+        assert_eq!(schema.get("user.account.balance"), Some(&FieldType::Number));
+        assert!(matches!(
+            schema.get("user.account"),
+            Some(FieldType::Map(_))
+        ));
+        assert_eq!(schema.get("price.value"), None);
+        assert_eq!(schema.get("user.name"), None);
+        assert_eq!(
+            schema.paths(),
+            vec!["close", "price", "user.account.balance", "user.age"]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let schema = schema();
+        let evaluator = Evaluator::builder()
+            .with_function_info(
+                FunctionInfo::new("lookup").symbol("table").param("key"),
+                |_| Ok(FunctionResult::UnnamedF64(0.0)),
+            )
+            .build();
+        let check =
+            |expression| schema.check(&evaluator, &evaluator.parse_expression(expression).unwrap());
+        let check_columns = |expression| {
+            schema.check_columns(&evaluator, &evaluator.parse_expression(expression).unwrap())
+        };
+
+        assert!(
+            check("price > 10 AND user.account.balance > sma(period: 5, value: price)").is_ok()
+        );
+        assert_eq!(
+            check("user.agee > 18"),
+            Err(
+                "Identifier 'user.agee' not found in context. Did you mean 'user.age'?".to_string()
+            )
+        );
+        assert_eq!(
+            check("user > 18"),
+            Err(
+                "'user' is a map, use one of its fields such as 'user.account.balance'".to_string()
+            )
+        );
+        assert!(check("close > 10").is_err());
+        assert!(check("lookup(table: prices, key: price) > 0").is_ok());
+        assert_eq!(
+            check("lookup(table: prices, key: prices) > 0"),
+            Err("Identifier 'prices' not found in context. Did you mean 'price'?".to_string())
+        );
+
+        assert!(check_columns("close > 10").is_ok());
+        assert_eq!(
+            check_columns("close > price"),
+            Err("'price' is a number, but evaluate_columns needs an array".to_string())
+        );
+    }
+
+    #[test]
+    fn test_context() {
+        let context = HashMap::from([
+            ("price".to_string(), 10.0),
+            ("user.age".to_string(), 30.0),
+            ("user.account.balance".to_string(), 100.0),
+        ]);
+        let derived = ContextSchema::from_provider(&context);
+        assert_eq!(
+            derived.paths(),
+            vec!["price", "user.account.balance", "user.age"]
+        );
+        assert!(derived.check_context(&context).is_ok());
+
+        let schema = schema();
+        assert!(schema.check_context(&context).is_ok());
+        let typo = HashMap::from([("prices".to_string(), 10.0)]);
+        assert_eq!(
+            schema.check_context(&typo),
+            Err(
+                "Variable 'prices' is not declared in the schema. Did you mean 'price'?"
+                    .to_string()
+            )
+        );
+        let array = HashMap::from([("close".to_string(), 10.0)]);
+        assert!(schema.check_context(&array).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::ast::{ASTNode, Evaluator, FunctionArgValue};
+
+/// Relative costs of the operations of an expression, in units of one arithmetic
+/// operation, for `Evaluator::estimated_cost`.
+///
+/// Functions registered with `FunctionInfo::cost` use their declared cost instead of
+/// `function_call`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostModel {
+    pub number: f64,
+    /// Looking a variable up in the context, also charged for each identifier argument
+    pub variable: f64,
+    /// Arithmetic, comparison, logical operators, `NOT` and negation
+    pub operator: f64,
+    pub custom_operator: f64,
+    pub function_call: f64,
+    pub property_access: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            number: 0.0,
+            variable: 2.0,
+            operator: 1.0,
+            custom_operator: 5.0,
+            function_call: 50.0,
+            property_access: 2.0,
+        }
+    }
+}
+
+impl Evaluator {
+    /// Estimates the cost of evaluating `ast` with the default `CostModel`, so that
+    /// services can budget or reject expensive user expressions before running them.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::{Evaluator, FunctionInfo, FunctionResult, Parser};
+    ///
+    /// let mut evaluator = Evaluator::new(100);
+    /// evaluator.register_function_with_info(FunctionInfo::new("sma").cost(500.0), |_| {
+    ///     Ok(FunctionResult::UnnamedF64(0.0))
+    /// });
+    ///
+    /// let cheap = Parser::parse_expression("close > 100").unwrap();
+    /// let expensive = Parser::parse_expression("sma(period: 200) > close").unwrap();
+    /// assert_eq!(evaluator.estimated_cost(&cheap), 3.0);
+    /// assert_eq!(evaluator.estimated_cost(&expensive), 503.0);
+    /// ```
+    pub fn estimated_cost(&self, ast: &ASTNode) -> f64 {
+        self.estimated_cost_with(ast, &CostModel::default())
+    }
+
+    /// Estimates the cost of evaluating `ast` with the given costs.
+    pub fn estimated_cost_with(&self, ast: &ASTNode, model: &CostModel) -> f64 {
+        let mut cost = 0.0;
+        let mut nodes = vec![ast];
+        while let Some(node) = nodes.pop() {
+            cost += self.node_cost(node, model);
+            match node {
+                ASTNode::BinaryOperation { left, right, .. }
+                | ASTNode::LogicalOperation { left, right, .. }
+                | ASTNode::CustomOperation { left, right, .. } => nodes.extend([&**left, &**right]),
+                ASTNode::NotOperation(inner)
+                | ASTNode::Negate(inner)
+                | ASTNode::Group(inner)
+                | ASTNode::PropertyAccess { base: inner, .. } => nodes.push(inner),
+                ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {}
+            }
+        }
+        cost
+    }
+
+    /// Cost of `ast` itself, without its operands.
+    pub(crate) fn node_cost(&self, ast: &ASTNode, model: &CostModel) -> f64 {
+        match ast {
+            ASTNode::Number(_) => model.number,
+            ASTNode::Identifier(_) => model.variable,
+            ASTNode::BinaryOperation { .. }
+            | ASTNode::LogicalOperation { .. }
+            | ASTNode::NotOperation(_)
+            | ASTNode::Negate(_) => model.operator,
+            ASTNode::CustomOperation { .. } => model.custom_operator,
+            ASTNode::Group(_) => 0.0,
+            ASTNode::FunctionCall { name, args } => {
+                let call = self
+                    .function_info(name)
+                    .and_then(|info| info.cost)
+                    .unwrap_or(model.function_call);
+                let lookups = args
+                    .args
+                    .values()
+                    .filter(|value| matches!(value, FunctionArgValue::Identifier(_)))
+                    .count();
+                call + lookups as f64 * model.variable
+            }
+            ASTNode::PropertyAccess { .. } => model.property_access,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionInfo, FunctionResult, Parser};
+
+    #[test]
+    fn test_estimated_cost() {
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function("bands", |_| Ok(FunctionResult::UnnamedF64(0.0)));
+        evaluator.register_function_with_info(FunctionInfo::new("rsi").cost(10.0), |_| {
+            Ok(FunctionResult::UnnamedF64(0.0))
+        });
+        let cost =
+            |expression| evaluator.estimated_cost(&Parser::parse_expression(expression).unwrap());
+
+        assert_eq!(cost("1"), 0.0);
+        assert_eq!(cost("-(a + 1)"), 4.0);
+        assert_eq!(cost("NOT a AND b"), 6.0);
+        assert_eq!(cost("bands(value: close).upper > rsi(period: 14)"), 65.0);
+
+        let model = CostModel {
+            function_call: 1.0,
+            ..CostModel::default()
+        };
+        let ast = Parser::parse_expression("bands(value: close, k: 2) > 0").unwrap();
+        assert_eq!(evaluator.estimated_cost_with(&ast, &model), 4.0);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::ast::{unknown_identifier, Evaluator, FunctionArgValue, FunctionArgs};
+use std::collections::HashMap;
+
+/// Functions computed across all rows of a columnar evaluation rather than row by row.
+const FUNCTIONS: [&str; 4] = ["rank", "percentile_rank", "zscore_cross", "top_n"];
+
+impl Evaluator {
+    /// Evaluates an expression for every member of a universe, e.g. one context per symbol,
+    /// returning one result per member in order.
+    ///
+    /// Besides the registered functions, expressions can use cross-sectional functions,
+    /// which compare a variable across the whole universe:
+    ///
+    /// - `rank(value: x)`: 1 for the largest `x`, with ties sharing the best rank.
+    /// - `percentile_rank(value: x)`: fraction of the other members with a lower `x`, from 0
+    ///   to 1.
+    /// - `zscore_cross(value: x)`: distance of `x` from the universe mean, in standard
+    ///   deviations.
+    /// - `top_n(value: x, n: 10)`: 1 if `x` ranks in the top `n`, otherwise 0.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::Evaluator;
+    /// use std::collections::HashMap;
+    ///
+    /// let universe: Vec<_> = [("AAPL", 0.12), ("MSFT", 0.08), ("NVDA", 0.31)]
+    ///     .into_iter()
+    ///     .map(|(_symbol, momentum)| HashMap::from([("momentum".to_string(), momentum)]))
+    ///     .collect();
+    ///
+    /// let evaluator = Evaluator::new(100);
+    /// let selected = evaluator
+    ///     .evaluate_cross_section("rank(value: momentum) <= 2", &universe)
+    ///     .unwrap();
+    /// assert_eq!(selected, vec![1.0, 0.0, 1.0]);
+    /// ```
+    ///
+    /// The same functions are available in `Evaluator::evaluate_columns`, where each row is a
+    /// member. A registered function of the same name takes precedence.
+    pub fn evaluate_cross_section(
+        &self,
+        expression: &str,
+        universe: &[HashMap<String, f64>],
+    ) -> Result<Vec<f64>, String> {
+        let ast = self.parse_expression(expression)?;
+
+        // Only variables known for every member become columns
+        let mut names: Vec<&String> = universe.first().map_or(Vec::new(), |m| m.keys().collect());
+        names.retain(|name| universe.iter().all(|member| member.contains_key(*name)));
+        let values: Vec<(&String, Vec<f64>)> = names
+            .into_iter()
+            .map(|name| (name, universe.iter().map(|member| member[name]).collect()))
+            .collect();
+        let columns: HashMap<String, &[f64]> = values
+            .iter()
+            .map(|(name, column)| ((*name).clone(), column.as_slice()))
+            .collect();
+
+        self.evaluate_rows(&ast, &columns, universe.len())
+    }
+}
+
+/// Computes a cross-sectional function over all rows, or returns `None` if `name` is not one.
+pub(crate) fn evaluate(
+    name: &str,
+    args: &FunctionArgs,
+    columns: &HashMap<String, &[f64]>,
+    rows: usize,
+) -> Option<Result<Vec<f64>, String>> {
+    if !FUNCTIONS.contains(&name) {
+        return None;
+    }
+    Some(
+        column_arg(args, "value", columns, rows).and_then(|values| match name {
+            "rank" => Ok(rank(&values)),
+            "percentile_rank" => Ok(percentile_rank(&values)),
+            "zscore_cross" => Ok(zscore(&values)),
+            "top_n" => {
+                let n = args.get_number("n")?;
+                Ok(rank(&values)
+                    .into_iter()
+                    .map(|rank| (rank <= n) as i32 as f64)
+                    .collect())
+            }
+            _ => unreachable!("listed in FUNCTIONS"),
+        }),
+    )
+}
+
+/// Returns the argument as a column, reading identifiers from `columns` and broadcasting
+/// numbers.
+fn column_arg(
+    args: &FunctionArgs,
+    key: &str,
+    columns: &HashMap<String, &[f64]>,
+    rows: usize,
+) -> Result<Vec<f64>, String> {
+    match args.args.get(key) {
+        Some(FunctionArgValue::Identifier(ident)) => columns
+            .get(ident)
+            .map(|column| column.to_vec())
+            .ok_or_else(|| unknown_identifier(ident, columns.keys())),
+        Some(value) => Ok(vec![value.as_number()?; rows]),
+        None => Err(format!("Missing argument: {}", key)),
+    }
+}
+
+/// Ranks values from 1 for the largest, giving ties the same rank. NaN values rank NaN.
+fn rank(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).filter(|&i| !values[i].is_nan()).collect();
+    order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+
+    let mut ranks = vec![f64::NAN; values.len()];
+    for (position, &i) in order.iter().enumerate() {
+        ranks[i] = match position {
+            0 => 1.0,
+            _ if values[order[position - 1]] == values[i] => ranks[order[position - 1]],
+            _ => (position + 1) as f64,
+        };
+    }
+    ranks
+}
+
+fn percentile_rank(values: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    sorted.sort_by(f64::total_cmp);
+    let others = sorted.len().saturating_sub(1).max(1) as f64;
+
+    values
+        .iter()
+        .map(|value| {
+            if value.is_nan() {
+                f64::NAN
+            } else {
+                sorted.partition_point(|v| v < value) as f64 / others
+            }
+        })
+        .collect()
+}
+
+fn zscore(values: &[f64]) -> Vec<f64> {
+    let present: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    let n = present.len() as f64;
+    let mean = present.iter().sum::<f64>() / n;
+    let std_dev = (present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+    values
+        .iter()
+        .map(|value| {
+            if std_dev == 0.0 && !value.is_nan() {
+                0.0
+            } else {
+                (value - mean) / std_dev
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::FunctionResult;
+
+    fn universe(momentum: &[f64]) -> Vec<HashMap<String, f64>> {
+        momentum
+            .iter()
+            .enumerate()
+            .map(|(i, momentum)| {
+                HashMap::from([
+                    ("momentum".to_string(), *momentum),
+                    ("volume".to_string(), (i + 1) as f64 * 1000.0),
+                ])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cross_sectional_functions() {
+        let evaluator = Evaluator::new(100);
+        let universe = universe(&[0.1, 0.3, 0.2, 0.3, -0.2]);
+        let evaluate = |expression| evaluator.evaluate_cross_section(expression, &universe);
+
+        assert_eq!(
+            evaluate("rank(value: momentum)").unwrap(),
+            vec![4.0, 1.0, 3.0, 1.0, 5.0]
+        );
+        assert_eq!(
+            evaluate("percentile_rank(value: momentum)").unwrap(),
+            vec![0.25, 0.75, 0.5, 0.75, 0.0]
+        );
+        assert_eq!(
+            evaluate("top_n(value: momentum, n: 2) AND volume > 3000").unwrap(),
+            vec![0.0, 0.0, 0.0, 1.0, 0.0]
+        );
+
+        let zscores = evaluate("zscore_cross(value: momentum)").unwrap();
+        let mean = zscores.iter().sum::<f64>() / 5.0;
+        assert!(mean.abs() < 1e-12);
+        assert!(zscores[1] > 0.8 && zscores[4] < -1.8);
+        assert_eq!(evaluate("zscore_cross(value: 5)").unwrap(), vec![0.0; 5]);
+
+        assert_eq!(evaluate("1 + 1").unwrap(), vec![2.0; 5]);
+        assert_eq!(
+            evaluator
+                .evaluate_cross_section("1 + 1", &[HashMap::new(), HashMap::new()])
+                .unwrap(),
+            vec![2.0; 2]
+        );
+        assert_eq!(
+            evaluate("rank(value: momentun)").unwrap_err(),
+            "Identifier 'momentun' not found in context. Did you mean 'momentum'?"
+        );
+        assert_eq!(
+            evaluate("top_n(value: momentum)").unwrap_err(),
+            "Missing argument: n"
+        );
+    }
+
+    #[test]
+    fn test_cross_sectional_columns() {
+        let momentum = [f64::NAN, 2.0, 1.0];
+        let columns = HashMap::from([("momentum".to_string(), &momentum[..])]);
+
+        let evaluator = Evaluator::new(100);
+        let ast = evaluator.parse_expression("rank(value: momentum)").unwrap();
+        let ranks = evaluator.evaluate_columns(&ast, &columns).unwrap();
+        assert!(ranks[0].is_nan());
+        assert_eq!(ranks[1..], [1.0, 2.0]);
+
+        // Registered functions take precedence
+        let evaluator = Evaluator::builder()
+            .with_function("rank", |_| Ok(FunctionResult::UnnamedF64(0.0)))
+            .build();
+        assert_eq!(
+            evaluator.evaluate_columns(&ast, &columns).unwrap(),
+            vec![0.0; 3]
+        );
+    }
+}
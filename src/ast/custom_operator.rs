@@ -0,0 +1,177 @@
+use crate::ast::Evaluator;
+use std::sync::Arc;
+
+/// Implementation of a custom binary operator, called with its evaluated operands.
+pub type OperatorFunction = Arc<dyn Fn(f64, f64) -> Result<f64, String> + Send + Sync>;
+
+/// Precedence of the comparison operators (`>`, `==`, ...). Higher precedences bind tighter.
+pub const COMPARISON_PRECEDENCE: u8 = 10;
+/// Precedence of `+` and `-`.
+pub const ADDITIVE_PRECEDENCE: u8 = 20;
+/// Precedence of `*`, `/` and `%`.
+pub const MULTIPLICATIVE_PRECEDENCE: u8 = 30;
+
+/// Characters a custom operator symbol is made of, as accepted by the grammar.
+const OPERATOR_CHARS: &str = "~<>=!?^@";
+
+#[derive(Clone)]
+pub(crate) struct CustomOperator {
+    pub(crate) precedence: u8,
+    pub(crate) function: OperatorFunction,
+}
+
+impl Evaluator {
+    /// Registers a binary operator, such as an approximate comparison, without changing
+    /// the grammar.
+    ///
+    /// `symbol` is a run of the characters `~ < > = ! ? ^ @` that is not a built-in
+    /// comparison. Operators are left-associative; `precedence` places the operator
+    /// relative to `COMPARISON_PRECEDENCE`, `ADDITIVE_PRECEDENCE` and
+    /// `MULTIPLICATIVE_PRECEDENCE`, and all of them bind looser than unary minus and
+    /// tighter than `NOT`, `AND` and `OR`.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::{Evaluator, COMPARISON_PRECEDENCE};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut evaluator = Evaluator::new(100);
+    /// evaluator
+    ///     .register_operator(">~", COMPARISON_PRECEDENCE, |a, b| {
+    ///         Ok((a > b * 0.99) as i32 as f64)
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let context = HashMap::from([("close".to_string(), 99.5), ("sma".to_string(), 100.0)]);
+    /// assert_eq!(evaluator.evaluate_expression("close >~ sma", &context).unwrap(), 1.0);
+    /// ```
+    pub fn register_operator<F>(
+        &mut self,
+        symbol: &str,
+        precedence: u8,
+        function: F,
+    ) -> Result<(), String>
+    where
+        F: Fn(f64, f64) -> Result<f64, String> + Send + Sync + 'static,
+    {
+        if symbol.is_empty() || !symbol.chars().all(|c| OPERATOR_CHARS.contains(c)) {
+            return Err(format!(
+                "Invalid operator '{}': operators are made of the characters {}",
+                symbol, OPERATOR_CHARS
+            ));
+        }
+        if matches!(symbol, ">" | ">=" | "<" | "<=" | "==" | "!=" | "~=") {
+            return Err(format!("Operator '{}' is built in", symbol));
+        }
+
+        self.operators.insert(
+            symbol.to_string(),
+            CustomOperator {
+                precedence,
+                function: Arc::new(function),
+            },
+        );
+        self.programs.clear();
+        Ok(())
+    }
+
+    /// Returns the implementation of a registered custom operator.
+    pub(crate) fn custom_operator(&self, symbol: &str) -> Result<&OperatorFunction, String> {
+        self.operators
+            .get(symbol)
+            .map(|operator| &operator.function)
+            .ok_or_else(|| format!("Unknown operator: {}", symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{ASTNode, Parser};
+    use std::collections::HashMap;
+
+    fn setup_evaluator() -> Evaluator {
+        let mut evaluator = Evaluator::new(100);
+        evaluator
+            .register_operator("~~", COMPARISON_PRECEDENCE, |a, b| {
+                Ok(((a - b).abs() < 0.01) as i32 as f64)
+            })
+            .unwrap();
+        evaluator
+            .register_operator("^", MULTIPLICATIVE_PRECEDENCE + 1, |a, b| Ok(a.powf(b)))
+            .unwrap();
+        evaluator
+    }
+
+    #[test]
+    fn test_custom_operator_precedence() {
+        let evaluator = setup_evaluator();
+        let parse = |input: &str| evaluator.parse_expression(input).unwrap().to_string();
+
+        assert_eq!(parse("a ~~ b + 1"), "a ~~ (b + 1)");
+        assert_eq!(parse("2 * x ^ 2"), "2 * (x ^ 2)");
+        assert_eq!(parse("x ^ 2 ^ 3"), "(x ^ 2) ^ 3");
+        assert_eq!(parse("a ~~ b AND NOT c ~~ d"), "a ~~ b AND NOT (c ~~ d)");
+    }
+
+    #[test]
+    fn test_custom_operator_evaluation() {
+        let mut evaluator = setup_evaluator();
+        let context = HashMap::from([("x".to_string(), 3.0), ("y".to_string(), 3.004)]);
+
+        assert_eq!(
+            evaluator.evaluate_expression("x ~~ y", &context).unwrap(),
+            1.0
+        );
+        assert_eq!(
+            evaluator
+                .evaluate_expression("x ~~ y + 1", &context)
+                .unwrap(),
+            0.0
+        );
+        assert_eq!(
+            evaluator
+                .evaluate_expression("2 * x ^ 2", &context)
+                .unwrap(),
+            18.0
+        );
+
+        let ast = evaluator.parse_expression("x ^ 2 ~~ 9").unwrap();
+        let compiled = evaluator.compile(&ast).unwrap();
+        assert_eq!(compiled.evaluate(&context).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_unknown_operator() {
+        let mut evaluator = setup_evaluator();
+        let err = evaluator
+            .evaluate_expression("x >~ y", &HashMap::new())
+            .unwrap_err();
+        assert_eq!(err, "Unknown operator: >~");
+
+        // Without a registry every custom operator is unknown
+        assert!(Parser::parse_expression("x ~~ y").is_err());
+
+        let ast = ASTNode::CustomOperation {
+            left: Box::new(ASTNode::Number(1.0)),
+            operator: "<>".to_string(),
+            right: Box::new(ASTNode::Number(2.0)),
+        };
+        assert_eq!(
+            evaluator.compile(&ast).err().unwrap(),
+            "Unknown operator: <>"
+        );
+    }
+
+    #[test]
+    fn test_register_invalid_operator() {
+        let mut evaluator = Evaluator::new(100);
+        assert!(evaluator.register_operator("", 1, |a, _| Ok(a)).is_err());
+        assert!(evaluator.register_operator("+~", 1, |a, _| Ok(a)).is_err());
+        assert_eq!(
+            evaluator
+                .register_operator(">=", 1, |a, _| Ok(a))
+                .unwrap_err(),
+            "Operator '>=' is built in"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::ast::{ASTNode, Parser};
+use std::collections::{BTreeSet, HashMap};
+
+/// Identifies an expression added to a `DependencyIndex`.
+pub type ExpressionId = usize;
+
+/// Tracks which variables each expression reads, so that when a variable changes only the
+/// expressions depending on it are re-evaluated.
+///
+/// ```
+/// use quantixis_rs::ast::DependencyIndex;
+///
+/// let mut index = DependencyIndex::new();
+/// let breakout = index.add_expression("close > high_20").unwrap();
+/// let liquid = index.add_expression("volume > 1000000").unwrap();
+///
+/// assert_eq!(index.on_update("close", 101.5), vec![breakout]);
+/// // Unchanged values trigger nothing
+/// assert!(index.on_update("close", 101.5).is_empty());
+/// assert_eq!(index.on_update("volume", 2e6), vec![liquid]);
+/// ```
+#[derive(Debug, Default)]
+pub struct DependencyIndex {
+    /// Expression ids by the variables they read, in ascending order.
+    dependents: HashMap<String, Vec<ExpressionId>>,
+    /// Variables read by each expression, `None` once removed.
+    expressions: Vec<Option<BTreeSet<String>>>,
+    /// Last value seen for each variable.
+    values: HashMap<String, f64>,
+}
+
+impl DependencyIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an expression and returns its id.
+    pub fn add(&mut self, ast: &ASTNode) -> ExpressionId {
+        let id = self.expressions.len();
+        let variables = ast.variables();
+        for name in &variables {
+            self.dependents.entry(name.clone()).or_default().push(id);
+        }
+        self.expressions.push(Some(variables));
+        id
+    }
+
+    /// Parses an expression and adds it.
+    pub fn add_expression(&mut self, expression: &str) -> Result<ExpressionId, String> {
+        Ok(self.add(&Parser::parse_expression(expression)?))
+    }
+
+    /// Removes an expression. Its id is not reused.
+    pub fn remove(&mut self, id: ExpressionId) {
+        let Some(variables) = self.expressions.get_mut(id).and_then(Option::take) else {
+            return;
+        };
+        for name in variables {
+            if let Some(ids) = self.dependents.get_mut(&name) {
+                ids.retain(|dependent| *dependent != id);
+                if ids.is_empty() {
+                    self.dependents.remove(&name);
+                }
+            }
+        }
+    }
+
+    /// Returns the variables an expression reads, or `None` if it is not in the index.
+    pub fn variables(&self, id: ExpressionId) -> Option<&BTreeSet<String>> {
+        self.expressions.get(id)?.as_ref()
+    }
+
+    /// Returns the expressions reading `name`, in ascending order.
+    pub fn dependents(&self, name: &str) -> &[ExpressionId] {
+        self.dependents.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Records a new value for a variable and returns the expressions to re-evaluate, which
+    /// is none if the value did not change.
+    pub fn on_update(&mut self, name: &str, value: f64) -> Vec<ExpressionId> {
+        // `to_bits` so that a NaN that stays NaN counts as unchanged
+        match self.values.insert(name.to_string(), value) {
+            Some(previous) if previous.to_bits() == value.to_bits() => Vec::new(),
+            _ => self.dependents(name).to_vec(),
+        }
+    }
+
+    /// Records several updates at once and returns the expressions to re-evaluate, each
+    /// listed once, in ascending order.
+    pub fn on_updates<'a>(
+        &mut self,
+        updates: impl IntoIterator<Item = (&'a str, f64)>,
+    ) -> Vec<ExpressionId> {
+        let mut ids: Vec<ExpressionId> = updates
+            .into_iter()
+            .flat_map(|(name, value)| self.on_update(name, value))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::LogicalOperator;
+
+    #[test]
+    fn test_dependency_index() {
+        let mut index = DependencyIndex::new();
+        let trend = index
+            .add_expression("sma(data: close, period: 20) > bar.open AND NOT halted")
+            .unwrap();
+        let spread = index.add_expression("(ask - bid) / bid < 0.1%").unwrap();
+        let both = index.add_expression("close > ask").unwrap();
+
+        assert_eq!(
+            index.variables(trend).unwrap().iter().collect::<Vec<_>>(),
+            ["bar.open", "close", "halted"]
+        );
+        assert_eq!(index.dependents("close"), [trend, both]);
+        assert!(index.dependents("sma").is_empty());
+
+        assert_eq!(index.on_update("close", 10.0), vec![trend, both]);
+        assert!(index.on_update("close", 10.0).is_empty());
+        assert!(index.on_update("unused", 1.0).is_empty());
+        assert_eq!(
+            index.on_updates([("ask", 10.2), ("bid", 10.1), ("bar.open", 9.0)]),
+            vec![trend, spread, both]
+        );
+
+        index.remove(both);
+        assert_eq!(index.variables(both), None);
+        assert_eq!(index.on_update("close", 11.0), vec![trend]);
+        assert_eq!(index.on_update("ask", 10.3), vec![spread]);
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let mut ast = ASTNode::Identifier("x0".into());
+        for i in 1..100_000 {
+            ast = ASTNode::LogicalOperation {
+                left: Box::new(ast),
+                operator: LogicalOperator::And,
+                right: Box::new(ASTNode::Identifier(format!("x{}", i % 3).into())),
+            };
+        }
+        let mut index = DependencyIndex::new();
+        let id = index.add(&ast);
+        assert_eq!(
+            index.variables(id).unwrap().iter().collect::<Vec<_>>(),
+            ["x0", "x1", "x2"]
+        );
+    }
+}
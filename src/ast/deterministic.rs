@@ -0,0 +1,200 @@
+use crate::ast::Evaluator;
+use std::collections::HashMap;
+
+/// The result of `Evaluator::evaluate_audited`, with a checksum of what produced it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Audited {
+    pub value: f64,
+    /// See `audit_checksum`
+    pub checksum: u64,
+}
+
+impl Evaluator {
+    /// Switches to deterministic evaluation, so that an evaluation can be reproduced
+    /// exactly from its expression and context:
+    ///
+    /// - Functions that are not `Capability::Pure`, such as `random()` or `now()`, are
+    ///   rejected like those above `set_max_capability`.
+    /// - Results are normalized: `-0.0` becomes `0.0` and every NaN the same NaN, so that
+    ///   equal results have equal bits and checksums. This covers compiled expressions
+    ///   compiled afterwards, columnar and async evaluation too.
+    pub fn set_deterministic(&mut self) {
+        self.deterministic = true;
+        self.programs.clear();
+    }
+
+    /// Normalizes a result in deterministic mode, and returns it unchanged otherwise.
+    pub(crate) fn normalize_result(&self, value: f64) -> f64 {
+        if self.deterministic {
+            normalize(value)
+        } else {
+            value
+        }
+    }
+
+    /// Evaluates `expression` like `evaluate_expression` and returns the result with its
+    /// `audit_checksum`, for regulated users who need to show later why a signal fired.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::{audit_checksum, Evaluator};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut evaluator = Evaluator::builder().with_deterministic().build();
+    /// let context = HashMap::from([("close".to_string(), 101.0), ("sma".to_string(), 100.0)]);
+    ///
+    /// let audited = evaluator.evaluate_audited("close > sma", &context).unwrap();
+    /// assert_eq!(audited.value, 1.0);
+    ///
+    /// // An auditor with the stored expression and context recomputes the same checksum
+    /// assert_eq!(audit_checksum("close > sma", &context, 1.0), audited.checksum);
+    /// ```
+    pub fn evaluate_audited(
+        &mut self,
+        expression: &str,
+        context: &HashMap<String, f64>,
+    ) -> Result<Audited, String> {
+        let value = self.evaluate_expression(expression, context)?;
+        Ok(Audited {
+            value,
+            checksum: audit_checksum(expression, context, value),
+        })
+    }
+}
+
+/// A checksum of an expression, its context and its result that is stable across runs,
+/// platforms and crate versions. Context entries are hashed in name order, and the
+/// result after normalizing zeros and NaNs.
+pub fn audit_checksum(expression: &str, context: &HashMap<String, f64>, value: f64) -> u64 {
+    // 64-bit FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    write(expression.as_bytes());
+    let mut names: Vec<&String> = context.keys().collect();
+    names.sort();
+    for name in names {
+        write(&[0]);
+        write(name.as_bytes());
+        write(&[0]);
+        write(&normalize(context[name]).to_bits().to_le_bytes());
+    }
+    write(&[1]);
+    write(&normalize(value).to_bits().to_le_bytes());
+    hash
+}
+
+/// Gives every zero and every NaN a single representation.
+pub(crate) fn normalize(value: f64) -> f64 {
+    if value.is_nan() {
+        f64::NAN
+    } else if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Capability, FunctionInfo, FunctionResult};
+
+    #[test]
+    fn test_deterministic_mode() {
+        let mut evaluator = Evaluator::builder()
+            .with_function_info(
+                FunctionInfo::new("now").capability(Capability::ReadsClock),
+                |_| Ok(FunctionResult::UnnamedF64(1.7e9)),
+            )
+            .with_function_info(
+                FunctionInfo::new("random").capability(Capability::Random),
+                |_| Ok(FunctionResult::UnnamedF64(0.5)),
+            )
+            .build();
+        let context = HashMap::from([("a".to_string(), 0.0)]);
+
+        assert_eq!(evaluator.evaluate_expression("-a", &context), Ok(-0.0));
+        assert!(evaluator
+            .evaluate_expression("-a", &context)
+            .unwrap()
+            .is_sign_negative());
+        assert!(evaluator
+            .evaluate_expression("random() > a", &context)
+            .is_ok());
+
+        evaluator.set_deterministic();
+        assert!(evaluator
+            .evaluate_expression("-a", &context)
+            .unwrap()
+            .is_sign_positive());
+        assert_eq!(
+            evaluator.evaluate_expression("random() > a", &context),
+            Err(
+                "Function 'random' is random, which is not allowed in deterministic mode"
+                    .to_string()
+            )
+        );
+        assert!(evaluator.validate("now() > a").is_err());
+    }
+
+    #[test]
+    fn test_deterministic_backends() {
+        // A negative NaN with a payload, which `normalize` replaces with `f64::NAN`
+        let odd_nan = f64::from_bits(0xfff8_0000_0000_0001);
+        let mut evaluator = Evaluator::builder()
+            .with_function("odd_nan", move |_| Ok(FunctionResult::UnnamedF64(odd_nan)))
+            .with_deterministic()
+            .build();
+        let context = HashMap::from([("a".to_string(), 0.0)]);
+        let columns = HashMap::from([("a".to_string(), &[0.0][..])]);
+
+        for (expression, expected) in [("-a", 0.0), ("odd_nan() * 2", f64::NAN)] {
+            let ast = evaluator.parse_expression(expression).unwrap();
+            let results = [
+                evaluator.evaluate_ast(&ast, &context),
+                evaluator.compile(&ast).unwrap().evaluate(&context),
+                evaluator.evaluate_expression_with(expression, &context),
+                evaluator
+                    .evaluate_columns(&ast, &columns)
+                    .map(|values| values[0]),
+                evaluator
+                    .evaluate_cross_section(expression, std::slice::from_ref(&context))
+                    .map(|values| values[0]),
+            ];
+            for result in results {
+                assert_eq!(
+                    result.unwrap().to_bits(),
+                    expected.to_bits(),
+                    "{}",
+                    expression
+                );
+            }
+        }
+
+        // Rules only report whether they matched, which normalizing cannot change
+        let rules = ["-a", "odd_nan()"].map(|rule| evaluator.parse_expression(rule).unwrap());
+        let matches = evaluator.compile_rules(&rules).unwrap().evaluate(&context);
+        assert_eq!(matches.unwrap().iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_audit_checksum() {
+        let context = HashMap::from([("a".to_string(), 1.0), ("b".to_string(), 2.0)]);
+        let checksum = audit_checksum("a < b", &context, 1.0);
+
+        // Pinned, since stored checksums must keep verifying
+        assert_eq!(checksum, 7833231968791151398);
+        assert_ne!(checksum, audit_checksum("a < b", &context, 0.0));
+        assert_ne!(checksum, audit_checksum("a <= b", &context, 1.0));
+        let swapped = HashMap::from([("a".to_string(), 2.0), ("b".to_string(), 1.0)]);
+        assert_ne!(checksum, audit_checksum("a < b", &swapped, 1.0));
+        assert_eq!(
+            audit_checksum("x", &HashMap::new(), 0.0),
+            audit_checksum("x", &HashMap::new(), -0.0)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+/// Byte range of a token in the source expression.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What a name in the source refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NameKind {
+    Variable,
+    Function,
+    /// A `$`-prefixed template parameter.
+    Parameter,
+}
+
+/// A variable or function name found in the source, with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRef {
+    pub name: String,
+    pub kind: NameKind,
+    pub span: Span,
+}
+
+/// Renders `message` with the offending line of `source` and a caret under `span`.
+///
+/// ```text
+/// error: Identifier 'volum' not found in context
+///   |
+/// 1 | price > 100 AND volum > 5
+///   |                 ^^^^^
+/// ```
+pub fn render(source: &str, span: Span, message: &str) -> String {
+    let start = span.start.min(source.len());
+    let end = span.end.clamp(start, source.len());
+
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line_number = source[..start].matches('\n').count() + 1;
+
+    let line = &source[line_start..line_end];
+    let column = source[line_start..start].chars().count();
+    let width = source[start..end.min(line_end)].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    format!(
+        "error: {}\n{} |\n{} | {}\n{} | {}{}",
+        message,
+        gutter,
+        line_number,
+        line,
+        gutter,
+        " ".repeat(column),
+        "^".repeat(width)
+    )
+}
+
+/// An evaluation error, with the name it was caused by if that name is unknown, so that
+/// `Evaluator::evaluate_expression` can point at the name in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EvalError {
+    pub(crate) message: String,
+    pub(crate) unknown: Option<(NameKind, String)>,
+}
+
+impl EvalError {
+    /// A variable missing from the context, see `unknown_identifier`.
+    pub(crate) fn unknown_identifier<'a>(
+        name: &str,
+        known: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        Self {
+            message: unknown_identifier(name, known),
+            unknown: Some((NameKind::Variable, name.to_string())),
+        }
+    }
+
+    /// An unregistered function, see `unknown_function`.
+    pub(crate) fn unknown_function<'a>(
+        name: &str,
+        known: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        Self {
+            message: unknown_function(name, known),
+            unknown: Some((NameKind::Function, name.to_string())),
+        }
+    }
+}
+
+impl From<String> for EvalError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            unknown: None,
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Error message for a variable missing from the context, with a suggestion if one is close.
+pub(crate) fn unknown_identifier<'a>(
+    name: &str,
+    known: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let message = format!("Identifier '{}' not found in context", name);
+    match did_you_mean(name, known) {
+        Some(suggestion) => format!("{}. Did you mean '{}'?", message, suggestion),
+        None => message,
+    }
+}
+
+/// Error message for an unregistered function, with a suggestion if one is close.
+pub(crate) fn unknown_function<'a>(
+    name: &str,
+    known: impl IntoIterator<Item = &'a String>,
+) -> String {
+    let message = format!("Function {} not registered", name);
+    match did_you_mean(name, known) {
+        Some(suggestion) => format!("{}. Did you mean '{}'?", message, suggestion),
+        None => message,
+    }
+}
+
+/// Returns the closest candidate to `name` by edit distance, if it is close enough to be a typo.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a String>,
+) -> Option<&'a str> {
+    let max_distance = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by(|(a, a_name), (b, b_name)| a.cmp(b).then_with(|| a_name.cmp(b_name)))
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,353 @@
+//! Differential tests running the same expressions through every evaluation backend: the
+//! AST walker, compiled closures, and columnar evaluation over a single row. Arbitrary
+//! input is also run through every backend and transformation, none of which may panic.
+
+use crate::ast::{
+    to_sql, ASTNode, Evaluator, FunctionArgValue, FunctionArgs, FunctionInfo, FunctionResult,
+    LogicalOperator, Operator, SqlDialect, Symbol, COMPARISON_PRECEDENCE,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+fn setup_evaluator() -> Evaluator {
+    Evaluator::builder()
+        .with_function("add", |args| {
+            Ok(FunctionResult::UnnamedF64(
+                args.get_number("a")? + args.get_number("b")?,
+            ))
+        })
+        .with_function("pair", |args| {
+            let (x, y) = (args.get_number("x")?, args.get_number("y")?);
+            Ok(FunctionResult::NamedF64Map(HashMap::from([
+                ("sum".to_string(), x + y),
+                ("diff".to_string(), x - y),
+            ])))
+        })
+        .with_function_info(FunctionInfo::new("length").symbol("name"), |args| {
+            Ok(FunctionResult::UnnamedF64(
+                args.get_string("name")?.len() as f64
+            ))
+        })
+        .with_operator("<=>", COMPARISON_PRECEDENCE, |a, b| {
+            Ok(a.partial_cmp(&b)
+                .map_or(f64::NAN, |ordering| ordering as i32 as f64))
+        })
+        .build()
+}
+
+/// Evaluates `ast` with every backend and returns the results labelled by backend.
+fn run_backends(
+    evaluator: &mut Evaluator,
+    ast: &ASTNode,
+    context: &HashMap<String, f64>,
+) -> Vec<(&'static str, Result<f64, String>)> {
+    let columns: HashMap<String, &[f64]> = context
+        .iter()
+        .map(|(name, value)| (name.clone(), std::slice::from_ref(value)))
+        .collect();
+
+    vec![
+        ("ast", evaluator.evaluate_ast(ast, context)),
+        (
+            "compiled",
+            evaluator
+                .compile(ast)
+                .and_then(|compiled| compiled.evaluate(context)),
+        ),
+        (
+            "columnar",
+            evaluator
+                .evaluate_columns(ast, &columns)
+                .map(|values| values[0]),
+        ),
+    ]
+}
+
+/// Results agree if all backends fail, or all succeed with the same value (NaN included).
+fn backends_agree(results: &[(&str, Result<f64, String>)]) -> bool {
+    let (_, first) = &results[0];
+    results.iter().all(|(_, result)| match (first, result) {
+        (Ok(a), Ok(b)) => a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+        (Err(_), Err(_)) => true,
+        _ => false,
+    })
+}
+
+/// Panics with every backend's result if they disagree on `expression`.
+pub(crate) fn assert_backends_agree(expression: &str, context: &HashMap<String, f64>) {
+    let mut evaluator = setup_evaluator();
+    let ast = evaluator.parse_expression(expression).unwrap();
+    let results = run_backends(&mut evaluator, &ast, context);
+    assert!(
+        backends_agree(&results),
+        "Backends disagree on '{}': {:?}",
+        expression,
+        results
+    );
+}
+
+#[test]
+fn test_backends_agree_on_corpus() {
+    let context = HashMap::from([
+        ("x".to_string(), 3.0),
+        ("y".to_string(), -2.5),
+        ("zero".to_string(), 0.0),
+        ("bar.close".to_string(), 101.5),
+    ]);
+    let corpus = [
+        "x + y * 2",
+        "x - y - 1",
+        "x % 2 == 1",
+        "-x * -y",
+        "NOT x > y",
+        "x > 1 AND y < 0 OR zero",
+        "(x + y) / zero",
+        "x % zero",
+        "x / (y - y)",
+        "missing + 1",
+        "add(a: x, b: y) * 2",
+        "add(a: x, b: 1) > add(a: y, b: 5)",
+        "add(a: missing, b: 1)",
+        "add(a: x)",
+        "unknown(a: x)",
+        "pair(x: x, y: y).sum",
+        "pair(x: x, y: 1).diff > 0",
+        "pair(x: 1, y: 2).missing",
+        "pair(x: 1, y: 2) > 0",
+        "add(a: 1, b: 2).sum",
+        "x.y",
+        "bar.close * 2 > x",
+        "(pair(x: x, y: y)).diff",
+        "x <=> y * 2",
+        "x / 3 * 3 ~= x",
+        "y + 0.1 ~= -2.4 AND y != -2.5",
+        "(x <=> missing) + 1",
+        "length(name: x) + length(name: missing)",
+        "length(name: 2)",
+    ];
+    for expression in corpus {
+        assert_backends_agree(expression, &context);
+    }
+}
+
+/// Runs untrusted `input` through the parser and, if it parses, every backend and
+/// transformation of the AST. Errors are fine; panics fail the test.
+fn exercise(evaluator: &mut Evaluator, input: &str) {
+    let Ok(ast) = evaluator.parse_expression(input) else {
+        return;
+    };
+    let context = HashMap::from([
+        ("x".to_string(), 3.0),
+        ("y".to_string(), -2.5),
+        ("zero".to_string(), 0.0),
+        ("bar.close".to_string(), 101.5),
+    ]);
+    let _ = run_backends(evaluator, &ast, &context);
+    let (x, y) = ([3.0, f64::NAN, -0.0], [-2.5, 0.0, f64::INFINITY]);
+    let columns = HashMap::from([("x".to_string(), &x[..]), ("y".to_string(), &y[..])]);
+    let _ = evaluator.evaluate_columns(&ast, &columns);
+    let _ = evaluator.evaluate_cross_section(input, &[context.clone(), HashMap::new()]);
+    let _ = evaluator.explain(&ast, &context);
+    let _ = evaluator.generate_rust(&ast, "rule");
+    let _ = evaluator.estimated_cost(&ast);
+    let _ = to_sql(&ast, SqlDialect::Postgres);
+    let _ = to_sql(&ast, SqlDialect::ClickHouse);
+    let _ = ast.canonicalize().fingerprint();
+    let _ = ast.partial_eval(&HashMap::from([("x".to_string(), 3.0)]));
+    let _ = evaluator.parse_expression(&ast.to_string());
+    #[cfg(feature = "async")]
+    {
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+
+        let evaluator = crate::ast::AsyncEvaluator::new(setup_evaluator());
+        let mut future = std::pin::pin!(evaluator.evaluate(&ast, &context));
+        let mut cx = Context::from_waker(Waker::noop());
+        // Registered functions are synchronous, so the future never waits
+        assert!(matches!(future.as_mut().poll(&mut cx), Poll::Ready(_)));
+    }
+}
+
+#[test]
+fn test_untrusted_input_does_not_panic() {
+    let mut evaluator = setup_evaluator();
+    let long_number = "9".repeat(400);
+    let inputs = [
+        "",
+        "(",
+        "-",
+        "f(a: )",
+        "f().x.y",
+        "a > > b",
+        "\0",
+        "é > 1",
+        &long_number,
+        "1 / 0",
+        "0 % 0",
+        "10%-3",
+        "x % -0",
+        "-(-(-x))",
+        "--1",
+        "NOT NOT x",
+        "x.y.z",
+        "(x).y",
+        "(x + 1).y",
+        "bar.close.open",
+        "add(a: x, b: 1).sum",
+        "pair(x: x, y: y).sum.diff",
+        "pair(x: x, y: y)",
+        "length(name: x.y)",
+        "unknown(a: x) > 1",
+        "x <=> y <=> zero",
+        "x ~= y ~= zero",
+        "1e308 * 10",
+        "x * 0 > -0",
+        "$threshold > 1",
+        "rank(value: x) <= 2",
+        "zscore(value: y) > 0",
+        "weight(x > 1, y)",
+        "score(x > 1, 2, y < 0, 3)",
+        "score(x)",
+        "15m - 1h30m",
+        "{a: 1}",
+    ];
+    for input in inputs {
+        exercise(&mut evaluator, input);
+    }
+}
+
+/// Short token soups, mostly close enough to the grammar to parse.
+fn input() -> impl Strategy<Value = String> {
+    let token = prop::sample::select(vec![
+        "x",
+        "y",
+        "zero",
+        "missing",
+        "bar",
+        ".",
+        "close",
+        "0",
+        "1",
+        "-0",
+        "2.5",
+        "3%",
+        "15m",
+        "$p",
+        "(",
+        ")",
+        "+",
+        "-",
+        "*",
+        "/",
+        "%",
+        ">",
+        "<=",
+        "==",
+        "~=",
+        "<=>",
+        " AND ",
+        " OR ",
+        "NOT ",
+        "add(a: x, b: y)",
+        "pair(x: 1, y: x)",
+        "length(name: x)",
+        "rank(value: x)",
+        "sum",
+        ",",
+        ":",
+    ]);
+    prop::collection::vec(token, 0..24).prop_map(|tokens| tokens.concat())
+}
+
+fn ast() -> impl Strategy<Value = ASTNode> {
+    let variable = prop::sample::select(vec!["x", "y", "zero", "missing"]).prop_map(String::from);
+    let arg = prop_oneof![
+        (-10i32..10).prop_map(|v| FunctionArgValue::Number(v.into())),
+        variable.clone().prop_map(FunctionArgValue::Identifier),
+    ];
+    let call = |name: &'static str, params: [&'static str; 2]| {
+        (arg.clone(), arg.clone()).prop_map(move |(first, second)| ASTNode::FunctionCall {
+            name: name.into(),
+            args: FunctionArgs::with_args(HashMap::from([
+                (params[0].to_string(), first),
+                (params[1].to_string(), second),
+            ])),
+        })
+    };
+    let property = prop::sample::select(vec!["sum", "diff"]).prop_map(Symbol::from);
+
+    let leaf = prop_oneof![
+        (-10i32..10).prop_map(|v| ASTNode::Number(v.into())),
+        variable.prop_map(|name| ASTNode::Identifier(name.into())),
+        call("add", ["a", "b"]),
+        (call("pair", ["x", "y"]), property).prop_map(|(base, property)| {
+            ASTNode::PropertyAccess {
+                base: Box::new(base),
+                property,
+            }
+        }),
+    ];
+    let operator = prop::sample::select(vec![
+        Operator::Add,
+        Operator::Subtract,
+        Operator::Multiply,
+        Operator::Divide,
+        Operator::Modulo,
+        Operator::GreaterThan,
+        Operator::LessThanOrEqual,
+        Operator::Equal,
+        Operator::NotEqual,
+        Operator::ApproxEqual,
+    ]);
+    let logical_operator = prop::sample::select(vec![LogicalOperator::And, LogicalOperator::Or]);
+
+    leaf.prop_recursive(5, 48, 2, move |inner| {
+        prop_oneof![
+            (inner.clone(), operator.clone(), inner.clone()).prop_map(|(left, operator, right)| {
+                ASTNode::BinaryOperation {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            }),
+            (inner.clone(), logical_operator.clone(), inner.clone()).prop_map(
+                |(left, operator, right)| ASTNode::LogicalOperation {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            ),
+            inner
+                .clone()
+                .prop_map(|node| ASTNode::NotOperation(Box::new(node))),
+            inner
+                .clone()
+                .prop_map(|node| ASTNode::Negate(Box::new(node))),
+            inner.prop_map(|node| ASTNode::Group(Box::new(node))),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn test_backends_agree_on_generated_expressions(ast in ast()) {
+        let mut evaluator = setup_evaluator();
+        let context = HashMap::from([
+            ("x".to_string(), 3.0),
+            ("y".to_string(), -2.5),
+            ("zero".to_string(), 0.0),
+        ]);
+        let results = run_backends(&mut evaluator, &ast, &context);
+        prop_assert!(backends_agree(&results), "Backends disagree on '{}': {:?}", ast, results);
+    }
+
+    #[test]
+    fn test_generated_input_does_not_panic(input in input()) {
+        exercise(&mut setup_evaluator(), &input);
+    }
+
+    #[test]
+    fn test_generated_expressions_do_not_panic(ast in ast()) {
+        exercise(&mut setup_evaluator(), &ast.to_string());
+    }
+}
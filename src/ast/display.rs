@@ -0,0 +1,364 @@
+use crate::ast::{ASTNode, FunctionArgValue, FunctionArgs, LogicalOperator, Operator};
+use std::fmt;
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::GreaterThan => ">",
+            Operator::LessThan => "<",
+            Operator::GreaterThanOrEqual => ">=",
+            Operator::LessThanOrEqual => "<=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::ApproxEqual => "~=",
+        })
+    }
+}
+
+impl fmt::Display for LogicalOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogicalOperator::And => "AND",
+            LogicalOperator::Or => "OR",
+        })
+    }
+}
+
+impl fmt::Display for FunctionArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionArgValue::Number(value) => write!(f, "{}", value),
+            FunctionArgValue::Identifier(ident) => f.write_str(ident),
+            FunctionArgValue::Boolean(value) => write!(f, "{}", value),
+            FunctionArgValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            FunctionArgValue::KeyValue(map) => {
+                let mut entries: Vec<String> = map
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for FunctionArgs {
+    /// Writes the arguments sorted by name so the output is deterministic.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut args: Vec<_> = self.args.iter().collect();
+        args.sort_by(|a, b| a.0.cmp(b.0));
+        for (i, (name, value)) in args.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Binding strength of each grammar level, loosest first.
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+/// Custom operators have a precedence only the `Evaluator` knows, so they and their
+/// operands are always parenthesized unless they bind as tightly as a unary operator
+const CUSTOM: u8 = 4;
+const COMPARISON: u8 = 5;
+const ADDITIVE: u8 = 6;
+const MULTIPLICATIVE: u8 = 7;
+const UNARY: u8 = 8;
+const PRIMARY: u8 = 9;
+
+fn precedence(node: &ASTNode) -> u8 {
+    match node {
+        ASTNode::LogicalOperation { operator, .. } => match operator {
+            LogicalOperator::Or => OR,
+            LogicalOperator::And => AND,
+        },
+        ASTNode::NotOperation(_) => NOT,
+        ASTNode::CustomOperation { .. } => CUSTOM,
+        ASTNode::BinaryOperation { operator, .. } => match operator {
+            Operator::Add | Operator::Subtract => ADDITIVE,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => MULTIPLICATIVE,
+            _ => COMPARISON,
+        },
+        ASTNode::Negate(_) => UNARY,
+        // Negative literals print with a leading minus, which NaN never does
+        ASTNode::Number(value) if value.is_sign_negative() && !value.is_nan() => UNARY,
+        _ => PRIMARY,
+    }
+}
+
+/// A piece of the output of `ASTNode`'s `Display`, written in order from a stack.
+enum Piece<'a> {
+    Node(&'a ASTNode),
+    /// An operand, wrapped in parentheses if it binds looser than the given level
+    Operand(&'a ASTNode, u8),
+    Text(&'a str),
+    Operator(&'a dyn fmt::Display),
+}
+
+impl fmt::Display for ASTNode {
+    /// Writes the expression in the syntax accepted by the parser, adding parentheses only
+    /// where precedence requires them, so parsing the output yields the same tree.
+    ///
+    /// Only finite numbers round-trip. The grammar has no literals for infinity and NaN,
+    /// which print as `inf` and `NaN` and read back as variables.
+    ///
+    /// Pieces are written from an explicit stack rather than by recursion, so that deep
+    /// trees cannot overflow the stack.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pieces = vec![Piece::Node(self)];
+        while let Some(piece) = pieces.pop() {
+            let node = match piece {
+                Piece::Node(node) => node,
+                Piece::Operand(node, min) if precedence(node) < min => {
+                    pieces.extend([Piece::Text(")"), Piece::Node(node)]);
+                    f.write_str("(")?;
+                    continue;
+                }
+                Piece::Operand(node, _) => node,
+                Piece::Text(text) => {
+                    f.write_str(text)?;
+                    continue;
+                }
+                Piece::Operator(operator) => {
+                    write!(f, " {} ", operator)?;
+                    continue;
+                }
+            };
+
+            // Pieces are pushed in reverse, the first to be written last
+            match node {
+                ASTNode::Number(value) => write!(f, "{}", value)?,
+                ASTNode::Identifier(ident) => f.write_str(ident)?,
+                ASTNode::BinaryOperation {
+                    left,
+                    operator,
+                    right,
+                } => {
+                    // Operators are left-associative, so the right operand must bind tighter
+                    let level = precedence(node);
+                    pieces.extend([
+                        Piece::Operand(right, level + 1),
+                        Piece::Operator(operator),
+                        Piece::Operand(left, level),
+                    ]);
+                }
+                ASTNode::LogicalOperation {
+                    left,
+                    operator,
+                    right,
+                } => {
+                    let level = precedence(node);
+                    pieces.extend([
+                        Piece::Operand(right, level + 1),
+                        Piece::Operator(operator),
+                        Piece::Operand(left, level),
+                    ]);
+                }
+                ASTNode::CustomOperation {
+                    left,
+                    operator,
+                    right,
+                } => pieces.extend([
+                    Piece::Operand(right, UNARY),
+                    Piece::Operator(operator),
+                    Piece::Operand(left, UNARY),
+                ]),
+                ASTNode::NotOperation(inner) => {
+                    f.write_str("NOT ")?;
+                    pieces.push(Piece::Operand(inner, COMPARISON));
+                }
+                ASTNode::Negate(inner) => {
+                    f.write_str("-")?;
+                    pieces.push(Piece::Operand(inner, UNARY));
+                }
+                ASTNode::Group(inner) => {
+                    f.write_str("(")?;
+                    pieces.extend([Piece::Text(")"), Piece::Node(inner)]);
+                }
+                ASTNode::FunctionCall { name, args } => write!(f, "{}({})", name, args)?,
+                ASTNode::PropertyAccess { base, property } => pieces.extend([
+                    Piece::Text(property.as_str()),
+                    Piece::Text("."),
+                    Piece::Operand(base, PRIMARY),
+                ]),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{ASTNode, FunctionArgValue, FunctionArgs, LogicalOperator, Operator, Parser};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_display() {
+        let cases = [
+            (
+                "price > 100 AND volume < 5000",
+                "price > 100 AND volume < 5000",
+            ),
+            ("(a + b) * c", "(a + b) * c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("a - b - c", "a - b - c"),
+            ("NOT (a OR b)", "NOT (a OR b)"),
+            ("(a OR b) AND c", "(a OR b) AND c"),
+            ("-(a + 1) * -2", "-(a + 1) * -2"),
+            (
+                "macd(short_period: 12, long_period: 26).signal > 0",
+                "macd(long_period: 26, short_period: 12).signal > 0",
+            ),
+            ("  ((x))  ", "x"),
+        ];
+        for (input, expected) in cases {
+            let ast = Parser::parse_expression(input).unwrap();
+            assert_eq!(ast.to_string(), expected, "input: {}", input);
+        }
+    }
+
+    fn identifier() -> impl Strategy<Value = String> {
+        "[a-z_][a-z0-9_]{0,5}"
+    }
+
+    fn number() -> impl Strategy<Value = f64> {
+        prop_oneof![
+            (-1000i32..1000).prop_map(f64::from),
+            (-1.0e6..1.0e6f64).prop_map(|v| (v * 100.0).round() / 100.0),
+        ]
+    }
+
+    fn function_call() -> impl Strategy<Value = ASTNode> {
+        let arg = prop_oneof![
+            number().prop_map(FunctionArgValue::Number),
+            identifier().prop_map(FunctionArgValue::Identifier),
+        ];
+        (
+            identifier(),
+            prop::collection::hash_map(identifier(), arg, 0..4),
+        )
+            .prop_map(|(name, args)| ASTNode::FunctionCall {
+                name: name.into(),
+                args: FunctionArgs { args },
+            })
+    }
+
+    /// Generates the trees the parser can produce: no `Group` nodes, and negated literals
+    /// folded into `Number`.
+    fn ast() -> impl Strategy<Value = ASTNode> {
+        let leaf = prop_oneof![
+            number().prop_map(ASTNode::Number),
+            identifier().prop_map(|name| ASTNode::Identifier(name.into())),
+            function_call(),
+            (function_call(), identifier()).prop_map(|(base, property)| {
+                ASTNode::PropertyAccess {
+                    base: Box::new(base),
+                    property: property.into(),
+                }
+            }),
+        ];
+
+        let operator = prop::sample::select(vec![
+            Operator::Add,
+            Operator::Subtract,
+            Operator::Multiply,
+            Operator::Divide,
+            Operator::Modulo,
+            Operator::GreaterThan,
+            Operator::LessThan,
+            Operator::GreaterThanOrEqual,
+            Operator::LessThanOrEqual,
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::ApproxEqual,
+        ]);
+        let logical_operator =
+            prop::sample::select(vec![LogicalOperator::And, LogicalOperator::Or]);
+
+        leaf.prop_recursive(6, 64, 2, move |inner| {
+            prop_oneof![
+                (inner.clone(), operator.clone(), inner.clone()).prop_map(
+                    |(left, operator, right)| ASTNode::BinaryOperation {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    }
+                ),
+                (inner.clone(), logical_operator.clone(), inner.clone()).prop_map(
+                    |(left, operator, right)| ASTNode::LogicalOperation {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    }
+                ),
+                inner
+                    .clone()
+                    .prop_map(|node| ASTNode::NotOperation(Box::new(node))),
+                inner
+                    .prop_filter("negated literals are folded", |node| {
+                        !matches!(node, ASTNode::Number(_))
+                    })
+                    .prop_map(|node| ASTNode::Negate(Box::new(node))),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_display_parse_round_trip(ast in ast()) {
+            let text = ast.to_string();
+            let parsed = Parser::parse_expression(&text);
+            prop_assert_eq!(parsed, Ok(ast), "text: {}", text);
+        }
+
+        #[test]
+        fn test_display_is_stable(ast in ast()) {
+            let text = ast.to_string();
+            let reparsed = Parser::parse_expression(&text).unwrap().to_string();
+            prop_assert_eq!(reparsed, text);
+        }
+    }
+
+    #[test]
+    fn test_non_finite_numbers_do_not_round_trip() {
+        let ast = ASTNode::BinaryOperation {
+            left: Box::new(ASTNode::Number(f64::NEG_INFINITY)),
+            operator: Operator::LessThan,
+            right: Box::new(ASTNode::Number(f64::NAN)),
+        };
+        assert_eq!(ast.to_string(), "-inf < NaN");
+        // The names read back as variables
+        let parsed = Parser::parse_expression(&ast.to_string()).unwrap();
+        assert_eq!(
+            parsed.variables().into_iter().collect::<Vec<_>>(),
+            ["NaN", "inf"]
+        );
+    }
+
+    #[test]
+    fn test_group_is_kept() {
+        let ast = ASTNode::Group(Box::new(ASTNode::Identifier("x".into())));
+        assert_eq!(ast.to_string(), "(x)");
+        assert_eq!(
+            FunctionArgs {
+                args: HashMap::from([("v".to_string(), FunctionArgValue::Array(vec![1.0, 2.5]))])
+            }
+            .to_string(),
+            "v: [1, 2.5]"
+        );
+    }
+}
@@ -1,27 +1,73 @@
-use crate::ast::{ASTNode, FunctionArgValue, FunctionArgs, FunctionResult, Parser};
-use std::collections::HashMap;
+use crate::ast::{
+    custom_operator::CustomOperator, did_you_mean, property_path, render, unknown_function,
+    ASTNode, Capability, EvalError, EvaluatorBuilder, FunctionArgValue, FunctionArgs, FunctionInfo,
+    FunctionResult, Keywords, Metrics, NameKind, Parser, ProgramCache, SessionRecorder, Units,
+};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
+use std::time::Instant;
 
 pub type Function = Arc<dyn Fn(&FunctionArgs) -> Result<FunctionResult, String> + Send + Sync>;
 
 pub struct Evaluator {
     pub(crate) functions: HashMap<String, Function>,
+    pub(crate) function_info: HashMap<String, FunctionInfo>,
+    pub(crate) metrics: Option<Metrics>,
+    pub(crate) operators: HashMap<String, CustomOperator>,
+    pub(crate) epsilon: Option<f64>,
+    pub(crate) recorder: Option<SessionRecorder>,
+    pub(crate) max_capability: Option<Capability>,
+    pub(crate) keywords: Keywords,
+    pub(crate) deterministic: bool,
+    pub(crate) units: Option<Units>,
+    pub(crate) programs: ProgramCache,
 }
 
 impl Evaluator {
-    /// Creates a new `Evaluator` with a given maximum cache size.
-    pub fn new(_max_cache_size: usize) -> Self {
+    /// Creates a new `Evaluator` whose program cache holds up to `max_cache_size` compiled
+    /// expressions, see `Evaluator::compile_cached`.
+    pub fn new(max_cache_size: usize) -> Self {
         Self {
             functions: HashMap::new(),
+            function_info: HashMap::new(),
+            metrics: None,
+            operators: HashMap::new(),
+            epsilon: None,
+            recorder: None,
+            max_capability: None,
+            keywords: Keywords::default(),
+            deterministic: false,
+            units: None,
+            programs: ProgramCache::new(max_cache_size),
         }
     }
 
-    /// Parse an expression string into an AST.
+    /// Starts a fluent `EvaluatorBuilder`.
+    pub fn builder() -> EvaluatorBuilder {
+        EvaluatorBuilder::new()
+    }
+
+    /// Parse an expression string into an AST, accepting the configured keywords and the
+    /// registered custom operators, and checking units if `Evaluator::set_units` was called.
     pub fn parse_expression(&self, expression: &str) -> Result<ASTNode, String> {
-        let ast = Parser::parse_expression(expression)?; // Parse the expression using the grammar.
+        let ast = Parser::parse_with(expression, &self.keywords, &|symbol| {
+            self.operators
+                .get(symbol)
+                .map(|operator| operator.precedence)
+        })?;
+        self.check_units(&ast)?;
         Ok(ast)
     }
 
+    /// Parses a map of named expressions such as `{signal: close > sma, strength: close - sma}`.
+    pub fn parse_outputs(&self, expression: &str) -> Result<Vec<(String, ASTNode)>, String> {
+        Parser::parse_outputs(expression, &self.keywords, &|symbol| {
+            self.operators
+                .get(symbol)
+                .map(|operator| operator.precedence)
+        })
+    }
+
     /// Evaluates a given expression string against a provided context.
     ///
     /// # Arguments
@@ -32,20 +78,74 @@ impl Evaluator {
     /// # Returns
     ///
     /// * `Ok(f64)` if the evaluation succeeds.
-    /// * `Err(String)` if parsing or evaluation fails.
+    /// * `Err(String)` if parsing or evaluation fails. Unknown variables and functions are
+    ///   reported with the offending part of the expression underlined.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "evaluate",
+            level = "debug",
+            skip_all,
+            fields(expression = expression),
+            err
+        )
+    )]
     pub fn evaluate_expression(
         &mut self,
         expression: &str,
         context: &HashMap<String, f64>,
     ) -> Result<f64, String> {
         // Step 1: Parse the expression into an AST
+        let started = Instant::now();
         let ast = self.parse_expression(expression)?;
+        self.evaluate_parsed(expression, &ast, context, started)
+    }
 
-        // Step 2: Resolve identifiers in the AST
-        let resolved_ast = ast.resolve_identifiers(context)?;
+    /// Evaluates `ast`, parsed from `expression` since `started`, with the diagnostics,
+    /// metrics and session recording of `evaluate_expression`.
+    pub(crate) fn evaluate_parsed(
+        &mut self,
+        expression: &str,
+        ast: &ASTNode,
+        context: &HashMap<String, f64>,
+        started: Instant,
+    ) -> Result<f64, String> {
+        let parsed = Instant::now();
 
-        // Step 3: Evaluate the resolved AST
-        self.evaluate_ast(&resolved_ast, context)
+        // Step 2: Resolve identifiers and evaluate the resolved AST
+        let result = self
+            .evaluate_resolved(ast, context)
+            .map_err(|err| self.diagnose(expression, err));
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_parse(expression, parsed - started);
+            metrics.record_execution(expression, ast, parsed.elapsed());
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(expression, context, &result, started.elapsed());
+        }
+        result
+    }
+
+    /// Points `err` at its location in `expression` if it was caused by an unknown name.
+    fn diagnose(&self, expression: &str, err: EvalError) -> String {
+        let Some((kind, name)) = &err.unknown else {
+            return err.message;
+        };
+        let name_refs = Parser::name_refs_with_keywords(expression, &self.keywords);
+        let name_ref = name_refs.ok().and_then(|name_refs| {
+            name_refs.into_iter().find(|name_ref| {
+                let same_kind = match kind {
+                    NameKind::Function => name_ref.kind == NameKind::Function,
+                    _ => name_ref.kind != NameKind::Function,
+                };
+                same_kind && name_ref.name == *name
+            })
+        });
+        match name_ref {
+            Some(name_ref) => render(expression, name_ref.span, &err.message),
+            None => err.message,
+        }
     }
 
     /// Evaluate a single AST node against a single context.
@@ -54,8 +154,33 @@ impl Evaluator {
         ast: &ASTNode,
         context: &HashMap<String, f64>,
     ) -> Result<f64, String> {
-        let resolved_ast = ast.resolve_identifiers(context)?; // Resolve identifiers per context.
-        self.evaluate(&resolved_ast, context) // Evaluate the resolved AST.
+        self.check_units(ast)?;
+        self.evaluate_resolved(ast, context)
+            .map_err(|err| err.message)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "execute", level = "debug", skip_all, err)
+    )]
+    fn evaluate_resolved(
+        &self,
+        ast: &ASTNode,
+        context: &HashMap<String, f64>,
+    ) -> Result<f64, EvalError> {
+        let resolved_ast = ast.resolve(context)?; // Resolve identifiers per context.
+        let value = self.walk(&resolved_ast, context)?; // Evaluate the resolved AST.
+        Ok(self.normalize_result(value))
+    }
+
+    /// Switches to lenient equality: `==` and `!=` compare within `epsilon`, as does `~=`.
+    ///
+    /// Strict equality makes rules like `sma(source: close, period: 3) == 4` fragile, since
+    /// the computed value may be off in the last bits. The tolerance is absolute up to 1 and
+    /// relative to the larger operand beyond that.
+    pub fn set_equality_epsilon(&mut self, epsilon: f64) {
+        self.epsilon = Some(epsilon);
+        self.programs.clear();
     }
 
     /// Registers a function with the evaluator.
@@ -63,116 +188,369 @@ impl Evaluator {
     where
         F: Fn(&FunctionArgs) -> Result<FunctionResult, String> + Send + Sync + 'static,
     {
-        self.functions.insert(name.to_string(), Arc::new(function));
+        self.register_function_with_info(FunctionInfo::new(name), function);
+    }
+
+    /// Registers a function along with its signature metadata.
+    pub fn register_function_with_info<F>(&mut self, info: FunctionInfo, function: F)
+    where
+        F: Fn(&FunctionArgs) -> Result<FunctionResult, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(info.name.clone(), Arc::new(function));
+        self.function_info.insert(info.name.clone(), info);
+        self.programs.clear();
+    }
+
+    /// Sets the spellings accepted for `AND`, `OR` and `NOT`.
+    pub fn set_keywords(&mut self, keywords: Keywords) {
+        self.keywords = keywords;
+        self.programs.clear();
+    }
+
+    /// Only allows calls to functions up to `capability`, e.g. `Capability::Pure` when
+    /// evaluating expressions supplied by tenants of a shared service. Calling any other
+    /// function fails, as does validating an expression that calls one.
+    ///
+    /// Functions registered without `FunctionInfo::capability` count as pure.
+    pub fn set_max_capability(&mut self, capability: Capability) {
+        self.max_capability = Some(capability);
+        self.programs.clear();
+    }
+
+    /// Calls a registered function directly with already-resolved arguments.
+    pub fn call_function(&self, name: &str, args: &FunctionArgs) -> Result<FunctionResult, String> {
+        self.function(name)?(args)
+    }
+
+    /// Looks up a registered function, checking that its capability is allowed.
+    pub(crate) fn function(&self, name: &str) -> Result<&Function, String> {
+        let name = self.resolve_function(name)?;
+        self.check_capability(name)?;
+        Ok(&self.functions[name])
+    }
+
+    /// Returns the registered name a call to `name` refers to: `name` itself if registered,
+    /// otherwise, for a name without a namespace, the only namespaced function with that
+    /// name, so that `ema(...)` still calls `ta.ema` once it moves into a namespace.
+    pub(crate) fn resolve_function<'a>(&'a self, name: &'a str) -> Result<&'a str, String> {
+        let mut candidates = self.function_candidates(name);
+        match candidates.len() {
+            0 => Err(unknown_function(name, self.functions.keys())),
+            1 => Ok(candidates[0]),
+            _ => {
+                candidates.sort_unstable();
+                Err(format!(
+                    "Function {} is ambiguous, call one of {}",
+                    name,
+                    candidates.join(", ")
+                ))
+            }
+        }
+    }
+
+    /// The registered functions a call to `name` may refer to, see `resolve_function`.
+    fn function_candidates<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        if let Some((registered, _)) = self.functions.get_key_value(name) {
+            return vec![registered];
+        }
+        if name.contains('.') {
+            return Vec::new();
+        }
+        self.functions
+            .keys()
+            .filter(|registered| {
+                registered
+                    .rsplit_once('.')
+                    .is_some_and(|(_, short)| short == name)
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn check_capability(&self, name: &str) -> Result<(), String> {
+        let capability = self
+            .function_info
+            .get(name)
+            .map_or(Capability::Pure, |info| info.capability);
+        self.check_allowed(name, capability)
+    }
+
+    /// Checks that a function with `capability` may be called, given the maximum
+    /// capability and deterministic mode.
+    pub(crate) fn check_allowed(&self, name: &str, capability: Capability) -> Result<(), String> {
+        match self.max_capability {
+            _ if self.deterministic && capability != Capability::Pure => Err(format!(
+                "Function '{}' is {}, which is not allowed in deterministic mode",
+                name, capability
+            )),
+            Some(allowed) if capability > allowed => Err(format!(
+                "Function '{}' is {}, which is not allowed (at most {})",
+                name, capability, allowed
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `property` is one of the declared outputs of function `name`. Functions
+    /// that declare no outputs are not checked.
+    pub(crate) fn check_output(&self, name: &str, property: &str) -> Result<(), String> {
+        let outputs = match self.function_info(name) {
+            Some(info) if !info.outputs.is_empty() => &info.outputs,
+            _ => return Ok(()),
+        };
+        if outputs.iter().any(|output| output == property) {
+            return Ok(());
+        }
+        let message = format!("Function '{}' has no output '{}'", name, property);
+        Err(match did_you_mean(property, outputs) {
+            Some(suggestion) => format!("{}. Did you mean '{}'?", message, suggestion),
+            None => format!("{} (outputs: {})", message, outputs.join(", ")),
+        })
+    }
+
+    /// Lists the registered functions and their metadata, sorted by name.
+    pub fn functions(&self) -> Vec<&FunctionInfo> {
+        let mut functions: Vec<&FunctionInfo> = self.function_info.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        functions
+    }
+
+    /// Returns the metadata of a registered function.
+    pub fn function_info(&self, name: &str) -> Option<&FunctionInfo> {
+        self.function_info.get(self.resolve_function(name).ok()?)
+    }
+
+    /// Returns the names of the context variables `ast` reads, like `ASTNode::variables`
+    /// but leaving out identifiers passed to symbolic parameters of registered functions.
+    pub fn variables(&self, ast: &ASTNode) -> BTreeSet<String> {
+        ast.variables_with(|name| self.function_info(name))
+    }
+
+    /// Checks that an expression parses, only calls registered functions and only reads
+    /// declared outputs of their results, without needing a context.
+    pub fn validate(&self, expression: &str) -> Result<(), String> {
+        let ast = self.parse_expression(expression)?;
+        for name_ref in Parser::name_refs_with_keywords(expression, &self.keywords)? {
+            if name_ref.kind == NameKind::Function {
+                if let Err(message) = self.function(&name_ref.name) {
+                    return Err(render(expression, name_ref.span, &message));
+                }
+            }
+        }
+
+        let mut nodes = vec![&ast];
+        while let Some(node) = nodes.pop() {
+            match node {
+                ASTNode::BinaryOperation { left, right, .. }
+                | ASTNode::LogicalOperation { left, right, .. }
+                | ASTNode::CustomOperation { left, right, .. } => nodes.extend([&**left, &**right]),
+                ASTNode::NotOperation(inner) | ASTNode::Negate(inner) | ASTNode::Group(inner) => {
+                    nodes.push(inner)
+                }
+                ASTNode::PropertyAccess { base, property } => {
+                    if let (ASTNode::FunctionCall { name, .. }, path) =
+                        property_path(base, property)
+                    {
+                        self.check_output(name, &path)?;
+                    }
+                }
+                ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {}
+            }
+        }
+        Ok(())
     }
 
     /// Evaluates an `ASTNode` with a given context.
+    ///
+    /// The tree is walked with an explicit stack rather than recursion, so that long
+    /// generated chains such as thousands of `AND` clauses cannot overflow the stack.
     pub fn evaluate(
         &mut self,
         ast: &ASTNode,
         context: &HashMap<String, f64>,
     ) -> Result<f64, String> {
-        // Evaluate the AST node
-        let result = match ast {
-            ASTNode::Number(n) => Ok(*n),
-
-            ASTNode::Identifier(ident) => context
-                .get(ident)
-                .copied()
-                .ok_or_else(|| format!("Identifier '{}' not found in context", ident)),
-
-            ASTNode::BinaryOperation {
-                left,
-                operator,
-                right,
-            } => {
-                let left_value = self.evaluate(left, context)?;
-                // assert_eq!(left_value, 200.0);
-                let right_value = self.evaluate(right, context)?;
-                // assert_eq!(right_value, 200.0);
-                operator.apply(left_value, right_value)
-            }
-
-            ASTNode::LogicalOperation {
-                left,
-                operator,
-                right,
-            } => {
-                let left_value = self.evaluate(left, context)?;
-                let right_value = self.evaluate(right, context)?;
-                operator.apply(left_value, right_value)
-            }
+        self.check_units(ast)?;
+        self.walk(ast, context).map_err(|err| err.message)
+    }
 
-            ASTNode::NotOperation(inner) => {
-                Ok((self.evaluate(inner, context)? == 0.0) as i32 as f64)
-            }
+    fn walk(&self, ast: &ASTNode, context: &HashMap<String, f64>) -> Result<f64, EvalError> {
+        let mut tasks = vec![Task::Visit(ast)];
+        let mut values: Vec<f64> = Vec::new();
 
-            ASTNode::FunctionCall { name, args } => {
-                // Evaluate the function
-                let function = self
-                    .functions
-                    .get(name)
-                    .ok_or_else(|| format!("Function {} not registered", name))?;
-
-                // Evaluate the arguments, resolving identifiers to values from the context
-                let mut new_args = args.clone();
-                for (arg_name, arg_value) in args.args.iter() {
-                    let resolved_value: FunctionArgValue = match arg_value {
-                        FunctionArgValue::Identifier(ident) => {
-                            // Resolve the identifier to a value in the context
-                            context
-                                .get(ident)
-                                .copied()
-                                .ok_or_else(|| {
-                                    format!("Identifier '{}' not found in context", ident)
-                                })?
-                                .try_into()
-                                .unwrap()
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(node) => match node {
+                    ASTNode::BinaryOperation { left, right, .. }
+                    | ASTNode::LogicalOperation { left, right, .. } => {
+                        tasks.extend([Task::Apply(node), Task::Visit(right), Task::Visit(left)]);
+                    }
+                    ASTNode::CustomOperation {
+                        left,
+                        operator,
+                        right,
+                    } => {
+                        // Unknown operators fail before their operands are evaluated
+                        self.custom_operator(operator)?;
+                        tasks.extend([Task::Apply(node), Task::Visit(right), Task::Visit(left)]);
+                    }
+                    ASTNode::NotOperation(inner) | ASTNode::Negate(inner) => {
+                        tasks.extend([Task::Apply(node), Task::Visit(inner)]);
+                    }
+                    ASTNode::Group(inner) => tasks.push(Task::Visit(inner)),
+                    leaf => values.push(self.evaluate_leaf(leaf, context)?),
+                },
+                Task::Apply(node) => {
+                    let value = match node {
+                        ASTNode::NotOperation(_) => (pop(&mut values)? == 0.0) as i32 as f64,
+                        ASTNode::Negate(_) => -pop(&mut values)?,
+                        _ => {
+                            let right_value = pop(&mut values)?;
+                            let left_value = pop(&mut values)?;
+                            match node {
+                                ASTNode::BinaryOperation { operator, .. } => operator
+                                    .apply_with_tolerance(left_value, right_value, self.epsilon)?,
+                                ASTNode::LogicalOperation { operator, .. } => {
+                                    operator.apply(left_value, right_value)?
+                                }
+                                ASTNode::CustomOperation { operator, .. } => {
+                                    self.custom_operator(operator)?(left_value, right_value)?
+                                }
+                                _ => unreachable!("only operations are applied"),
+                            }
                         }
-                        _ => arg_value.clone(),
                     };
-
-                    new_args.insert(&arg_name, resolved_value);
+                    values.push(value);
                 }
+            }
+        }
 
-                // Call the function with the resolved arguments
-                let result = function(&new_args)?;
+        Ok(pop(&mut values)?)
+    }
 
-                match result {
+    /// Evaluates a node without operands.
+    fn evaluate_leaf(
+        &self,
+        ast: &ASTNode,
+        context: &HashMap<String, f64>,
+    ) -> Result<f64, EvalError> {
+        match ast {
+            ASTNode::Number(n) => Ok(*n),
+
+            ASTNode::Identifier(ident) => context
+                .get(ident.as_str())
+                .copied()
+                .ok_or_else(|| EvalError::unknown_identifier(ident, context.keys())),
+
+            ASTNode::FunctionCall { name, args } => {
+                match self.call_with_context(name, args, context)? {
                     FunctionResult::UnnamedF64(value) => Ok(value),
                     FunctionResult::NamedF64Map(_) => {
-                        Err("Expected single value, got multi-value".to_string())
+                        Err("Expected single value, got multi-value".to_string().into())
                     }
                 }
             }
-            ASTNode::PropertyAccess { base, property } => {
-                if let ASTNode::FunctionCall { name, args } = &**base {
-                    let function = self
-                        .functions
-                        .get(name)
-                        .ok_or_else(|| format!("Function {} not registered", name))?;
-                    if let FunctionResult::NamedF64Map(map) = function(args)? {
-                        map.get(property)
+            ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                (ASTNode::FunctionCall { name, args }, path) => {
+                    if let FunctionResult::NamedF64Map(map) =
+                        self.call_with_context(name, args, context)?
+                    {
+                        map.get(&path)
                             .copied()
-                            .ok_or_else(|| format!("Property {} not found in result", property))
+                            .ok_or_else(|| format!("Property {} not found in result", path).into())
                     } else {
-                        Err("Expected multi-value, got single value".to_string())
+                        Err("Expected multi-value, got single value".to_string().into())
                     }
-                } else {
-                    Err("Base must be a function call".to_string())
                 }
+                (ASTNode::Identifier(name), path) => {
+                    let key = format!("{}.{}", name, path);
+                    context
+                        .get(&key)
+                        .copied()
+                        .ok_or_else(|| EvalError::unknown_identifier(&key, context.keys()))
+                }
+                _ => Err("Base must be a function call or identifier"
+                    .to_string()
+                    .into()),
+            },
+            _ => unreachable!("operations have operands"),
+        }
+    }
+
+    /// Calls a function after resolving its identifier arguments from the context.
+    ///
+    /// Identifiers given to parameters declared with `FunctionInfo::symbol` are passed
+    /// through unchanged, so the function can read them with `FunctionArgs::get_string`.
+    fn call_with_context(
+        &self,
+        name: &str,
+        args: &FunctionArgs,
+        context: &HashMap<String, f64>,
+    ) -> Result<FunctionResult, EvalError> {
+        if self.function_candidates(name).is_empty() {
+            return Err(EvalError::unknown_function(name, self.functions.keys()));
+        }
+        let function = self.function(name)?;
+        let identifiers = bound_identifiers(self.function_info(name), args);
+        let args = bind_args(args, &identifiers, |ident| context.get(ident).copied())
+            .map_err(|ident| EvalError::unknown_identifier(ident, context.keys()))?;
+        Ok(function(&args)?)
+    }
+}
+
+/// A step of `Evaluator::evaluate`: evaluate a node's operands, or combine their values.
+enum Task<'a> {
+    Visit(&'a ASTNode),
+    Apply(&'a ASTNode),
+}
+
+/// Pops an operand value, which the traversal order guarantees is there.
+pub(crate) fn pop(values: &mut Vec<f64>) -> Result<f64, String> {
+    values
+        .pop()
+        .ok_or_else(|| "Unexpected end of expression".to_string())
+}
+
+/// The identifier arguments of a call that are read from the context, as (parameter,
+/// identifier) pairs. Identifiers given to parameters `info` declares as symbols are left out.
+pub(crate) fn bound_identifiers(
+    info: Option<&FunctionInfo>,
+    args: &FunctionArgs,
+) -> Vec<(String, String)> {
+    let symbolic = |arg_name: &str| {
+        info.is_some_and(|info| {
+            info.params
+                .iter()
+                .any(|param| param.symbolic && param.name == arg_name)
+        })
+    };
+    args.args
+        .iter()
+        .filter_map(|(arg_name, value)| match value {
+            FunctionArgValue::Identifier(ident) if !symbolic(arg_name) => {
+                Some((arg_name.clone(), ident.clone()))
             }
-            ASTNode::Group(inner) => self.evaluate(inner, context),
-        }?;
+            _ => None,
+        })
+        .collect()
+}
 
-        Ok(result)
+/// Resolves `identifiers` with `lookup`, or returns the first identifier it cannot resolve.
+pub(crate) fn bind_args<'a>(
+    args: &FunctionArgs,
+    identifiers: &'a [(String, String)],
+    lookup: impl Fn(&str) -> Option<f64>,
+) -> Result<FunctionArgs, &'a str> {
+    let mut bound = args.clone();
+    for (arg_name, ident) in identifiers {
+        bound.insert(arg_name, lookup(ident).ok_or(ident.as_str())?);
     }
+    Ok(bound)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{LogicalOperator, Operator};
+    use crate::ast::{to_sql, LogicalOperator, Operator, SqlDialect};
 
     // Helper function to register basic functions for testing
     fn setup_evaluator() -> Evaluator {
@@ -184,7 +562,11 @@ mod tests {
             Ok(FunctionResult::UnnamedF64(a + b))
         });
 
-        evaluator.register_function("map_example", |args| {
+        let info = FunctionInfo::new("map_example")
+            .param("a")
+            .param("b")
+            .symbol("c");
+        evaluator.register_function_with_info(info, |args| {
             let a = args.get_number("a")?;
             let b = args.get_number("b")?;
             let c = args.get_string("c")?;
@@ -270,6 +652,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unary_minus() {
+        let mut evaluator = Evaluator::new(100);
+        let context = HashMap::from([("price".to_string(), 100.0), ("volume".to_string(), 50.0)]);
+
+        assert_eq!(
+            evaluator
+                .evaluate_expression("-price + volume", &context)
+                .unwrap(),
+            -50.0
+        );
+        assert_eq!(
+            evaluator
+                .evaluate_expression("-(price - volume) * 2", &context)
+                .unwrap(),
+            -100.0
+        );
+        assert_eq!(
+            evaluator
+                .evaluate_expression("price - -volume", &context)
+                .unwrap(),
+            150.0
+        );
+    }
+
     #[test]
     fn test_logical_expression() {
         let input = "price > 100 AND volume < 5000";
@@ -306,6 +713,40 @@ mod tests {
         assert_eq!(result, 50.0); // Sum of a and b
     }
 
+    #[test]
+    fn test_unbound_function_argument() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function("offset", |args| {
+            let value = args.get_number("value")?;
+            Ok(FunctionResult::UnnamedF64(
+                value + args.get_number("by").unwrap_or(1.0),
+            ))
+        });
+        let context = HashMap::from([("close".to_string(), 100.0), ("period".to_string(), 3.0)]);
+
+        // A misspelled variable is not silently replaced by the default
+        for input in [
+            "offset(value: close, by: perid)",
+            "map_example(a: close, b: perid, c: label).sum",
+        ] {
+            let ast = evaluator.parse_expression(input).unwrap();
+            let expected =
+                "Identifier 'perid' not found in context. Did you mean 'period'?".to_string();
+            assert_eq!(
+                evaluator.evaluate_ast(&ast, &context),
+                Err(expected.clone())
+            );
+            assert_eq!(
+                evaluator.compile(&ast).unwrap().evaluate(&context),
+                Err(expected)
+            );
+        }
+        assert_eq!(
+            evaluator.evaluate_expression("offset(value: close, by: period)", &context),
+            Ok(103.0)
+        );
+    }
+
     #[test]
     fn test_invalid_function_call() {
         let input = "add(a: 10)"; // Missing required argument "b"
@@ -329,7 +770,7 @@ mod tests {
         let mut evaluator = setup_evaluator();
         let context = HashMap::from([("price".to_string(), 50.0)]);
         let ast = ASTNode::BinaryOperation {
-            left: Box::new(ASTNode::Identifier("price".to_string())),
+            left: Box::new(ASTNode::Identifier("price".into())),
             operator: Operator::Add,
             right: Box::new(ASTNode::Number(20.0)),
         };
@@ -343,13 +784,13 @@ mod tests {
         let context = HashMap::from([("price".to_string(), 120.0), ("volume".to_string(), 3000.0)]);
         let ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("price".to_string())),
+                left: Box::new(ASTNode::Identifier("price".into())),
                 operator: Operator::GreaterThan,
                 right: Box::new(ASTNode::Number(100.0)),
             }),
             operator: LogicalOperator::And,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::LessThan,
                 right: Box::new(ASTNode::Number(5000.0)),
             }),
@@ -518,6 +959,467 @@ mod tests {
         assert_eq!(result, 1350.0); // (20 + 10) * (50 - 5)
     }
 
+    #[test]
+    fn test_unknown_name_diagnostics() {
+        let mut evaluator = setup_evaluator();
+        let context = HashMap::from([("price".to_string(), 120.0)]);
+
+        let err = evaluator
+            .evaluate_expression("price > 100 AND volum > 5", &context)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "error: Identifier 'volum' not found in context\n  |\n1 | price > 100 AND volum > 5\n  |                 ^^^^^"
+        );
+
+        let err = evaluator
+            .evaluate_expression("ad(a: price, b: 1) > 5", &context)
+            .unwrap_err();
+        assert!(err.ends_with("1 | ad(a: price, b: 1) > 5\n  | ^^"));
+
+        let err = evaluator
+            .evaluate_expression("add(a: price, b: volume)", &context)
+            .unwrap_err();
+        assert!(err.ends_with("  |                  ^^^^^^"));
+
+        // Only names the evaluator failed to find are located, not a function's own errors
+        evaluator.register_function_with_info(FunctionInfo::new("stored").symbol("key"), |args| {
+            Err(format!(
+                "Identifier '{}' not found in context",
+                args.get_string("key")?
+            ))
+        });
+        assert_eq!(
+            evaluator.evaluate_expression("stored(key: volume) > 1", &context),
+            Err("Identifier 'volume' not found in context".to_string())
+        );
+    }
+
+    #[test]
+    fn test_did_you_mean_suggestions() {
+        let mut evaluator = setup_evaluator();
+        let context = HashMap::from([("price".to_string(), 120.0), ("volume".to_string(), 10.0)]);
+
+        let err = evaluator
+            .evaluate_expression("volum > 100", &context)
+            .unwrap_err();
+        assert!(err
+            .starts_with("error: Identifier 'volum' not found in context. Did you mean 'volume'?"));
+
+        let err = evaluator
+            .evaluate_expression("multipy(a: price, b: 2)", &context)
+            .unwrap_err();
+        assert!(err.starts_with("error: Function multipy not registered. Did you mean 'multiply'?"));
+
+        let err = evaluator
+            .evaluate_expression("unrelated > 1", &context)
+            .unwrap_err();
+        assert!(!err.contains("Did you mean"));
+    }
+
+    #[test]
+    fn test_list_registered_functions() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("scale")
+                .param("value")
+                .param_with_default("factor", 2.0)
+                .description("Multiplies a value by a factor"),
+            |args| {
+                let factor = args.get_number("factor").unwrap_or(2.0);
+                Ok(FunctionResult::UnnamedF64(
+                    args.get_number("value")? * factor,
+                ))
+            },
+        );
+
+        let names: Vec<&str> = evaluator
+            .functions()
+            .iter()
+            .map(|info| info.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "add",
+                "complex_map",
+                "constant",
+                "map_example",
+                "multiply",
+                "scale"
+            ]
+        );
+
+        let info = evaluator.function_info("scale").unwrap();
+        assert_eq!(info.params.len(), 2);
+        assert_eq!(info.params[1].default, Some(2.0));
+        assert_eq!(
+            info.description.as_deref(),
+            Some("Multiplies a value by a factor")
+        );
+        assert!(evaluator.function_info("add").unwrap().params.is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let evaluator = setup_evaluator();
+        assert!(evaluator
+            .validate("add(a: x, b: 2) > unknown_variable")
+            .is_ok());
+        assert!(evaluator.validate("x >").is_err());
+
+        let err = evaluator.validate("ad(a: x, b: 2) > 1").unwrap_err();
+        assert!(err.contains("Did you mean 'add'?"));
+        assert!(err.contains("^^"));
+    }
+
+    #[test]
+    fn test_very_large_expression() {
+        // Parsed into a left-leaning chain 50,000 levels deep
+        let expression = (0..50_000)
+            .map(|i| format!("(price > {} OR NOT volume)", i % 100))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let mut evaluator = Evaluator::new(100);
+        let mut context =
+            HashMap::from([("price".to_string(), 100.0), ("volume".to_string(), 1.0)]);
+        assert_eq!(
+            evaluator.evaluate_expression(&expression, &context),
+            Ok(1.0)
+        );
+
+        let ast = evaluator.parse_expression(&expression).unwrap();
+        context.insert("price".to_string(), 50.0);
+        assert_eq!(evaluator.evaluate_ast(&ast, &context), Ok(0.0));
+        context.insert("volume".to_string(), 0.0);
+        assert_eq!(evaluator.evaluate_ast(&ast, &context), Ok(1.0));
+        context.remove("volume");
+        assert!(evaluator.evaluate_ast(&ast, &context).is_err());
+
+        // Every other walker over the tree is iterative as well
+        context.insert("volume".to_string(), 1.0);
+        let compiled = evaluator.compile(&ast).unwrap();
+        assert_eq!(compiled.evaluate(&context), Ok(0.0));
+        assert_eq!(
+            evaluator.evaluate_expression_with(&expression, &context),
+            Ok(0.0)
+        );
+        let copy = ast.clone();
+        assert!(copy == ast);
+        let displayed = ast.to_string();
+        assert!(displayed.starts_with("(price > 0 OR NOT volume) AND (price > 1 OR NOT volume)"));
+        assert!(evaluator.parse_expression(&displayed).unwrap() == ast);
+        assert!(evaluator.explain(&ast, &context).is_err());
+        let residual = ast.partial_eval(&HashMap::from([("volume".to_string(), 1.0)]));
+        assert!(residual.to_string().starts_with("(price > 0 OR 0) AND"));
+        let source = evaluator.generate_rust(&ast, "rule").unwrap();
+        // Four statements per clause after the first, which also reads both variables
+        assert!(source.ends_with(
+            "let t200000 = (t199996 != 0.0 && t199999 != 0.0) as i32 as f64;\n    Ok(t200000)\n}\n"
+        ));
+        let sql = to_sql(&ast, SqlDialect::Postgres).unwrap();
+        assert!(sql.starts_with(r#"("price" > 0 OR NOT "volume" <> 0) AND"#));
+        let canonical = ast.canonicalize();
+        assert!(canonical
+            .to_string()
+            .starts_with("(NOT volume OR price > 0) AND"));
+        assert_eq!(canonical.fingerprint(), ast.fingerprint());
+
+        let (price, volume) = ([50.0, 100.0], [1.0, 1.0]);
+        let columns = HashMap::from([
+            ("price".to_string(), &price[..]),
+            ("volume".to_string(), &volume[..]),
+        ]);
+        assert_eq!(
+            evaluator.evaluate_columns(&ast, &columns),
+            Ok(vec![0.0, 1.0])
+        );
+        assert_eq!(
+            evaluator.evaluate_cross_section(&expression, &[context.clone()]),
+            Ok(vec![0.0])
+        );
+
+        let metrics = evaluator.enable_metrics();
+        assert_eq!(
+            evaluator.evaluate_expression(&expression, &context),
+            Ok(0.0)
+        );
+        // Six nodes per clause and the `AND`s between them
+        assert_eq!(
+            metrics.get(&expression).unwrap().nodes_evaluated,
+            50_000 * 6 + 49_999
+        );
+    }
+
+    #[test]
+    fn test_max_capability() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("now").capability(Capability::ReadsClock),
+            |_| Ok(FunctionResult::UnnamedF64(1_700_000_000.0)),
+        );
+        evaluator.register_function_with_info(
+            FunctionInfo::new("fetch_quote").capability(Capability::Network),
+            |_| Ok(FunctionResult::UnnamedF64(101.0)),
+        );
+        let context = HashMap::from([("x".to_string(), 5.0)]);
+        let expression = "now() > 0 AND add(a: x, b: 1) > 5";
+        assert_eq!(evaluator.evaluate_expression(expression, &context), Ok(1.0));
+
+        evaluator.set_max_capability(Capability::ReadsClock);
+        assert_eq!(evaluator.evaluate_expression(expression, &context), Ok(1.0));
+        assert_eq!(
+            evaluator.evaluate_expression("fetch_quote() > x", &context),
+            Err(
+                "Function 'fetch_quote' is network, which is not allowed (at most reads-clock)"
+                    .to_string()
+            )
+        );
+
+        evaluator.set_max_capability(Capability::Pure);
+        let err = evaluator.validate(expression).unwrap_err();
+        assert!(err.contains("Function 'now' is reads-clock"));
+        assert!(err.contains("^^^"));
+        let ast = evaluator.parse_expression(expression).unwrap();
+        assert!(evaluator.compile(&ast).is_err());
+        assert!(evaluator.validate("add(a: x, b: 1) > 5").is_ok());
+    }
+
+    #[test]
+    fn test_namespaced_functions() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function("ta.ema", |_| Ok(FunctionResult::UnnamedF64(1.0)));
+        evaluator.register_function("ema", |_| Ok(FunctionResult::UnnamedF64(2.0)));
+        evaluator.register_function("math.clamp", |_| Ok(FunctionResult::UnnamedF64(3.0)));
+        evaluator.register_function("my.clamp", |_| Ok(FunctionResult::UnnamedF64(4.0)));
+        evaluator.register_function_with_info(
+            FunctionInfo::new("ta.bands").output("upper"),
+            |_| {
+                Ok(FunctionResult::NamedF64Map(HashMap::from([(
+                    "upper".to_string(),
+                    5.0,
+                )])))
+            },
+        );
+        evaluator.register_function("ta.sma", |_| Ok(FunctionResult::UnnamedF64(6.0)));
+        let context = HashMap::from([("bar.close".to_string(), 10.0)]);
+
+        let mut evaluate = |expression| evaluator.evaluate_expression(expression, &context);
+        assert_eq!(evaluate("ta.ema(period: 3)"), Ok(1.0));
+        assert_eq!(evaluate("ema(period: 3)"), Ok(2.0));
+        assert_eq!(evaluate("sma(period: 3) + ta.bands().upper"), Ok(11.0));
+        assert_eq!(evaluate("bar.close - my.clamp()"), Ok(6.0));
+        assert_eq!(
+            evaluate("clamp()"),
+            Err("Function clamp is ambiguous, call one of math.clamp, my.clamp".to_string())
+        );
+        assert!(evaluate("ta.clamp()").is_err());
+        assert!(evaluate("bar.close(x: 1)").is_err());
+
+        assert!(evaluator.validate("ta.bands().upper > sma()").is_ok());
+        assert!(evaluator.validate("bands().lower > 1").is_err());
+        assert!(evaluator.function_info("bands").is_some());
+    }
+
+    #[test]
+    fn test_declared_outputs() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("bands").output("upper").output("lower"),
+            |_| {
+                Ok(FunctionResult::NamedF64Map(HashMap::from([
+                    ("upper".to_string(), 110.0),
+                    ("lower".to_string(), 90.0),
+                ])))
+            },
+        );
+        assert!(evaluator.validate("x > bands().lower").is_ok());
+        assert!(evaluator.validate("map_example().anything > 1").is_ok());
+        assert_eq!(
+            evaluator.validate("x > bands().lowr"),
+            Err("Function 'bands' has no output 'lowr'. Did you mean 'lower'?".to_string())
+        );
+
+        let ast = evaluator.parse_expression("(bands()).middle > 1").unwrap();
+        assert_eq!(
+            evaluator.compile(&ast).err(),
+            Some("Function 'bands' has no output 'middle' (outputs: upper, lower)".to_string())
+        );
+        assert!(evaluator.compile_rules(&[ast]).is_err());
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut evaluator = setup_evaluator();
+        let context = HashMap::from([("x".to_string(), 5.0), ("y".to_string(), 1.0)]);
+        assert!(evaluator.metrics().is_none());
+
+        let metrics = evaluator.enable_metrics();
+        let expression = "add(a: x, b: 2) > y AND NOT x > 100";
+        for _ in 0..3 {
+            evaluator.evaluate_expression(expression, &context).unwrap();
+        }
+
+        let compiled = evaluator.compile_expression("add(a: x, b: 1)").unwrap();
+        compiled.evaluate(&context).unwrap();
+        compiled.evaluate(&context).unwrap();
+
+        let recorded = metrics.get(expression).unwrap();
+        assert_eq!(recorded.evaluations, 3);
+        assert_eq!(recorded.nodes_evaluated, 3 * 8);
+        assert_eq!(recorded.function_calls.get("add"), Some(&3));
+        assert_eq!(recorded.compile_time, std::time::Duration::ZERO);
+
+        let recorded = metrics.get("add(a: x, b: 1)").unwrap();
+        assert_eq!(recorded.evaluations, 2);
+        assert_eq!(recorded.function_calls.get("add"), Some(&2));
+        assert_eq!(metrics.slowest(10).len(), 2);
+
+        metrics.reset();
+        assert!(evaluator.metrics().unwrap().snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_markdown_docs() {
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function_with_info(
+            FunctionInfo::new("scale")
+                .description("Multiplies a value by a factor.")
+                .param("value")
+                .param_description("Input number")
+                .param_with_default("factor", 2.5),
+            |args| Ok(FunctionResult::UnnamedF64(args.get_number("value")?)),
+        );
+        evaluator.register_function("constant", |_args| Ok(FunctionResult::UnnamedF64(42.0)));
+
+        assert_eq!(
+            crate::docs::markdown(&evaluator),
+            "# Function Reference\n\
+             \n## `constant`\n\n```\nconstant()\n```\n\
+             \n## `scale`\n\n```\nscale(value: ..., factor: ...)\n```\n\
+             \nMultiplies a value by a factor.\n\
+             \n| Parameter | Default | Description |\n| --- | --- | --- |\n\
+             | `value` | required | Input number |\n\
+             | `factor` | 2.5 |  |\n"
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut evaluator = Evaluator::builder()
+            .with_cache_size(10)
+            .with_math()
+            .with_function("double", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 2.0))
+            })
+            .with_default_indicators()
+            .build();
+        let context = HashMap::from([("price".to_string(), -21.0)]);
+
+        let result = evaluator
+            .evaluate_expression("double(value: price) + abs(value: price)", &context)
+            .unwrap();
+        assert_eq!(result, -21.0);
+        assert!(evaluator.function_info("simple_moving_average").is_some());
+        assert_eq!(evaluator.program_cache().capacity(), 10);
+
+        // The cache size can be set last without losing earlier settings
+        let evaluator = Evaluator::builder()
+            .with_math()
+            .with_equality_epsilon(0.5)
+            .with_cache_size(3)
+            .build();
+        assert_eq!(evaluator.program_cache().capacity(), 3);
+        assert_eq!(
+            evaluator.evaluate_expression_with("abs(value: -1) == 1.2", &HashMap::new()),
+            Ok(1.0)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_columns() {
+        let evaluator = setup_evaluator();
+        let close = [100.0, 103.0, 98.0, 110.0];
+        let sma = [99.0, 100.0, 100.0, 100.0];
+        let columns: HashMap<String, &[f64]> = HashMap::from([
+            ("close".to_string(), &close[..]),
+            ("sma".to_string(), &sma[..]),
+        ]);
+
+        let ast = evaluator.parse_expression("close > sma * 1.02").unwrap();
+        assert_eq!(
+            evaluator.evaluate_columns(&ast, &columns).unwrap(),
+            vec![0.0, 1.0, 0.0, 1.0]
+        );
+
+        let ast = evaluator
+            .parse_expression("add(a: close, b: 1) - -sma")
+            .unwrap();
+        assert_eq!(
+            evaluator.evaluate_columns(&ast, &columns).unwrap(),
+            vec![200.0, 204.0, 199.0, 211.0]
+        );
+
+        // Columnar results match row-by-row evaluation
+        let mut row_evaluator = setup_evaluator();
+        let ast = evaluator
+            .parse_expression("(close - sma) / sma > 0.02 OR NOT close >= 100")
+            .unwrap();
+        let columnar = evaluator.evaluate_columns(&ast, &columns).unwrap();
+        for row in 0..close.len() {
+            let context = HashMap::from([
+                ("close".to_string(), close[row]),
+                ("sma".to_string(), sma[row]),
+            ]);
+            assert_eq!(
+                row_evaluator.evaluate_ast(&ast, &context).unwrap(),
+                columnar[row]
+            );
+        }
+
+        let ast = evaluator.parse_expression("close / (sma - 100)").unwrap();
+        assert!(evaluator.evaluate_columns(&ast, &columns).is_err());
+
+        let short = [1.0];
+        let mismatched: HashMap<String, &[f64]> = HashMap::from([
+            ("close".to_string(), &close[..]),
+            ("sma".to_string(), &short[..]),
+        ]);
+        assert!(evaluator.evaluate_columns(&ast, &mismatched).is_err());
+    }
+
+    #[test]
+    fn test_compiled_expression() {
+        let mut evaluator = setup_evaluator();
+        let inputs = [
+            "price + 20 * volume",
+            "(price > 100 AND NOT volume < 2000) OR volume >= 3000",
+            "-add(a: price, b: 10) / 2",
+            "complex_map(x: 100, y: 50).sum > 120",
+        ];
+        let context = HashMap::from([("price".to_string(), 120.0), ("volume".to_string(), 3000.0)]);
+
+        for input in inputs {
+            let ast = evaluator.parse_expression(input).unwrap();
+            let compiled = evaluator.compile(&ast).unwrap();
+            assert_eq!(
+                compiled.evaluate(&context).unwrap(),
+                evaluator.evaluate_ast(&ast, &context).unwrap(),
+                "Mismatch for '{}'",
+                input
+            );
+        }
+
+        let ast = evaluator.parse_expression("price / volume").unwrap();
+        let compiled = evaluator.compile(&ast).unwrap();
+        let zero_volume = HashMap::from([("price".to_string(), 1.0), ("volume".to_string(), 0.0)]);
+        assert!(compiled.evaluate(&zero_volume).is_err());
+        assert!(compiled.evaluate(&HashMap::new()).is_err());
+
+        let ast = evaluator.parse_expression("undefined_function()").unwrap();
+        assert!(evaluator.compile(&ast).is_err());
+    }
+
     #[test]
     fn test_error_cases() {
         let mut evaluator = setup_evaluator();
@@ -537,4 +1439,69 @@ mod tests {
             .evaluate_expression("price + ", &HashMap::new())
             .is_err());
     }
+
+    #[test]
+    fn test_approximate_equality() {
+        let mut evaluator = Evaluator::new(100);
+        let context = HashMap::from([("a".to_string(), 0.1), ("b".to_string(), 0.2)]);
+
+        let eval = |evaluator: &mut Evaluator, input: &str| {
+            evaluator.evaluate_expression(input, &context).unwrap()
+        };
+        assert_eq!(eval(&mut evaluator, "a + b == 0.3"), 0.0);
+        assert_eq!(eval(&mut evaluator, "a + b ~= 0.3"), 1.0);
+        assert_eq!(eval(&mut evaluator, "a + b ~= 0.31"), 0.0);
+        assert_eq!(eval(&mut evaluator, "a * 1000000000 ~= 100000000.01"), 1.0);
+
+        evaluator.set_equality_epsilon(0.05);
+        assert_eq!(eval(&mut evaluator, "a + b == 0.3"), 1.0);
+        assert_eq!(eval(&mut evaluator, "a + b != 0.3"), 0.0);
+        assert_eq!(eval(&mut evaluator, "a + b ~= 0.34"), 1.0);
+        assert_eq!(eval(&mut evaluator, "a + b == 0.4"), 0.0);
+
+        let ast = evaluator.parse_expression("a + b == 0.32").unwrap();
+        let compiled = evaluator.compile(&ast).unwrap();
+        assert_eq!(compiled.evaluate(&context).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_nested_property_access() {
+        let mut evaluator = setup_evaluator();
+        evaluator.register_function("nested", |_args| {
+            Ok(FunctionResult::NamedF64Map(HashMap::from([
+                ("data.value".to_string(), 7.0),
+                ("data.count".to_string(), 3.0),
+            ])))
+        });
+        let context = HashMap::from([
+            ("indicator.result.signal".to_string(), 1.5),
+            ("indicator.result.level".to_string(), 1.0),
+        ]);
+
+        for (input, expected) in [
+            ("indicator.result.signal > indicator.result.level", 1.0),
+            ("(indicator.result).signal * 2", 3.0),
+            ("nested().data.value + nested().data.count", 10.0),
+            ("(map_example(a: 1, b: 2, c: label)).sum", 3.0),
+        ] {
+            assert_eq!(
+                evaluator.evaluate_expression(input, &context).unwrap(),
+                expected,
+                "Mismatch for '{}'",
+                input
+            );
+            let compiled = evaluator.compile_expression(input).unwrap();
+            assert_eq!(compiled.evaluate(&context).unwrap(), expected);
+        }
+
+        let err = evaluator
+            .evaluate_expression("indicator.result.signl > 1", &context)
+            .unwrap_err();
+        assert!(err.contains("Identifier 'indicator.result.signl' not found in context"));
+        assert!(err.contains("Did you mean 'indicator.result.signal'?"));
+        assert!(err.contains("^^^^^^^^^^^^^^^^^^^^^^"));
+        assert!(evaluator
+            .evaluate_expression("nested().data.missing", &context)
+            .is_err());
+    }
 }
@@ -0,0 +1,151 @@
+use crate::ast::{
+    Capability, Evaluator, FunctionArgs, FunctionInfo, FunctionResult, Keywords, SessionRecorder,
+    Units,
+};
+use crate::functions;
+
+/// Fluent setup for an `Evaluator`.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, FunctionResult};
+///
+/// let evaluator = Evaluator::builder()
+///     .with_default_indicators()
+///     .with_math()
+///     .with_function("double", |args| {
+///         Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 2.0))
+///     })
+///     .build();
+/// assert!(evaluator.function_info("double").is_some());
+/// ```
+pub struct EvaluatorBuilder {
+    evaluator: Evaluator,
+}
+
+impl EvaluatorBuilder {
+    /// Creates a builder for an evaluator with no functions registered.
+    pub fn new() -> Self {
+        Self {
+            evaluator: Evaluator::new(100),
+        }
+    }
+
+    /// Sets the maximum cache size passed to `Evaluator::new`.
+    pub fn with_cache_size(mut self, max_cache_size: usize) -> Self {
+        self.evaluator.set_cache_size(max_cache_size);
+        self
+    }
+
+    /// Registers the built-in momentum, trend, volatility and volume indicators.
+    pub fn with_default_indicators(mut self) -> Self {
+        functions::register_indicators(&mut self.evaluator);
+        self
+    }
+
+    /// Registers the array statistics such as `mean`, `max` and `last`.
+    pub fn with_aggregations(mut self) -> Self {
+        functions::aggregation::register(&mut self.evaluator);
+        self
+    }
+
+    /// Registers the built-in math helpers such as `abs`, `round` and `round_to_tick`.
+    pub fn with_math(mut self) -> Self {
+        functions::math::register(&mut self.evaluator);
+        self
+    }
+
+    /// Registers the calendar helpers such as `hour` and `is_between_time`.
+    pub fn with_time(mut self) -> Self {
+        functions::time::register(&mut self.evaluator);
+        self
+    }
+
+    /// Enables metrics collection; read them back with `Evaluator::metrics`.
+    pub fn with_metrics(mut self) -> Self {
+        self.evaluator.enable_metrics();
+        self
+    }
+
+    /// Enables lenient equality, see `Evaluator::set_equality_epsilon`.
+    pub fn with_equality_epsilon(mut self, epsilon: f64) -> Self {
+        self.evaluator.set_equality_epsilon(epsilon);
+        self
+    }
+
+    /// Sets the spellings accepted for `AND`, `OR` and `NOT`, see `Keywords`.
+    pub fn with_keywords(mut self, keywords: Keywords) -> Self {
+        self.evaluator.set_keywords(keywords);
+        self
+    }
+
+    /// Rejects functions above `capability`, see `Evaluator::set_max_capability`.
+    pub fn with_max_capability(mut self, capability: Capability) -> Self {
+        self.evaluator.set_max_capability(capability);
+        self
+    }
+
+    /// Evaluates reproducibly, see `Evaluator::set_deterministic`.
+    pub fn with_deterministic(mut self) -> Self {
+        self.evaluator.set_deterministic();
+        self
+    }
+
+    /// Rejects expressions that mix units, see `Evaluator::set_units`.
+    pub fn with_units(mut self, units: Units) -> Self {
+        self.evaluator.set_units(units);
+        self
+    }
+
+    /// Records every evaluation into a session log, see `Evaluator::set_recorder`.
+    pub fn with_recorder(mut self, recorder: SessionRecorder) -> Self {
+        self.evaluator.set_recorder(recorder);
+        self
+    }
+
+    /// Registers a custom function.
+    pub fn with_function<F>(mut self, name: &str, function: F) -> Self
+    where
+        F: Fn(&FunctionArgs) -> Result<FunctionResult, String> + Send + Sync + 'static,
+    {
+        self.evaluator.register_function(name, function);
+        self
+    }
+
+    /// Registers a custom binary operator, see `Evaluator::register_operator`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `symbol` is not a valid operator symbol.
+    pub fn with_operator<F>(mut self, symbol: &str, precedence: u8, function: F) -> Self
+    where
+        F: Fn(f64, f64) -> Result<f64, String> + Send + Sync + 'static,
+    {
+        if let Err(err) = self
+            .evaluator
+            .register_operator(symbol, precedence, function)
+        {
+            panic!("{}", err);
+        }
+        self
+    }
+
+    /// Registers a custom function along with its signature metadata.
+    pub fn with_function_info<F>(mut self, info: FunctionInfo, function: F) -> Self
+    where
+        F: Fn(&FunctionArgs) -> Result<FunctionResult, String> + Send + Sync + 'static,
+    {
+        self.evaluator.register_function_with_info(info, function);
+        self
+    }
+
+    /// Finishes the setup and returns the evaluator.
+    pub fn build(self) -> Evaluator {
+        self.evaluator
+    }
+}
+
+impl Default for EvaluatorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,199 @@
+use crate::ast::{ASTNode, Evaluator};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Deepest tree `Evaluator::explain` accepts. An explanation repeats each subexpression
+/// within those that contain it, so its size grows with the square of the depth.
+const MAX_EXPLAIN_DEPTH: usize = 1_000;
+
+/// The value of a subexpression, with the explanations of its operands, as returned by
+/// `Evaluator::explain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The subexpression, formatted as by `ASTNode`'s `Display`.
+    pub expression: String,
+    /// The value the subexpression evaluated to, or why it could not be evaluated.
+    pub value: Result<f64, String>,
+    /// The operands of the subexpression. Function calls, variables and literals have none.
+    pub children: Vec<Explanation>,
+}
+
+impl Evaluator {
+    /// Evaluates an AST, recording the value of every subexpression so a UI can show which
+    /// clause of a rule made it fail.
+    ///
+    /// Unlike `evaluate`, evaluation continues past errors: every operand is explained, and
+    /// a failing subexpression carries the first error among its operands. Trees deeper
+    /// than 1,000 levels are rejected.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::Evaluator;
+    /// use std::collections::HashMap;
+    ///
+    /// let evaluator = Evaluator::new(100);
+    /// let context = HashMap::from([("price".to_string(), 120.0), ("volume".to_string(), 8000.0)]);
+    /// let ast = evaluator.parse_expression("price > 100 AND volume < 5000").unwrap();
+    ///
+    /// let explanation = evaluator.explain(&ast, &context).unwrap();
+    /// assert_eq!(
+    ///     explanation.to_string(),
+    ///     "price > 100 AND volume < 5000 = 0
+    ///   price > 100 = 1
+    ///     price = 120
+    ///   volume < 5000 = 0
+    ///     volume = 8000
+    /// "
+    /// );
+    /// ```
+    pub fn explain(
+        &self,
+        ast: &ASTNode,
+        context: &HashMap<String, f64>,
+    ) -> Result<Explanation, String> {
+        if ast.depth() > MAX_EXPLAIN_DEPTH {
+            return Err(format!(
+                "Expression is too deeply nested to explain (more than {} levels)",
+                MAX_EXPLAIN_DEPTH
+            ));
+        }
+        Ok(self.explain_node(ast, context))
+    }
+
+    fn explain_node(&self, ast: &ASTNode, context: &HashMap<String, f64>) -> Explanation {
+        let children: Vec<Explanation> = match ast {
+            ASTNode::Group(inner) => return self.explain_node(inner, context),
+            ASTNode::BinaryOperation { left, right, .. }
+            | ASTNode::LogicalOperation { left, right, .. }
+            | ASTNode::CustomOperation { left, right, .. } => {
+                vec![
+                    self.explain_node(left, context),
+                    self.explain_node(right, context),
+                ]
+            }
+            ASTNode::NotOperation(inner) | ASTNode::Negate(inner) => {
+                vec![self.explain_node(inner, context)]
+            }
+            ASTNode::Number(_)
+            | ASTNode::Identifier(_)
+            | ASTNode::FunctionCall { .. }
+            | ASTNode::PropertyAccess { .. } => Vec::new(),
+        };
+
+        let value = match children.iter().find_map(|child| child.value.as_ref().err()) {
+            Some(err) => Err(err.clone()),
+            None => self.apply(ast, &children, context),
+        };
+        Explanation {
+            expression: ast.to_string(),
+            value,
+            children,
+        }
+    }
+
+    /// Parses and explains an expression.
+    pub fn explain_expression(
+        &self,
+        expression: &str,
+        context: &HashMap<String, f64>,
+    ) -> Result<Explanation, String> {
+        self.explain(&self.parse_expression(expression)?, context)
+    }
+
+    /// Computes the value of `ast` from the values of its operands.
+    fn apply(
+        &self,
+        ast: &ASTNode,
+        children: &[Explanation],
+        context: &HashMap<String, f64>,
+    ) -> Result<f64, String> {
+        let operand = |i: usize| children[i].value.clone();
+        match ast {
+            ASTNode::BinaryOperation { operator, .. } => {
+                operator.apply_with_tolerance(operand(0)?, operand(1)?, self.epsilon)
+            }
+            ASTNode::LogicalOperation { operator, .. } => operator.apply(operand(0)?, operand(1)?),
+            ASTNode::CustomOperation { operator, .. } => {
+                self.custom_operator(operator)?(operand(0)?, operand(1)?)
+            }
+            ASTNode::NotOperation(_) => Ok((operand(0)? == 0.0) as i32 as f64),
+            ASTNode::Negate(_) => Ok(-operand(0)?),
+            _ => self.compile(ast)?.evaluate(context),
+        }
+    }
+}
+
+impl Explanation {
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        match &self.value {
+            Ok(value) => writeln!(f, "{}{} = {}", indent, self.expression, value)?,
+            Err(err) => writeln!(f, "{}{}: {}", indent, self.expression, err)?,
+        }
+        // Literals explain nothing
+        for child in &self.children {
+            let literal = child
+                .expression
+                .starts_with(|c: char| c.is_ascii_digit() || c == '-');
+            if child.children.is_empty() && literal {
+                continue;
+            }
+            child.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Explanation {
+    /// Writes one line per subexpression with its value or error, indented by depth.
+    /// Literals are left out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::FunctionResult;
+
+    #[test]
+    fn test_explain() {
+        let evaluator = Evaluator::builder()
+            .with_function("sma", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("data")? - 5.0))
+            })
+            .build();
+        let context = HashMap::from([("close".to_string(), 100.0), ("volume".to_string(), 0.0)]);
+
+        let explanation = evaluator
+            .explain_expression(
+                "(close > sma(data: close) OR NOT volume) AND close / volume > 1",
+                &context,
+            )
+            .unwrap();
+        assert_eq!(explanation.value, Err("Division by zero".to_string()));
+        assert_eq!(explanation.children[0].value, Ok(1.0));
+        assert_eq!(
+            explanation.to_string(),
+            "(close > sma(data: close) OR NOT volume) AND close / volume > 1: Division by zero
+  close > sma(data: close) OR NOT volume = 1
+    close > sma(data: close) = 1
+      close = 100
+      sma(data: close) = 95
+    NOT volume = 1
+      volume = 0
+  close / volume > 1: Division by zero
+    close / volume: Division by zero
+      close = 100
+      volume = 0
+"
+        );
+
+        let explanation = evaluator.explain_expression("-closes", &context).unwrap();
+        assert_eq!(
+            explanation.children[0].value,
+            Err("Identifier 'closes' not found in context. Did you mean 'close'?".to_string())
+        );
+        assert_eq!(explanation.value, explanation.children[0].value);
+    }
+}
@@ -10,7 +10,8 @@ pub enum FunctionArgValue {
     Identifier(String),
     // // An array of numbers
     Array(Vec<f64>),
-    // KeyValue(HashMap<String, f64>), // Key-value pairs for complex functions
+    // Key-value pairs for complex functions, written as `{stop: 0.98, target: 1.05}`
+    KeyValue(HashMap<String, f64>),
     // String(String),                 // A string value
     // A boolean value
     Boolean(bool),
@@ -35,14 +36,14 @@ impl FunctionArgValue {
         }
     }
 
-    // /// Helper to get a key-value map or return an error
-    // pub fn as_key_value(&self) -> Result<&HashMap<String, f64>, String> {
-    //     if let FunctionArgValue::KeyValue(map) = self {
-    //         Ok(map)
-    //     } else {
-    //         Err("Expected a KeyValue type".to_string())
-    //     }
-    // }
+    /// Helper to get a key-value map or return an error
+    pub fn as_key_value(&self) -> Result<&HashMap<String, f64>, String> {
+        if let FunctionArgValue::KeyValue(map) = self {
+            Ok(map)
+        } else {
+            Err("Expected a KeyValue type".to_string())
+        }
+    }
 
     /// Helper to get a string or return an error
     pub fn as_string(&self) -> Result<&str, String> {
@@ -64,7 +65,7 @@ impl FunctionArgValue {
 }
 
 /// Struct to represent arguments passed to functions
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct FunctionArgs {
     pub(crate) args: HashMap<String, FunctionArgValue>,
 }
@@ -102,13 +103,13 @@ impl FunctionArgs {
             .as_array()
     }
 
-    // /// Retrieves an argument by key and expects it to be a key-value map
-    // pub fn get_key_value(&self, key: &str) -> Result<&HashMap<String, f64>, String> {
-    //     self.args
-    //         .get(key)
-    //         .ok_or_else(|| format!("Missing argument: {}", key))?
-    //         .as_key_value()
-    // }
+    /// Retrieves an argument by key and expects it to be a key-value map
+    pub fn get_key_value(&self, key: &str) -> Result<&HashMap<String, f64>, String> {
+        self.args
+            .get(key)
+            .ok_or_else(|| format!("Missing argument: {}", key))?
+            .as_key_value()
+    }
 
     /// Retrieves an argument by key and expects it to be a string
     pub fn get_string(&self, key: &str) -> Result<&str, String> {
@@ -194,12 +195,12 @@ impl From<Vec<f64>> for FunctionArgValue {
     }
 }
 
-// impl From<HashMap<String, f64>> for FunctionArgValue {
-//     fn from(value: HashMap<String, f64>) -> Self {
-//         FunctionArgValue::KeyValue(value)
-//     }
-// }
-//
+impl From<HashMap<String, f64>> for FunctionArgValue {
+    fn from(value: HashMap<String, f64>) -> Self {
+        FunctionArgValue::KeyValue(value)
+    }
+}
+
 // impl From<String> for FunctionArgValue {
 //     fn from(value: String) -> Self {
 //         FunctionArgValue::String(value)
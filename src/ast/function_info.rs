@@ -0,0 +1,136 @@
+use std::fmt;
+
+/// Describes a single named parameter of a registered function
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamInfo {
+    pub name: String,
+    // Value used when the argument is omitted, `None` if there is no numeric default
+    pub default: Option<f64>,
+    pub description: Option<String>,
+    // Whether an identifier argument is passed as written instead of read from the context
+    pub symbolic: bool,
+}
+
+/// What a registered function may do beyond computing on its arguments, from least to most
+/// privileged. Executors can reject functions above a level with
+/// `Evaluator::set_max_capability`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    /// Depends only on its arguments
+    #[default]
+    Pure,
+    /// Returns a different result on every call, such as `random()`
+    Random,
+    /// Reads the current time
+    ReadsClock,
+    /// Performs network requests
+    Network,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Capability::Pure => "pure",
+            Capability::Random => "random",
+            Capability::ReadsClock => "reads-clock",
+            Capability::Network => "network",
+        })
+    }
+}
+
+/// Signature metadata for a registered function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub params: Vec<ParamInfo>,
+    pub description: Option<String>,
+    pub capability: Capability,
+    /// Relative cost of one call, see `CostModel`
+    pub cost: Option<f64>,
+    /// Keys of the `NamedF64Map` result, empty if the function returns a single value or
+    /// does not declare them
+    pub outputs: Vec<String>,
+}
+
+impl FunctionInfo {
+    /// Creates metadata for `name` with no parameters or description
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            params: Vec::new(),
+            description: None,
+            capability: Capability::Pure,
+            cost: None,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Adds a required parameter
+    pub fn param(mut self, name: &str) -> Self {
+        self.params.push(ParamInfo {
+            name: name.to_string(),
+            default: None,
+            description: None,
+            symbolic: false,
+        });
+        self
+    }
+
+    /// Adds an optional parameter with its default value
+    pub fn param_with_default(mut self, name: &str, default: f64) -> Self {
+        self.params.push(ParamInfo {
+            name: name.to_string(),
+            default: Some(default),
+            description: None,
+            symbolic: false,
+        });
+        self
+    }
+
+    /// Adds a required parameter that takes a name as written, such as the ticker in
+    /// `quote(symbol: AAPL)`, read with `FunctionArgs::get_string`. Identifier arguments to
+    /// other parameters must be bound in the context.
+    pub fn symbol(mut self, name: &str) -> Self {
+        self.params.push(ParamInfo {
+            name: name.to_string(),
+            default: None,
+            description: None,
+            symbolic: true,
+        });
+        self
+    }
+
+    /// Sets the description shown to users
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Declares what the function may do, `Capability::Pure` by default
+    pub fn capability(mut self, capability: Capability) -> Self {
+        self.capability = capability;
+        self
+    }
+
+    /// Declares the cost of one call for `Evaluator::estimated_cost`, in units of one
+    /// arithmetic operation
+    pub fn cost(mut self, cost: f64) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// Declares a key of the `NamedF64Map` result, so that property accesses such as
+    /// `pivot_points(values: hlc).support1` are checked when compiling and validating
+    pub fn output(mut self, name: &str) -> Self {
+        self.outputs.push(name.to_string());
+        self
+    }
+
+    /// Documents the most recently added parameter
+    pub fn param_description(mut self, description: &str) -> Self {
+        if let Some(param) = self.params.last_mut() {
+            param.description = Some(description.to_string());
+        }
+        self
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+/// A value that can be flattened into a context, with nested fields under dotted names such
+/// as `quote.bid`.
+///
+/// Derive it for plain structs with `#[derive(IntoContext)]` from the `quantixis-macros`
+/// crate, which also implements `VariableProvider` so the struct can be evaluated against
+/// directly. Numbers, booleans, options and maps are implemented here.
+pub trait IntoContext {
+    /// Writes the value to `context` under `path`, or under `path.<field>` for each of its
+    /// fields.
+    fn write_context(&self, path: &str, context: &mut HashMap<String, f64>);
+
+    /// Returns the number at `path`, relative to this value, the empty path being the value
+    /// itself.
+    fn lookup(&self, path: &str) -> Option<f64>;
+
+    /// Flattens the value into a new context.
+    fn to_context(&self) -> HashMap<String, f64> {
+        let mut context = HashMap::new();
+        self.write_context("", &mut context);
+        context
+    }
+}
+
+/// Joins a path and a field name, e.g. `quote` and `bid` into `quote.bid`.
+pub fn context_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+macro_rules! impl_into_context {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoContext for $ty {
+                fn write_context(&self, path: &str, context: &mut HashMap<String, f64>) {
+                    context.insert(path.to_string(), *self as f64);
+                }
+
+                fn lookup(&self, path: &str) -> Option<f64> {
+                    path.is_empty().then_some(*self as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_into_context!(f64, f32, i8, i16, i32, i64, u8, u16, u32, u64, isize, usize);
+
+impl IntoContext for bool {
+    fn write_context(&self, path: &str, context: &mut HashMap<String, f64>) {
+        context.insert(path.to_string(), *self as i32 as f64);
+    }
+
+    fn lookup(&self, path: &str) -> Option<f64> {
+        path.is_empty().then_some(*self as i32 as f64)
+    }
+}
+
+/// `None` leaves the value out of the context, so reading it reports a missing variable.
+impl<T: IntoContext> IntoContext for Option<T> {
+    fn write_context(&self, path: &str, context: &mut HashMap<String, f64>) {
+        if let Some(value) = self {
+            value.write_context(path, context);
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Option<f64> {
+        self.as_ref()?.lookup(path)
+    }
+}
+
+/// Each entry goes under `path.<key>`, like a struct field.
+impl<T: IntoContext> IntoContext for HashMap<String, T> {
+    fn write_context(&self, path: &str, context: &mut HashMap<String, f64>) {
+        for (key, value) in self {
+            value.write_context(&context_path(path, key), context);
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Option<f64> {
+        let (key, rest) = path.split_once('.').unwrap_or((path, ""));
+        self.get(key)?.lookup(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Quote {
+        bid: f64,
+        size: Option<u32>,
+    }
+
+    impl IntoContext for Quote {
+        fn write_context(&self, path: &str, context: &mut HashMap<String, f64>) {
+            self.bid.write_context(&context_path(path, "bid"), context);
+            self.size
+                .write_context(&context_path(path, "size"), context);
+        }
+
+        fn lookup(&self, path: &str) -> Option<f64> {
+            let (field, rest) = path.split_once('.').unwrap_or((path, ""));
+            match field {
+                "bid" => self.bid.lookup(rest),
+                "size" => self.size.lookup(rest),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_context() {
+        let quote = Quote {
+            bid: 99.5,
+            size: Some(10),
+        };
+        assert_eq!(
+            quote.to_context(),
+            HashMap::from([("bid".to_string(), 99.5), ("size".to_string(), 10.0)])
+        );
+        assert_eq!(quote.lookup("size"), Some(10.0));
+        assert_eq!(quote.lookup("bid.value"), None);
+
+        let mut context = HashMap::new();
+        Quote {
+            bid: 1.0,
+            size: None,
+        }
+        .write_context("quote", &mut context);
+        assert_eq!(context, HashMap::from([("quote.bid".to_string(), 1.0)]));
+        assert_eq!(true.to_context(), HashMap::from([(String::new(), 1.0)]));
+    }
+}
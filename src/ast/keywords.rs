@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+/// A logical keyword of the expression language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyword {
+    And,
+    Or,
+    Not,
+}
+
+impl Keyword {
+    /// The character the grammar reads in place of any spelling of the keyword. Each is a
+    /// single byte, so substituting it for a spelling padded with spaces keeps every other
+    /// token at the same offset.
+    fn marker(self) -> char {
+        match self {
+            Keyword::And => '\u{1}',
+            Keyword::Or => '\u{2}',
+            Keyword::Not => '\u{3}',
+        }
+    }
+}
+
+/// The spellings the parser accepts for `AND`, `OR` and `NOT`.
+///
+/// The default accepts `AND`/`&&`, `OR`/`||` and `NOT`/`!`. Spellings can be added, e.g. to
+/// localize the keywords, or removed for strictness. Expressions are always displayed with
+/// `AND`, `OR` and `NOT`.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, Keyword, Keywords};
+/// use std::collections::HashMap;
+///
+/// let keywords = Keywords::default()
+///     .alias("ET", Keyword::And)
+///     .unwrap()
+///     .alias("OU", Keyword::Or)
+///     .unwrap()
+///     .without("&&")
+///     .without("||");
+/// let mut evaluator = Evaluator::builder().with_keywords(keywords).build();
+///
+/// let context = HashMap::from([("a".to_string(), 1.0), ("b".to_string(), 0.0)]);
+/// assert_eq!(evaluator.evaluate_expression("a ET NOT b", &context), Ok(1.0));
+/// assert!(evaluator.evaluate_expression("a && b", &context).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keywords {
+    aliases: HashMap<String, Keyword>,
+}
+
+impl Default for Keywords {
+    fn default() -> Self {
+        let aliases = [
+            ("AND", Keyword::And),
+            ("&&", Keyword::And),
+            ("OR", Keyword::Or),
+            ("||", Keyword::Or),
+            ("NOT", Keyword::Not),
+            ("!", Keyword::Not),
+        ];
+        Self {
+            aliases: aliases
+                .into_iter()
+                .map(|(alias, keyword)| (alias.to_string(), keyword))
+                .collect(),
+        }
+    }
+}
+
+impl Keywords {
+    /// Accepts no spelling at all, to be followed by `alias` for each accepted one.
+    pub fn empty() -> Self {
+        Self {
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Accepts `alias` as a spelling of `keyword`.
+    ///
+    /// An alias is either a word, such as `ET`, or a run of `&`, of `|` or of operator
+    /// characters, such as `!!`. Words stop being usable as variable names, and
+    /// comparison operators cannot be aliases.
+    pub fn alias(mut self, alias: &str, keyword: Keyword) -> Result<Self, String> {
+        let mut classes = alias.chars().map(CharClass::of);
+        let valid = match classes.next() {
+            Some(Some(CharClass::Word)) => {
+                alias.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && !alias.contains('$')
+                    && classes.all(|class| class == Some(CharClass::Word))
+            }
+            Some(Some(class)) => {
+                !COMPARISON_OPERATORS.contains(&alias) && classes.all(|c| c == Some(class))
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(format!("Invalid keyword alias: '{}'", alias));
+        }
+        self.aliases.insert(alias.to_string(), keyword);
+        Ok(self)
+    }
+
+    /// Stops accepting `alias`.
+    pub fn without(mut self, alias: &str) -> Self {
+        self.aliases.remove(alias);
+        self
+    }
+
+    /// Returns the keyword `alias` spells, if any.
+    pub fn keyword(&self, alias: &str) -> Option<Keyword> {
+        self.aliases.get(alias).copied()
+    }
+
+    /// Replaces every accepted spelling in `input` with the marker the grammar reads,
+    /// padded with spaces to the same length.
+    pub(crate) fn substitute(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(first) = rest.chars().next() {
+            let len = match CharClass::of(first) {
+                Some(class) => rest
+                    .find(|c| CharClass::of(c) != Some(class))
+                    .unwrap_or(rest.len()),
+                None => first.len_utf8(),
+            };
+            let (token, tail) = rest.split_at(len);
+            match self.keyword(token) {
+                Some(keyword) => {
+                    output.push(keyword.marker());
+                    output.push_str(&" ".repeat(len - 1));
+                }
+                // Markers typed in the input are not keywords
+                None if ['\u{1}', '\u{2}', '\u{3}'].contains(&first) => output.push('\0'),
+                None => output.push_str(token),
+            }
+            rest = tail;
+        }
+        output
+    }
+}
+
+const COMPARISON_OPERATORS: [&str; 7] = [">=", ">", "<=", "<", "==", "!=", "~="];
+
+/// Characters that form tokens together, so that an alias only matches a whole token.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Word,
+    Ampersand,
+    Pipe,
+    Operator,
+}
+
+impl CharClass {
+    fn of(c: char) -> Option<Self> {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '$' => Some(CharClass::Word),
+            '&' => Some(CharClass::Ampersand),
+            '|' => Some(CharClass::Pipe),
+            '~' | '<' | '>' | '=' | '!' | '?' | '^' | '@' => Some(CharClass::Operator),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Evaluator, Parser};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_substitute() {
+        let keywords = Keywords::default();
+        assert_eq!(
+            keywords.substitute("a AND !b||c != ANDROID"),
+            "a \u{1}   \u{3}b\u{2} c != ANDROID"
+        );
+        assert_eq!(keywords.substitute("$AND \u{1}"), "$AND \0");
+    }
+
+    #[test]
+    fn test_keyword_aliases() {
+        let keywords = Keywords::empty()
+            .alias("et", Keyword::And)
+            .unwrap()
+            .alias("ou", Keyword::Or)
+            .unwrap()
+            .alias("non", Keyword::Not)
+            .unwrap();
+        let mut evaluator = Evaluator::builder().with_keywords(keywords).build();
+        let context = HashMap::from([("a".to_string(), 1.0), ("b".to_string(), 0.0)]);
+
+        let ast = evaluator.parse_expression("a et non b ou b").unwrap();
+        assert_eq!(ast, Parser::parse_expression("a AND NOT b OR b").unwrap());
+        assert_eq!(
+            evaluator.evaluate_expression("non (a et b)", &context),
+            Ok(1.0)
+        );
+
+        // The default spellings are gone, and errors point at the original text
+        let err = evaluator.parse_expression("a AND b").unwrap_err();
+        assert!(err.contains("a AND b"), "{}", err);
+        assert!(evaluator.validate("a && b").is_err());
+
+        // Unknown names are still located
+        let err = evaluator
+            .evaluate_expression("a et bb", &context)
+            .unwrap_err();
+        assert!(err.contains("Did you mean 'b'?"));
+        assert!(err.contains("     ^^"), "{}", err);
+
+        assert!(Keywords::default().alias("==", Keyword::And).is_err());
+        assert!(Keywords::default().alias("&|", Keyword::And).is_err());
+        assert!(Keywords::default().alias("2x", Keyword::And).is_err());
+        assert!(Keywords::default().alias("", Keyword::And).is_err());
+    }
+}
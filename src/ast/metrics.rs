@@ -0,0 +1,154 @@
+use crate::ast::{ASTNode, Evaluator};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Timings and counters accumulated for a single expression.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExpressionMetrics {
+    /// Number of times the expression was evaluated.
+    pub evaluations: u64,
+    /// Total time spent parsing the expression.
+    pub parse_time: Duration,
+    /// Total time spent compiling the expression into closures.
+    pub compile_time: Duration,
+    /// Total time spent evaluating the expression.
+    pub execution_time: Duration,
+    /// Total number of AST nodes visited across all evaluations.
+    pub nodes_evaluated: u64,
+    /// Number of calls made to each function across all evaluations.
+    pub function_calls: HashMap<String, u64>,
+}
+
+/// Opt-in collector of per-expression metrics, keyed by expression source.
+///
+/// Cloning returns a handle to the same collector, so metrics recorded by an evaluator can
+/// be read from elsewhere (e.g. a metrics exporter) while evaluation continues.
+///
+/// ```
+/// use quantixis_rs::ast::Evaluator;
+/// use std::collections::HashMap;
+///
+/// let mut evaluator = Evaluator::new(100);
+/// let metrics = evaluator.enable_metrics();
+///
+/// let context = HashMap::from([("price".to_string(), 120.0)]);
+/// evaluator.evaluate_expression("price > 100", &context).unwrap();
+///
+/// assert_eq!(metrics.get("price > 100").unwrap().evaluations, 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    expressions: Arc<Mutex<HashMap<String, ExpressionMetrics>>>,
+}
+
+impl Metrics {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the metrics recorded for an expression.
+    pub fn get(&self, expression: &str) -> Option<ExpressionMetrics> {
+        self.lock().get(expression).cloned()
+    }
+
+    /// Returns the metrics of every expression recorded so far.
+    pub fn snapshot(&self) -> HashMap<String, ExpressionMetrics> {
+        self.lock().clone()
+    }
+
+    /// Returns up to `n` expressions with the highest total execution time, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<(String, ExpressionMetrics)> {
+        let mut expressions: Vec<_> = self.snapshot().into_iter().collect();
+        expressions.sort_by_key(|(_, metrics)| std::cmp::Reverse(metrics.execution_time));
+        expressions.truncate(n);
+        expressions
+    }
+
+    /// Discards everything recorded so far.
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, ExpressionMetrics>> {
+        // A panic while holding the lock can only leave counters partially updated
+        self.expressions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn record(&self, expression: &str, update: impl FnOnce(&mut ExpressionMetrics)) {
+        let mut expressions = self.lock();
+        match expressions.get_mut(expression) {
+            Some(metrics) => update(metrics),
+            None => update(expressions.entry(expression.to_string()).or_default()),
+        }
+    }
+
+    pub(crate) fn record_parse(&self, expression: &str, elapsed: Duration) {
+        self.record(expression, |metrics| metrics.parse_time += elapsed);
+    }
+
+    pub(crate) fn record_compile(&self, expression: &str, elapsed: Duration) {
+        self.record(expression, |metrics| metrics.compile_time += elapsed);
+    }
+
+    pub(crate) fn record_execution(&self, expression: &str, ast: &ASTNode, elapsed: Duration) {
+        self.record(expression, |metrics| {
+            metrics.evaluations += 1;
+            metrics.execution_time += elapsed;
+            count_nodes(ast, metrics);
+        });
+    }
+}
+
+/// Adds the nodes and function calls in `ast` to `metrics`. Every operand is evaluated, so
+/// the static shape of the tree matches what runs.
+fn count_nodes(ast: &ASTNode, metrics: &mut ExpressionMetrics) {
+    let mut nodes = vec![ast];
+    while let Some(node) = nodes.pop() {
+        metrics.nodes_evaluated += 1;
+        if let ASTNode::FunctionCall { name, .. } = node {
+            *metrics.function_calls.entry(name.to_string()).or_default() += 1;
+        }
+        nodes.extend(node.operands());
+    }
+}
+
+/// Records execution metrics for a `CompiledExpression` created with metrics enabled.
+pub(crate) struct Recorder {
+    metrics: Metrics,
+    expression: String,
+    ast: ASTNode,
+}
+
+impl Recorder {
+    pub(crate) fn new(metrics: &Metrics, expression: &str, ast: ASTNode) -> Self {
+        Self {
+            metrics: metrics.clone(),
+            expression: expression.to_string(),
+            ast,
+        }
+    }
+
+    pub(crate) fn record(&self, elapsed: Duration) {
+        self.metrics
+            .record_execution(&self.expression, &self.ast, elapsed);
+    }
+}
+
+impl Evaluator {
+    /// Starts collecting metrics for every expression evaluated or compiled from source, and
+    /// returns a handle for reading them.
+    pub fn enable_metrics(&mut self) -> Metrics {
+        // Programs compiled before have no recorder
+        self.programs.clear();
+        self.metrics.get_or_insert_with(Metrics::new).clone()
+    }
+
+    /// Returns the metrics collector, if enabled.
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+}
@@ -1,19 +1,83 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::Infallible;
 
+#[cfg(feature = "async")]
+mod async_evaluator;
+mod canonical;
+mod codegen;
+mod columnar;
+mod compiled_expression;
+mod context_diff;
+mod context_schema;
+mod cost;
+mod cross_section;
+mod custom_operator;
+mod dependencies;
+mod deterministic;
+mod diagnostics;
+#[cfg(test)]
+mod differential;
+mod display;
 mod evaluator;
+mod evaluator_builder;
+mod explain;
 mod function_args;
+mod function_info;
 mod function_result;
+mod into_context;
+mod keywords;
+mod metrics;
 mod parser;
+mod partial_eval;
+mod program_cache;
+mod recording;
+mod rule_program;
+mod rule_set;
+mod sql;
+mod symbol;
+mod template;
+mod units;
+mod value;
+mod variables;
 
+#[cfg(feature = "async")]
+pub use async_evaluator::{AsyncEvaluator, AsyncFunction, BoxFuture};
+pub use canonical::semantically_equal;
+pub use compiled_expression::*;
+pub use context_diff::ContextDiff;
+pub use context_schema::{ContextSchema, FieldType};
+pub use cost::CostModel;
+pub use custom_operator::{
+    OperatorFunction, ADDITIVE_PRECEDENCE, COMPARISON_PRECEDENCE, MULTIPLICATIVE_PRECEDENCE,
+};
+pub use dependencies::{DependencyIndex, ExpressionId};
+pub use deterministic::{audit_checksum, Audited};
+pub use diagnostics::*;
 pub use evaluator::*;
+pub use evaluator_builder::*;
+pub use explain::Explanation;
 pub use function_args::*;
+pub use function_info::*;
 pub use function_result::*;
+pub use into_context::{context_path, IntoContext};
+pub use keywords::{Keyword, Keywords};
+pub use metrics::{ExpressionMetrics, Metrics};
 pub use parser::LogicParser as Parser;
+pub use program_cache::ProgramCache;
+pub use recording::{Mismatch, Record, ReplayReport, SessionLog, SessionRecorder};
+pub use rule_program::{IncrementalState, RuleMatches, RuleProgram};
+pub use rule_set::{RuleError, RuleSet, RuleSetVersion};
+pub use sql::{to_sql, SqlDialect};
+pub use symbol::Symbol;
+pub use template::Template;
+pub use units::Units;
+pub use value::{Value, ValueType};
+pub use variables::VariableProvider;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug)]
 pub enum ASTNode {
     Number(f64),
-    Identifier(String),
+    Identifier(Symbol),
     BinaryOperation {
         left: Box<ASTNode>,
         operator: Operator,
@@ -24,47 +88,155 @@ pub enum ASTNode {
         operator: LogicalOperator,
         right: Box<ASTNode>,
     },
+    /// An operator registered with `Evaluator::register_operator`.
+    CustomOperation {
+        left: Box<ASTNode>,
+        operator: String,
+        right: Box<ASTNode>,
+    },
     NotOperation(Box<ASTNode>),
+    Negate(Box<ASTNode>),
     Group(Box<ASTNode>),
     FunctionCall {
-        name: String,
+        name: Symbol,
         args: FunctionArgs,
     },
     PropertyAccess {
         base: Box<ASTNode>,
-        property: String,
+        property: Symbol,
     },
 }
 
 impl ASTNode {
-    /// Recursively resolves all identifiers in the AST and replaces them with their values from the context.
+    /// Resolves all identifiers in the AST and replaces them with their values from the context.
+    ///
+    /// Walks the tree with an explicit stack, like `Evaluator::evaluate`, so that deep trees
+    /// cannot overflow the stack.
     pub fn resolve_identifiers(&self, context: &HashMap<String, f64>) -> Result<ASTNode, String> {
-        match self {
-            ASTNode::LogicalOperation {
-                left,
-                operator,
-                right,
-            } => Ok(ASTNode::LogicalOperation {
-                left: Box::new(left.resolve_identifiers(context)?),
-                operator: *operator,
-                right: Box::new(right.resolve_identifiers(context)?),
-            }),
-            ASTNode::NotOperation(expression) => Ok(ASTNode::NotOperation(Box::new(
-                expression.resolve_identifiers(context)?,
-            ))),
-            ASTNode::BinaryOperation {
-                left,
-                operator,
-                right,
-            } => Ok(ASTNode::BinaryOperation {
-                left: Box::new(left.resolve_identifiers(context)?),
-                operator: *operator,
-                right: Box::new(right.resolve_identifiers(context)?),
-            }),
-            ASTNode::Group(inner) => {
-                let resolved_inner = inner.resolve_identifiers(context)?;
-                Ok(ASTNode::Group(Box::new(resolved_inner)))
+        self.resolve(context).map_err(|err| err.message)
+    }
+
+    /// Like `resolve_identifiers`, naming the unknown identifier on failure.
+    pub(crate) fn resolve(&self, context: &HashMap<String, f64>) -> Result<ASTNode, EvalError> {
+        self.rebuild(|node| match node {
+            ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                (ASTNode::Identifier(name), path) => {
+                    resolve_variable(&format!("{}.{}", name, path), context).map(Some)
+                }
+                _ => Ok(None),
+            },
+            ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {
+                node.resolve_leaf(context).map(Some)
+            }
+            _ => Ok(None),
+        })
+    }
+
+    /// The operands of an operation from left to right. Literals, names and function calls
+    /// have none.
+    pub(crate) fn operands(&self) -> impl DoubleEndedIterator<Item = &ASTNode> {
+        let (first, second) = match self {
+            ASTNode::BinaryOperation { left, right, .. }
+            | ASTNode::LogicalOperation { left, right, .. }
+            | ASTNode::CustomOperation { left, right, .. } => (Some(&**left), Some(&**right)),
+            ASTNode::NotOperation(inner)
+            | ASTNode::Negate(inner)
+            | ASTNode::Group(inner)
+            | ASTNode::PropertyAccess { base: inner, .. } => (Some(&**inner), None),
+            ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {
+                (None, None)
+            }
+        };
+        first.into_iter().chain(second)
+    }
+
+    /// Returns the number of levels of the tree, 1 for a lone literal or name.
+    pub(crate) fn depth(&self) -> usize {
+        let mut nodes = vec![(self, 1)];
+        let mut depth = 0;
+        while let Some((node, level)) = nodes.pop() {
+            depth = depth.max(level);
+            nodes.extend(node.operands().map(|operand| (operand, level + 1)));
+        }
+        depth
+    }
+
+    /// Rebuilds the tree bottom-up with an explicit stack.
+    ///
+    /// `replace` sees every node before its operands. Returning a node substitutes it for
+    /// the whole subtree, while `None` rebuilds the node from its rebuilt operands, so
+    /// `replace` must return a node for literals, names and function calls.
+    pub(crate) fn rebuild<E>(
+        &self,
+        mut replace: impl FnMut(&ASTNode) -> Result<Option<ASTNode>, E>,
+    ) -> Result<ASTNode, E> {
+        // Each node is visited twice: first to queue its operands, then, with `true`, to
+        // rebuild it from theirs
+        let mut tasks = vec![(self, false)];
+        let mut rebuilt: Vec<ASTNode> = Vec::new();
+        let pop = |rebuilt: &mut Vec<ASTNode>| {
+            Box::new(
+                rebuilt
+                    .pop()
+                    .expect("operands are rebuilt before their operation"),
+            )
+        };
+
+        while let Some((node, operands_rebuilt)) = tasks.pop() {
+            if !operands_rebuilt {
+                match replace(node)? {
+                    Some(replacement) => rebuilt.push(replacement),
+                    None => {
+                        tasks.push((node, true));
+                        tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                    }
+                }
+                continue;
             }
+
+            let node = match node {
+                ASTNode::LogicalOperation { operator, .. } => {
+                    let right = pop(&mut rebuilt);
+                    ASTNode::LogicalOperation {
+                        left: pop(&mut rebuilt),
+                        operator: *operator,
+                        right,
+                    }
+                }
+                ASTNode::BinaryOperation { operator, .. } => {
+                    let right = pop(&mut rebuilt);
+                    ASTNode::BinaryOperation {
+                        left: pop(&mut rebuilt),
+                        operator: *operator,
+                        right,
+                    }
+                }
+                ASTNode::CustomOperation { operator, .. } => {
+                    let right = pop(&mut rebuilt);
+                    ASTNode::CustomOperation {
+                        left: pop(&mut rebuilt),
+                        operator: operator.clone(),
+                        right,
+                    }
+                }
+                ASTNode::NotOperation(_) => ASTNode::NotOperation(pop(&mut rebuilt)),
+                ASTNode::Negate(_) => ASTNode::Negate(pop(&mut rebuilt)),
+                ASTNode::Group(_) => ASTNode::Group(pop(&mut rebuilt)),
+                ASTNode::PropertyAccess { property, .. } => ASTNode::PropertyAccess {
+                    base: pop(&mut rebuilt),
+                    property: property.clone(),
+                },
+                _ => unreachable!("nodes without operands are replaced"),
+            };
+            rebuilt.push(node);
+        }
+
+        Ok(*pop(&mut rebuilt))
+    }
+
+    /// Resolves a node without operands.
+    fn resolve_leaf(&self, context: &HashMap<String, f64>) -> Result<ASTNode, EvalError> {
+        match self {
             ASTNode::FunctionCall { name, args } => {
                 let resolved_args = FunctionArgs {
                     args: args
@@ -76,6 +248,9 @@ impl ASTNode {
                                 FunctionArgValue::Identifier(identifier) => {
                                     Ok(FunctionArgValue::Identifier(identifier.clone()))
                                 }
+                                FunctionArgValue::KeyValue(map) => {
+                                    Ok(FunctionArgValue::KeyValue(map.clone()))
+                                }
                                 _ => Err("Unsupported argument type".to_string()),
                             }?;
                             Ok((key.clone(), resolved_value))
@@ -87,21 +262,186 @@ impl ASTNode {
                     args: resolved_args,
                 })
             }
+            ASTNode::Identifier(ident) => resolve_variable(ident, context),
+            ASTNode::Number(value) => Ok(ASTNode::Number(*value)),
+            _ => unreachable!("operations are rebuilt"),
+        }
+    }
+
+    /// Returns the names of the context variables the expression reads, including
+    /// identifiers passed as function arguments and dotted paths such as `bar.close`.
+    pub fn variables(&self) -> BTreeSet<String> {
+        self.variables_with(|_| None)
+    }
+
+    /// Like `variables`, leaving out identifiers passed to parameters that the
+    /// `FunctionInfo` returned by `function_info` declares as symbols.
+    pub fn variables_with<'a>(
+        &self,
+        function_info: impl Fn(&str) -> Option<&'a FunctionInfo>,
+    ) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        let mut nodes = vec![self];
+        while let Some(node) = nodes.pop() {
+            match node {
+                ASTNode::Identifier(name) => {
+                    names.insert(name.to_string());
+                }
+                ASTNode::FunctionCall { name, args } => {
+                    let identifiers = bound_identifiers(function_info(name), args);
+                    names.extend(identifiers.into_iter().map(|(_, ident)| ident));
+                }
+                ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                    (ASTNode::Identifier(name), path) => {
+                        names.insert(format!("{}.{}", name, path));
+                    }
+                    (base, _) => nodes.push(base),
+                },
+                _ => nodes.extend(node.operands()),
+            }
+        }
+        names
+    }
+}
+
+impl Drop for ASTNode {
+    /// Drops the operands iteratively, since the default recursive drop overflows the
+    /// stack on long chains such as thousands of `AND` clauses.
+    fn drop(&mut self) {
+        let mut operands = Vec::new();
+        self.take_operands(&mut operands);
+        while let Some(mut operand) = operands.pop() {
+            operand.take_operands(&mut operands);
+        }
+    }
+}
+
+impl Clone for ASTNode {
+    /// Clones with an explicit stack, since the derived clone recurses once per level.
+    fn clone(&self) -> Self {
+        let Ok(cloned) = self.rebuild::<Infallible>(|node| {
+            Ok(match node {
+                ASTNode::Number(value) => Some(ASTNode::Number(*value)),
+                ASTNode::Identifier(name) => Some(ASTNode::Identifier(name.clone())),
+                ASTNode::FunctionCall { name, args } => Some(ASTNode::FunctionCall {
+                    name: name.clone(),
+                    args: args.clone(),
+                }),
+                _ => None,
+            })
+        });
+        cloned
+    }
+}
+
+impl PartialEq for ASTNode {
+    /// Compares with an explicit stack, since the derived comparison recurses once per level.
+    fn eq(&self, other: &Self) -> bool {
+        let mut pairs = vec![(self, other)];
+        while let Some(pair) = pairs.pop() {
+            let equal = match pair {
+                (ASTNode::Number(a), ASTNode::Number(b)) => a == b,
+                (ASTNode::Identifier(a), ASTNode::Identifier(b)) => a == b,
+                (
+                    ASTNode::BinaryOperation { operator: a, .. },
+                    ASTNode::BinaryOperation { operator: b, .. },
+                ) => a == b,
+                (
+                    ASTNode::LogicalOperation { operator: a, .. },
+                    ASTNode::LogicalOperation { operator: b, .. },
+                ) => a == b,
+                (
+                    ASTNode::CustomOperation { operator: a, .. },
+                    ASTNode::CustomOperation { operator: b, .. },
+                ) => a == b,
+                (ASTNode::NotOperation(_), ASTNode::NotOperation(_))
+                | (ASTNode::Negate(_), ASTNode::Negate(_))
+                | (ASTNode::Group(_), ASTNode::Group(_)) => true,
+                (
+                    ASTNode::FunctionCall {
+                        name: a,
+                        args: a_args,
+                    },
+                    ASTNode::FunctionCall {
+                        name: b,
+                        args: b_args,
+                    },
+                ) => a == b && a_args == b_args,
+                (
+                    ASTNode::PropertyAccess { property: a, .. },
+                    ASTNode::PropertyAccess { property: b, .. },
+                ) => a == b,
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+            // Operands of equal nodes come in equal numbers
+            pairs.extend(pair.0.operands().zip(pair.1.operands()));
+        }
+        true
+    }
+}
+
+impl ASTNode {
+    /// Moves the operands out of the node, leaving placeholders that drop trivially.
+    fn take_operands(&mut self, operands: &mut Vec<ASTNode>) {
+        let mut push = |operand: &mut Box<ASTNode>| {
+            if !matches!(**operand, ASTNode::Number(_)) {
+                operands.push(take(operand));
+            }
+        };
+        match self {
+            ASTNode::BinaryOperation { left, right, .. }
+            | ASTNode::LogicalOperation { left, right, .. }
+            | ASTNode::CustomOperation { left, right, .. } => {
+                push(left);
+                push(right);
+            }
+            ASTNode::NotOperation(inner)
+            | ASTNode::Negate(inner)
+            | ASTNode::Group(inner)
+            | ASTNode::PropertyAccess { base: inner, .. } => push(inner),
+            ASTNode::Number(_) | ASTNode::Identifier(_) | ASTNode::FunctionCall { .. } => {}
+        }
+    }
+}
+
+/// Moves an operand out of its box, which `ASTNode`'s `Drop` impl does not allow by
+/// destructuring, leaving a placeholder behind.
+pub(crate) fn take(operand: &mut Box<ASTNode>) -> ASTNode {
+    std::mem::replace(&mut **operand, ASTNode::Number(0.0))
+}
+
+/// Splits a chain of property accesses into its base, with groups removed, and the dotted
+/// path of properties, e.g. `(f()).a.b` into `f()` and `"a.b"`.
+///
+/// On an identifier base the path extends the variable name, so `indicator.result.signal`
+/// reads the context entry `"indicator.result.signal"`, as produced by flattening nested
+/// maps. On a function call it names an entry of the call's `NamedF64Map` result.
+/// Looks up a variable, or a dotted path, in the context.
+fn resolve_variable(name: &str, context: &HashMap<String, f64>) -> Result<ASTNode, EvalError> {
+    context.get(name).map_or_else(
+        || Err(EvalError::unknown_identifier(name, context.keys())),
+        |value| Ok(ASTNode::Number(*value)),
+    )
+}
+
+pub(crate) fn property_path<'a>(base: &'a ASTNode, property: &str) -> (&'a ASTNode, String) {
+    let mut path = vec![property];
+    let mut node = base;
+    loop {
+        match node {
             ASTNode::PropertyAccess { base, property } => {
-                let resolved_base = base.resolve_identifiers(context)?;
-                Ok(ASTNode::PropertyAccess {
-                    base: Box::new(resolved_base),
-                    property: property.clone(),
-                })
+                path.push(property);
+                node = base;
             }
-            ASTNode::Identifier(ident) => context.get(ident).map_or_else(
-                || Err(format!("Identifier '{}' not found in context", ident)),
-                // |value| Ok(ASTNode::Number(HashableF64(*value))),
-                |value| Ok(ASTNode::Number(*value)),
-            ),
-            ASTNode::Number(value) => Ok(ASTNode::Number(value.clone())),
+            ASTNode::Group(inner) => node = inner,
+            _ => break,
         }
     }
+    path.reverse();
+    (node, path.join("."))
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -152,6 +492,17 @@ pub enum Operator {
     LessThanOrEqual,
     Equal,
     NotEqual,
+    /// `~=`, equality within a tolerance, see `Operator::apply_with_tolerance`.
+    ApproxEqual,
+}
+
+/// Tolerance of `~=` unless the evaluator is configured with another one.
+pub const DEFAULT_EPSILON: f64 = 1e-9;
+
+/// Returns whether `left` and `right` differ by at most `epsilon`, taken as an absolute
+/// tolerance for values up to 1 in magnitude and relative to the larger one beyond that.
+pub(crate) fn approx_eq(left: f64, right: f64, epsilon: f64) -> bool {
+    left == right || (left - right).abs() <= epsilon * left.abs().max(right.abs()).max(1.0)
 }
 
 impl Operator {
@@ -180,6 +531,27 @@ impl Operator {
             Operator::LessThanOrEqual => Ok(if left <= right { 1.0 } else { 0.0 }),
             Operator::Equal => Ok(if left == right { 1.0 } else { 0.0 }),
             Operator::NotEqual => Ok(if left != right { 1.0 } else { 0.0 }),
+            Operator::ApproxEqual => Ok(approx_eq(left, right, DEFAULT_EPSILON) as i32 as f64),
+        }
+    }
+
+    /// Applies the operator, comparing with tolerance `epsilon` if one is given: `~=` uses
+    /// it in place of `DEFAULT_EPSILON`, and `==` and `!=` become tolerant as well.
+    pub fn apply_with_tolerance(
+        &self,
+        left: f64,
+        right: f64,
+        epsilon: Option<f64>,
+    ) -> Result<f64, String> {
+        let Some(epsilon) = epsilon else {
+            return self.apply(left, right);
+        };
+        match self {
+            Operator::Equal | Operator::ApproxEqual => {
+                Ok(approx_eq(left, right, epsilon) as i32 as f64)
+            }
+            Operator::NotEqual => Ok(!approx_eq(left, right, epsilon) as i32 as f64),
+            _ => self.apply(left, right),
         }
     }
 }
@@ -200,6 +572,7 @@ impl TryFrom<&str> for Operator {
             "<=" => Ok(Operator::LessThanOrEqual),
             "==" => Ok(Operator::Equal),
             "!=" => Ok(Operator::NotEqual),
+            "~=" => Ok(Operator::ApproxEqual),
             _ => Err(format!("Unknown operator: {}", value)),
         }
     }
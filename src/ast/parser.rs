@@ -1,7 +1,11 @@
-use crate::ast::{ASTNode, FunctionArgValue, FunctionArgs, LogicalOperator, Operator};
-use log::debug;
+use crate::ast::{
+    ASTNode, FunctionArgValue, FunctionArgs, Keywords, LogicalOperator, NameKind, NameRef,
+    Operator, Span, ValueType, ADDITIVE_PRECEDENCE, COMPARISON_PRECEDENCE,
+    MULTIPLICATIVE_PRECEDENCE,
+};
+use pest::error::InputLocation;
 use pest::iterators::{Pair, Pairs};
-use pest::Parser;
+use pest::{Parser, Position};
 use pest_derive::Parser;
 use std::collections::HashMap;
 
@@ -9,25 +13,106 @@ use std::collections::HashMap;
 #[grammar = "./expression.pest"] // Link to the grammar file
 pub struct LogicParser;
 
+/// Deepest nesting of parentheses and unary minus accepted, so that hostile input is
+/// rejected before it can overflow the stack of the recursive parser.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
 impl LogicParser {
     pub fn parse_expression(input: &str) -> Result<ASTNode, String> {
-        debug!("Parsing expression: {}", input);
-        let parse_result = LogicParser::parse(Rule::expression, input)
-            .map_err(|e| format!("Parse error: {}", e))?
+        Self::parse_with_operators(input, &|_| None)
+    }
+
+    /// Parses an expression that spells the logical keywords as configured in `keywords`.
+    pub fn parse_with_keywords(input: &str, keywords: &Keywords) -> Result<ASTNode, String> {
+        Self::parse_with(input, keywords, &|_| None)
+    }
+
+    /// Parses an expression that may use custom binary operators. `precedence` returns
+    /// the precedence of each registered operator symbol, and `None` for unknown ones.
+    pub fn parse_with_operators(input: &str, precedence: Precedence) -> Result<ASTNode, String> {
+        Self::parse_with(input, &Keywords::default(), precedence)
+    }
+
+    /// Parses an expression with both configured keywords and custom operators.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "parse",
+            level = "debug",
+            skip_all,
+            fields(expression = input),
+            err
+        )
+    )]
+    pub fn parse_with(
+        input: &str,
+        keywords: &Keywords,
+        precedence: Precedence,
+    ) -> Result<ASTNode, String> {
+        let substituted = keywords.substitute(input);
+        let parse_result = parse_pairs(input, &substituted, Rule::expression)?
+            .next()
+            .ok_or_else(|| "Failed to parse expression".to_string())?;
+        Self::build_logical_expression(parse_result, precedence)
+    }
+
+    /// Parses a map of named expressions, `{name: expression, ...}`, returning the entries
+    /// in order.
+    pub fn parse_outputs(
+        input: &str,
+        keywords: &Keywords,
+        precedence: Precedence,
+    ) -> Result<Vec<(String, ASTNode)>, String> {
+        let substituted = keywords.substitute(input);
+        let map = parse_pairs(input, &substituted, Rule::outputs)?
             .next()
             .ok_or_else(|| "Failed to parse expression".to_string())?;
 
-        debug!("Parse result: {:#?}", parse_result);
-        Self::build_logical_expression(parse_result)
+        let mut outputs: Vec<(String, ASTNode)> = Vec::new();
+        for entry in map.into_inner() {
+            let mut pairs = entry.into_inner();
+            let name = next_pair(&mut pairs)?.as_str().to_string();
+            if outputs.iter().any(|(existing, _)| *existing == name) {
+                return Err(format!("Duplicate output: {}", name));
+            }
+            let ast = Self::build_logical_expression(next_pair(&mut pairs)?, precedence)?;
+            outputs.push((name, ast));
+        }
+        Ok(outputs)
+    }
+
+    /// Lists every variable and function name referenced in the expression, with its span.
+    ///
+    /// Named-argument keys and property names are not included.
+    pub fn name_refs(input: &str) -> Result<Vec<NameRef>, String> {
+        Self::name_refs_with_keywords(input, &Keywords::default())
     }
 
-    fn build_logical_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
-        Self::build_or_expression(pairs.next().unwrap())
+    /// Like `name_refs`, for an expression spelling the keywords as configured in `keywords`.
+    pub fn name_refs_with_keywords(
+        input: &str,
+        keywords: &Keywords,
+    ) -> Result<Vec<NameRef>, String> {
+        let substituted = keywords.substitute(input);
+        let pairs = parse_pairs(input, &substituted, Rule::expression)?;
+
+        let mut refs = Vec::new();
+        for pair in pairs {
+            collect_name_refs(pair, &mut refs);
+        }
+        Ok(refs)
     }
 
-    fn build_or_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    fn build_logical_expression(
+        pair: Pair<Rule>,
+        precedence: Precedence,
+    ) -> Result<ASTNode, String> {
+        Self::build_or_expression(next_pair(&mut pair.into_inner())?, precedence)
+    }
+
+    fn build_or_expression(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
-        let mut node = Self::build_and_expression(pairs.next().unwrap())?;
+        let mut node = Self::build_and_expression(next_pair(&mut pairs)?, precedence)?;
 
         while let Some(operator_pair) = pairs.next() {
             let operator = match operator_pair.as_rule() {
@@ -35,7 +120,7 @@ impl LogicParser {
                 _ => return Err(format!("Unexpected logical operator: {:?}", operator_pair)),
             };
 
-            let right = Self::build_and_expression(pairs.next().unwrap())?;
+            let right = Self::build_and_expression(next_pair(&mut pairs)?, precedence)?;
             node = ASTNode::LogicalOperation {
                 left: Box::new(node),
                 operator,
@@ -46,10 +131,9 @@ impl LogicParser {
         Ok(node)
     }
 
-    fn build_and_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    fn build_and_expression(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
-        let p = pairs.next().unwrap();
-        let mut node = Self::build_not_expression(p)?;
+        let mut node = Self::build_not_expression(next_pair(&mut pairs)?, precedence)?;
 
         while let Some(operator_pair) = pairs.next() {
             let operator = match operator_pair.as_rule() {
@@ -57,7 +141,7 @@ impl LogicParser {
                 _ => return Err(format!("Unexpected logical operator: {:?}", operator_pair)),
             };
 
-            let right = Self::build_not_expression(pairs.next().unwrap())?;
+            let right = Self::build_not_expression(next_pair(&mut pairs)?, precedence)?;
             node = ASTNode::LogicalOperation {
                 left: Box::new(node),
                 operator,
@@ -68,125 +152,85 @@ impl LogicParser {
         Ok(node)
     }
 
-    fn build_not_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    fn build_not_expression(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
-        let operator_pair = pairs.next().unwrap();
+        let operator_pair = next_pair(&mut pairs)?;
         if operator_pair.as_rule() == Rule::NOT {
-            let inner_node = Self::build_comparison_expression(pairs.next().unwrap())?;
-            return Ok(ASTNode::NotOperation(Box::new(inner_node)));
+            let inner_node = Self::build_binary_expression(next_pair(&mut pairs)?, precedence)?;
+            Ok(ASTNode::NotOperation(Box::new(inner_node)))
         } else {
-            Self::build_comparison_expression(operator_pair)
+            Self::build_binary_expression(operator_pair, precedence)
         }
     }
 
-    fn build_comparison_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    /// Builds a flat chain of binary operations into a tree, binding higher-precedence
+    /// operators first and equal ones left to right.
+    fn build_binary_expression(
+        pair: Pair<Rule>,
+        precedence: Precedence,
+    ) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
-        let mut node = Self::build_arithmetic_expression(pairs.next().unwrap())?;
+        let mut operands = vec![Self::build_factor(next_pair(&mut pairs)?, precedence)?];
+        let mut pending: Vec<BinaryOperator> = Vec::new();
 
         while let Some(operator_pair) = pairs.next() {
-            let operator = match operator_pair.as_str() {
-                ">" => Operator::GreaterThan,
-                ">=" => Operator::GreaterThanOrEqual,
-                "<" => Operator::LessThan,
-                "<=" => Operator::LessThanOrEqual,
-                "==" => Operator::Equal,
-                "!=" => Operator::NotEqual,
-                _ => {
-                    return Err(format!(
-                        "Unexpected comparison operator: {:?}",
-                        operator_pair
-                    ))
-                }
-            };
-
-            let right = Self::build_arithmetic_expression(pairs.next().unwrap())?;
-            node = ASTNode::BinaryOperation {
-                left: Box::new(node),
-                operator,
-                right: Box::new(right),
-            };
+            let operator = BinaryOperator::from_pair(&operator_pair, precedence)?;
+            while pending
+                .last()
+                .is_some_and(|top| top.precedence() >= operator.precedence())
+            {
+                reduce(&mut operands, pending.pop())?;
+            }
+            pending.push(operator);
+            operands.push(Self::build_factor(next_pair(&mut pairs)?, precedence)?);
         }
-
-        Ok(node)
-    }
-
-    fn build_arithmetic_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
-        let mut pairs = pair.into_inner();
-        let mut node = Self::build_term(pairs.next().unwrap())?;
-        while let Some(operator_pair) = pairs.next() {
-            let operator = match operator_pair.as_rule() {
-                Rule::PLUS => Operator::Add,
-                Rule::MINUS => Operator::Subtract,
-                Rule::comparison_operator => operator_pair.as_str().try_into()?,
-                _ => {
-                    return Err(format!(
-                        "Unexpected arithmetic operator: {:?}",
-                        operator_pair
-                    ))
-                }
-            };
-
-            let right = Self::build_term(pairs.next().unwrap())?;
-            node = ASTNode::BinaryOperation {
-                left: Box::new(node),
-                operator,
-                right: Box::new(right),
-            };
+        while !pending.is_empty() {
+            reduce(&mut operands, pending.pop())?;
         }
 
-        Ok(node)
+        operands
+            .pop()
+            .ok_or_else(|| "Unexpected end of expression".to_string())
     }
 
-    fn build_term(pair: Pair<Rule>) -> Result<ASTNode, String> {
-        let mut pairs = pair.into_inner();
-        let mut node = Self::build_factor(pairs.next().unwrap())?;
-
-        while let Some(operator_pair) = pairs.next() {
-            let operator = match operator_pair.as_rule() {
-                Rule::STAR => Operator::Multiply,
-                Rule::SLASH => Operator::Divide,
-                Rule::MOD => Operator::Modulo,
-                _ => return Err(format!("Unexpected term operator: {:?}", operator_pair)),
-            };
-
-            let right = Self::build_factor(pairs.next().unwrap())?;
-            node = ASTNode::BinaryOperation {
-                left: Box::new(node),
-                operator,
-                right: Box::new(right),
-            };
-        }
-
-        Ok(node)
-    }
-
-    fn build_factor(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    fn build_factor(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
         if let Some(operator_pair) = pairs.peek() {
             if operator_pair.as_rule() == Rule::NOT {
                 pairs.next(); // Consume the NOT operator
-                let inner_node = Self::build_factor(pairs.next().unwrap())?;
+                let inner_node = Self::build_factor(next_pair(&mut pairs)?, precedence)?;
                 return Ok(ASTNode::NotOperation(Box::new(inner_node)));
             }
+            if operator_pair.as_rule() == Rule::MINUS {
+                pairs.next(); // Consume the unary minus
+                let inner_node = Self::build_factor(next_pair(&mut pairs)?, precedence)?;
+                // Fold negative literals so `-5` stays a plain number
+                return Ok(match inner_node {
+                    ASTNode::Number(value) => ASTNode::Number(-value),
+                    node => ASTNode::Negate(Box::new(node)),
+                });
+            }
         }
 
         let primary = pairs.next().ok_or("Expected a primary expression")?;
-        Self::build_primary_expression(primary)
+        Self::build_primary_expression(primary, precedence)
     }
 
-    fn build_primary_expression(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    fn build_primary_expression(
+        pair: Pair<Rule>,
+        precedence: Precedence,
+    ) -> Result<ASTNode, String> {
         match pair.as_rule() {
-            Rule::number => {
-                let value = pair.as_str().parse::<f64>().unwrap();
-                Ok(ASTNode::Number(value))
-            }
-            Rule::identifier => Ok(ASTNode::Identifier(pair.as_str().to_string())),
+            Rule::number | Rule::percent => Ok(ASTNode::Number(parse_number(&pair)?)),
+            Rule::duration => Ok(ASTNode::Number(parse_duration(pair.as_str())?)),
+            Rule::identifier | Rule::parameter => Ok(ASTNode::Identifier(pair.as_str().into())),
             Rule::group => {
-                let inner = pair.into_inner().next().unwrap();
-                Self::build_logical_expression(inner)
+                let inner = next_pair(&mut pair.into_inner())?;
+                Self::build_logical_expression(inner, precedence)
             }
             Rule::function_call => Self::build_function_call(pair),
-            Rule::property_access => Self::build_property_access(pair),
+            Rule::scoring_call => Self::build_scoring_call(pair, precedence),
+            Rule::property_access => Self::build_property_access(pair, precedence),
             _ => Err(format!(
                 "Unexpected rule in primary expression: {:?}",
                 pair.as_rule()
@@ -196,16 +240,49 @@ impl LogicParser {
 
     fn build_function_call(pair: Pair<Rule>) -> Result<ASTNode, String> {
         let mut inner = pair.into_inner();
-        let name = inner.next().unwrap().as_str().to_string();
-        let args = parse_function_args(inner.next());
+        let name = next_pair(&mut inner)?.as_str().into();
+        let args = parse_function_args(inner.next())?;
         Ok(ASTNode::FunctionCall { name, args })
     }
 
-    fn build_property_access(pair: Pair<Rule>) -> Result<ASTNode, String> {
+    /// Expands `weight(condition, w)` into `condition * w` and `score(a, b, ...)` into the
+    /// sum of its terms, where a condition counts as `condition * 1`. Conditions that are
+    /// not comparisons or logical operations are compared with 0 first.
+    fn build_scoring_call(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
         let mut pairs = pair.into_inner();
-        let mut base = Self::build_primary_expression(pairs.next().unwrap())?;
-        while let Some(property) = pairs.next() {
-            let property = property.as_str().to_string();
+        let function = next_pair(&mut pairs)?.as_str();
+        let args = pairs
+            .map(|arg| Self::build_logical_expression(arg, precedence))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let weight = |condition: ASTNode, weight: ASTNode| {
+            let condition = match condition.value_type() {
+                Ok(ValueType::Boolean) => condition,
+                _ => binary(condition, Operator::NotEqual, ASTNode::Number(0.0)),
+            };
+            binary(condition, Operator::Multiply, weight)
+        };
+        match function {
+            "weight" => match <[ASTNode; 2]>::try_from(args) {
+                Ok([condition, w]) => Ok(weight(condition, w)),
+                Err(_) => Err("weight expects a condition and a weight".to_string()),
+            },
+            _ => args
+                .into_iter()
+                .map(|term| match term.value_type() {
+                    Ok(ValueType::Boolean) => weight(term, ASTNode::Number(1.0)),
+                    _ => term,
+                })
+                .reduce(|sum, term| binary(sum, Operator::Add, term))
+                .ok_or_else(|| "score expects at least one term".to_string()),
+        }
+    }
+
+    fn build_property_access(pair: Pair<Rule>, precedence: Precedence) -> Result<ASTNode, String> {
+        let mut pairs = pair.into_inner();
+        let mut base = Self::build_primary_expression(next_pair(&mut pairs)?, precedence)?;
+        for property in pairs {
+            let property = property.as_str().into();
             base = ASTNode::PropertyAccess {
                 base: Box::new(base),
                 property,
@@ -215,25 +292,272 @@ impl LogicParser {
     }
 }
 
-fn parse_function_args(pair: Option<pest::iterators::Pair<Rule>>) -> FunctionArgs {
+/// Looks up the precedence of a custom operator symbol.
+type Precedence<'a> = &'a dyn Fn(&str) -> Option<u8>;
+
+/// A binary operator awaiting its operands while a chain is grouped by precedence.
+enum BinaryOperator {
+    Builtin(Operator),
+    Custom(String, u8),
+}
+
+impl BinaryOperator {
+    fn from_pair(pair: &Pair<Rule>, precedence: Precedence) -> Result<Self, String> {
+        let symbol = pair.as_str();
+        match pair.as_rule() {
+            Rule::custom_operator => precedence(symbol)
+                .map(|level| BinaryOperator::Custom(symbol.to_string(), level))
+                .ok_or_else(|| format!("Unknown operator: {}", symbol)),
+            _ => Ok(BinaryOperator::Builtin(symbol.try_into()?)),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Builtin(operator) => match operator {
+                Operator::Add | Operator::Subtract => ADDITIVE_PRECEDENCE,
+                Operator::Multiply | Operator::Divide | Operator::Modulo => {
+                    MULTIPLICATIVE_PRECEDENCE
+                }
+                _ => COMPARISON_PRECEDENCE,
+            },
+            BinaryOperator::Custom(_, level) => *level,
+        }
+    }
+}
+
+fn binary(left: ASTNode, operator: Operator, right: ASTNode) -> ASTNode {
+    ASTNode::BinaryOperation {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+    }
+}
+
+/// Replaces the last two operands with `operator` applied to them.
+fn reduce(operands: &mut Vec<ASTNode>, operator: Option<BinaryOperator>) -> Result<(), String> {
+    let (Some(operator), Some(right), Some(left)) = (operator, operands.pop(), operands.pop())
+    else {
+        return Err("Unexpected end of expression".to_string());
+    };
+    let (left, right) = (Box::new(left), Box::new(right));
+    operands.push(match operator {
+        BinaryOperator::Builtin(operator) => ASTNode::BinaryOperation {
+            left,
+            operator,
+            right,
+        },
+        BinaryOperator::Custom(operator, _) => ASTNode::CustomOperation {
+            left,
+            operator,
+            right,
+        },
+    });
+    Ok(())
+}
+
+fn collect_name_refs(pair: Pair<Rule>, refs: &mut Vec<NameRef>) {
+    let name_ref = |pair: &Pair<Rule>, kind| NameRef {
+        name: pair.as_str().to_string(),
+        kind,
+        span: Span {
+            start: pair.as_span().start(),
+            end: pair.as_span().end(),
+        },
+    };
+
+    match pair.as_rule() {
+        Rule::identifier => refs.push(name_ref(&pair, NameKind::Variable)),
+        Rule::parameter => refs.push(name_ref(&pair, NameKind::Parameter)),
+        Rule::function_call => {
+            let mut inner = pair.into_inner();
+            if let Some(name) = inner.next() {
+                refs.push(name_ref(&name, NameKind::Function));
+            }
+            for rest in inner {
+                collect_name_refs(rest, refs);
+            }
+        }
+        Rule::named_arg => {
+            // Skip the argument key, only the value refers to the context
+            for value in pair.into_inner().skip(1) {
+                collect_name_refs(value, refs);
+            }
+        }
+        Rule::property_access => {
+            let span = pair.as_span();
+            let mut inner = pair.into_inner();
+            match inner.next() {
+                // A path on a variable names a flattened context entry
+                Some(base) if base.as_rule() == Rule::identifier => {
+                    let path: Vec<&str> = std::iter::once(base)
+                        .chain(inner)
+                        .map(|p| p.as_str())
+                        .collect();
+                    refs.push(NameRef {
+                        name: path.join("."),
+                        kind: NameKind::Variable,
+                        span: Span {
+                            start: span.start(),
+                            end: span.end(),
+                        },
+                    });
+                }
+                // Otherwise only the base can contain names, the rest are property names
+                Some(base) => collect_name_refs(base, refs),
+                None => {}
+            }
+        }
+        _ => {
+            for inner in pair.into_inner() {
+                collect_name_refs(inner, refs);
+            }
+        }
+    }
+}
+
+fn parse_function_args(pair: Option<Pair<Rule>>) -> Result<FunctionArgs, String> {
     let mut args = HashMap::new();
     if let Some(inner) = pair {
         for named_arg in inner.into_inner() {
             let mut inner = named_arg.into_inner();
-            let key = inner.next().unwrap().as_str().to_string();
-            let value = parse_value(inner.next().unwrap());
+            let key = next_pair(&mut inner)?.as_str().to_string();
+            let value = parse_value(next_pair(&mut inner)?)?;
             args.insert(key, value);
         }
     }
-    FunctionArgs { args }
+    Ok(FunctionArgs { args })
 }
 
-fn parse_value(pair: pest::iterators::Pair<Rule>) -> FunctionArgValue {
+fn parse_value(pair: Pair<Rule>) -> Result<FunctionArgValue, String> {
     match pair.as_rule() {
-        Rule::number => FunctionArgValue::Number(pair.as_str().parse().unwrap()),
-        Rule::identifier => FunctionArgValue::Identifier(pair.as_str().to_string()),
-        _ => panic!("Unexpected value type: {:?}", pair),
+        Rule::number | Rule::percent => Ok(FunctionArgValue::Number(parse_number(&pair)?)),
+        Rule::duration => Ok(FunctionArgValue::Number(parse_duration(pair.as_str())?)),
+        Rule::map_literal => {
+            let mut map = HashMap::new();
+            for entry in pair.into_inner() {
+                let mut inner = entry.into_inner();
+                let key = next_pair(&mut inner)?.as_str().to_string();
+                let value = parse_value(next_pair(&mut inner)?)?.as_number()?;
+                if map.insert(key.clone(), value).is_some() {
+                    return Err(format!("Duplicate key in map literal: {}", key));
+                }
+            }
+            Ok(FunctionArgValue::KeyValue(map))
+        }
+        Rule::identifier | Rule::parameter => {
+            Ok(FunctionArgValue::Identifier(pair.as_str().to_string()))
+        }
+        rule => Err(format!("Unexpected argument value: {:?}", rule)),
+    }
+}
+
+fn parse_number(pair: &Pair<Rule>) -> Result<f64, String> {
+    let parsed = match pair.as_str().strip_suffix('%') {
+        // Shift the decimal point rather than dividing, so `0.1%` is exactly `0.001`
+        Some(percent) => format!("{}e-2", percent).parse(),
+        None => pair.as_str().parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number: {}", pair.as_str()))
+}
+
+/// Converts a duration literal such as `1h30m` to seconds.
+fn parse_duration(input: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid duration: {}", input);
+
+    let mut seconds = 0.0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let unit_start = rest
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let (amount, tail) = rest.split_at(unit_start);
+        let unit_end = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+
+        let scale = match unit {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3_600.0,
+            "d" => 86_400.0,
+            "w" => 604_800.0,
+            _ => return Err(invalid()),
+        };
+        seconds += amount.parse::<f64>().map_err(|_| invalid())? * scale;
+        rest = tail;
+    }
+    Ok(seconds)
+}
+
+/// Parses `substituted`, the input with its keywords replaced by markers, reporting errors
+/// against the original `input`.
+fn parse_pairs<'i>(
+    input: &str,
+    substituted: &'i str,
+    rule: Rule,
+) -> Result<Pairs<'i, Rule>, String> {
+    check_nesting_depth(input)?;
+    LogicParser::parse(rule, substituted).map_err(|err| {
+        // Offsets are the same in both, since markers are padded to the keyword's length
+        let located = match err.location {
+            InputLocation::Pos(pos) => Position::new(input, pos)
+                .map(|pos| pest::error::Error::new_from_pos(err.variant.clone(), pos)),
+            InputLocation::Span((start, end)) => pest::Span::new(input, start, end)
+                .map(|span| pest::error::Error::new_from_span(err.variant.clone(), span)),
+        };
+        format!("Parse error: {}", located.unwrap_or(err))
+    })
+}
+
+/// Takes the next child pair, which the grammar guarantees in well-formed trees.
+fn next_pair<'i>(pairs: &mut Pairs<'i, Rule>) -> Result<Pair<'i, Rule>, String> {
+    pairs
+        .next()
+        .ok_or_else(|| "Unexpected end of expression".to_string())
+}
+
+/// Rejects input nesting parentheses or unary minus deeper than `MAX_NESTING_DEPTH`.
+///
+/// A unary minus stays open until its operand ends, so `-(-(x))` counts as depth 4.
+fn check_nesting_depth(input: &str) -> Result<(), String> {
+    // Depth at the start of each open parenthesis
+    let mut bases = vec![0];
+    let mut depth = 0;
+    let mut expect_operand = true;
+
+    for c in input.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '(' => {
+                depth += 1;
+                bases.push(depth);
+                expect_operand = true;
+            }
+            ')' => {
+                if bases.len() > 1 {
+                    bases.pop();
+                }
+                depth = bases[bases.len() - 1];
+                expect_operand = false;
+            }
+            '-' if expect_operand => depth += 1,
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                depth = bases[bases.len() - 1];
+                expect_operand = false;
+            }
+            _ => expect_operand = true,
+        }
+
+        if depth > MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Expression is nested too deeply (maximum depth is {})",
+                MAX_NESTING_DEPTH
+            ));
+        }
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -247,7 +571,7 @@ mod tests {
         let input = "price > 100";
         let ast = LogicParser::parse_expression(input).unwrap();
         let expected_ast = ASTNode::BinaryOperation {
-            left: Box::new(ASTNode::Identifier("price".to_string())),
+            left: Box::new(ASTNode::Identifier("price".into())),
             operator: Operator::GreaterThan,
             right: Box::new(ASTNode::Number(100.0)),
         };
@@ -260,13 +584,13 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("price".to_string())),
+                left: Box::new(ASTNode::Identifier("price".into())),
                 operator: Operator::GreaterThan,
                 right: Box::new(ASTNode::Number(100.0)),
             }),
             operator: LogicalOperator::And,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::LessThan,
                 right: Box::new(ASTNode::Number(5000.0)),
             }),
@@ -280,13 +604,13 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("price".to_string())),
+                left: Box::new(ASTNode::Identifier("price".into())),
                 operator: Operator::GreaterThan,
                 right: Box::new(ASTNode::Number(100.0)),
             }),
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::LessThan,
                 right: Box::new(ASTNode::Number(5000.0)),
             }),
@@ -301,20 +625,20 @@ mod tests {
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("volume".to_string())),
+                    left: Box::new(ASTNode::Identifier("volume".into())),
                     operator: Operator::LessThan,
                     right: Box::new(ASTNode::Number(5000.0)),
                 }),
             }),
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::GreaterThanOrEqual,
                 right: Box::new(ASTNode::Number(3000.0)),
             }),
@@ -334,19 +658,146 @@ mod tests {
         args.insert("period".to_string(), FunctionArgValue::Number(10.0));
 
         let expected_ast = ASTNode::FunctionCall {
-            name: "ema".to_string(),
+            name: "ema".into(),
             args: FunctionArgs { args },
         };
         assert_eq!(ast, expected_ast);
     }
 
+    #[test]
+    fn test_unary_minus() {
+        let input = "-price * 2 > -5";
+        let ast = LogicParser::parse_expression(input).unwrap();
+        let expected_ast = ASTNode::BinaryOperation {
+            left: Box::new(ASTNode::BinaryOperation {
+                left: Box::new(ASTNode::Negate(Box::new(ASTNode::Identifier(
+                    "price".into(),
+                )))),
+                operator: Operator::Multiply,
+                right: Box::new(ASTNode::Number(2.0)),
+            }),
+            operator: Operator::GreaterThan,
+            right: Box::new(ASTNode::Number(-5.0)),
+        };
+        assert_eq!(ast, expected_ast);
+    }
+
+    #[test]
+    fn test_decimal_literal() {
+        let input = "sma * 1.02";
+        let ast = LogicParser::parse_expression(input).unwrap();
+        let expected_ast = ASTNode::BinaryOperation {
+            left: Box::new(ASTNode::Identifier("sma".into())),
+            operator: Operator::Multiply,
+            right: Box::new(ASTNode::Number(1.02)),
+        };
+        assert_eq!(ast, expected_ast);
+    }
+
+    #[test]
+    fn test_percent_literal() {
+        let parse = |input: &str| LogicParser::parse_expression(input).unwrap().to_string();
+
+        assert_eq!(parse("change > 2%"), "change > 0.02");
+        assert_eq!(
+            parse("price > prev_close * (1 + 2.5%)"),
+            "price > prev_close * (1 + 0.025)"
+        );
+        assert_eq!(parse("x < -0.1% AND y"), "x < -0.001 AND y");
+        assert_eq!(parse("1.% == 0.01"), "0.01 == 0.01");
+        assert_eq!(parse("f(pct: 5%)"), "f(pct: 0.05)");
+
+        // A `%` followed by an operand is still the modulo operator
+        assert_eq!(parse("10%3"), "10 % 3");
+        assert_eq!(parse("10 % x"), "10 % x");
+        assert_eq!(parse("10% (x)"), "10 % x");
+        assert_eq!(parse("10%-3"), "10 % -3");
+        assert_eq!(parse("10% -x"), "10 % -x");
+        // There is no unary plus, so a `+` is always the addition
+        assert_eq!(parse("10%+3"), "0.1 + 3");
+
+        let mut evaluator = crate::ast::Evaluator::new(10);
+        let context = HashMap::new();
+        assert_eq!(evaluator.evaluate_expression("10%-3", &context), Ok(1.0));
+        assert_eq!(evaluator.evaluate_expression("10%+3", &context), Ok(3.1));
+    }
+
+    #[test]
+    fn test_duration_literal() {
+        let parse = |input: &str| LogicParser::parse_expression(input).unwrap().to_string();
+
+        assert_eq!(parse("now - bar_time < 15m"), "now - bar_time < 900");
+        assert_eq!(parse("1h30m + 45s"), "5400 + 45");
+        assert_eq!(parse("2d == 48h"), "172800 == 172800");
+        assert_eq!(parse("-1w"), "-604800");
+        assert_eq!(parse("1.5h + 250ms"), "5400 + 0.25");
+        assert_eq!(parse("f(window: 5m)"), "f(window: 300)");
+
+        assert!(LogicParser::parse_expression("15min").is_err());
+        assert!(LogicParser::parse_expression("15 m").is_err());
+        assert!(LogicParser::parse_expression("1h30").is_err());
+    }
+
+    #[test]
+    fn test_map_literal_argument() {
+        let ast =
+            LogicParser::parse_expression("bracket(entry: close, exits: {target: 5%, stop: -2%})")
+                .unwrap();
+        let ASTNode::FunctionCall { args, .. } = &ast else {
+            panic!("Expected a function call, got {:?}", ast);
+        };
+        assert_eq!(
+            args.get_key_value("exits").unwrap(),
+            &HashMap::from([("stop".to_string(), -0.02), ("target".to_string(), 0.05)])
+        );
+        assert_eq!(
+            ast.to_string(),
+            "bracket(entry: close, exits: {stop: -0.02, target: 0.05})"
+        );
+        assert_eq!(
+            LogicParser::parse_expression("f(config: {})")
+                .unwrap()
+                .to_string(),
+            "f(config: {})"
+        );
+
+        assert!(LogicParser::parse_expression("f(config: {a: x})").is_err());
+        assert!(LogicParser::parse_expression("f(config: {a: 1, a: 2})").is_err());
+        assert!(LogicParser::parse_expression("{a: 1}").is_err());
+    }
+
+    #[test]
+    fn test_scoring_calls() {
+        let parse = |input: &str| LogicParser::parse_expression(input).map(|ast| ast.to_string());
+
+        assert_eq!(parse("weight(rsi < 30, 2)").unwrap(), "(rsi < 30) * 2");
+        assert_eq!(parse("weight(volume, w)").unwrap(), "(volume != 0) * w");
+        assert_eq!(
+            parse("score(weight(rsi < 30, 2), close > sma AND NOT halted, bonus)").unwrap(),
+            "(rsi < 30) * 2 + (close > sma AND NOT halted) * 1 + bonus"
+        );
+        assert_eq!(parse("score(a > 1) >= 1").unwrap(), "(a > 1) * 1 >= 1");
+
+        // Named arguments still call a registered function of the same name
+        assert_eq!(
+            parse("weight(cond: x, w: 2)").unwrap(),
+            "weight(cond: x, w: 2)"
+        );
+        assert_eq!(parse("weights(x: 1)").unwrap(), "weights(x: 1)");
+        assert_eq!(
+            parse("weight(a > 1)").unwrap_err(),
+            "weight expects a condition and a weight"
+        );
+        assert_eq!(parse("score()").unwrap(), "score()");
+    }
+
     #[test]
     fn test_property_access() {
         let input = "indicator.ema";
         let ast = LogicParser::parse_expression(input).unwrap();
         let expected_ast = ASTNode::PropertyAccess {
-            base: Box::new(ASTNode::Identifier("indicator".to_string())),
-            property: "ema".to_string(),
+            base: Box::new(ASTNode::Identifier("indicator".into())),
+            property: "ema".into(),
         };
         assert_eq!(ast, expected_ast);
     }
@@ -358,20 +809,20 @@ mod tests {
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("volume".to_string())),
+                    left: Box::new(ASTNode::Identifier("volume".into())),
                     operator: Operator::LessThan,
                     right: Box::new(ASTNode::Number(5000.0)),
                 }),
             }),
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::GreaterThanOrEqual,
                 right: Box::new(ASTNode::Number(3000.0)),
             }),
@@ -385,7 +836,7 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
 
         let expected = ASTNode::NotOperation(Box::new(ASTNode::BinaryOperation {
-            left: Box::new(ASTNode::Identifier("price".to_string())),
+            left: Box::new(ASTNode::Identifier("price".into())),
             operator: Operator::GreaterThan,
             right: Box::new(ASTNode::Number(100.0)),
         }));
@@ -401,20 +852,20 @@ mod tests {
         let expected = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::NotOperation(Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("volume".to_string())),
+                    left: Box::new(ASTNode::Identifier("volume".into())),
                     operator: Operator::LessThan,
                     right: Box::new(ASTNode::Number(5000.0)),
                 }))),
             }),
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::GreaterThanOrEqual,
                 right: Box::new(ASTNode::Number(3000.0)),
             }),
@@ -430,10 +881,10 @@ mod tests {
 
         let expected = ASTNode::PropertyAccess {
             base: Box::new(ASTNode::PropertyAccess {
-                base: Box::new(ASTNode::Identifier("indicator".to_string())),
-                property: "result".to_string(),
+                base: Box::new(ASTNode::Identifier("indicator".into())),
+                property: "result".into(),
             }),
-            property: "signal".to_string(),
+            property: "signal".into(),
         };
 
         assert_eq!(ast, expected);
@@ -445,7 +896,7 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
 
         let expected = ASTNode::FunctionCall {
-            name: "random".to_string(),
+            name: "random".into(),
             args: FunctionArgs {
                 args: HashMap::new(),
             },
@@ -459,7 +910,7 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
 
         let expected = ASTNode::FunctionCall {
-            name: "sma".to_string(),
+            name: "sma".into(),
             args: FunctionArgs {
                 args: HashMap::from([
                     (
@@ -498,7 +949,7 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
 
         // TODO: Should this be an error?
-        assert_eq!(ast, ASTNode::Identifier("price".to_string()));
+        assert_eq!(ast, ASTNode::Identifier("price".into()));
     }
 
     #[test]
@@ -509,20 +960,20 @@ mod tests {
         let expected = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("volume".to_string())),
+                    left: Box::new(ASTNode::Identifier("volume".into())),
                     operator: Operator::LessThan,
                     right: Box::new(ASTNode::Number(5000.0)),
                 }),
             }),
             operator: LogicalOperator::And,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::GreaterThanOrEqual,
                 right: Box::new(ASTNode::Number(3000.0)),
             }),
@@ -538,7 +989,7 @@ mod tests {
 
         let expected = ASTNode::PropertyAccess {
             base: Box::new(ASTNode::FunctionCall {
-                name: "ema".to_string(),
+                name: "ema".into(),
                 args: FunctionArgs {
                     args: HashMap::from([
                         (
@@ -549,7 +1000,7 @@ mod tests {
                     ]),
                 },
             }),
-            property: "signal".to_string(),
+            property: "signal".into(),
         };
 
         assert_eq!(ast, expected);
@@ -561,7 +1012,7 @@ mod tests {
         let ast = LogicParser::parse_expression(input).unwrap();
 
         let expected = ASTNode::FunctionCall {
-            name: "ema".to_string(),
+            name: "ema".into(),
             args: FunctionArgs {
                 args: HashMap::from([
                     (
@@ -611,20 +1062,20 @@ mod tests {
         let expected = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::LogicalOperation {
                     left: Box::new(ASTNode::BinaryOperation {
-                        left: Box::new(ASTNode::Identifier("volume".to_string())),
+                        left: Box::new(ASTNode::Identifier("volume".into())),
                         operator: Operator::LessThan,
                         right: Box::new(ASTNode::Number(5000.0)),
                     }),
                     operator: LogicalOperator::Or,
                     right: Box::new(ASTNode::BinaryOperation {
-                        left: Box::new(ASTNode::Identifier("volume".to_string())),
+                        left: Box::new(ASTNode::Identifier("volume".into())),
                         operator: Operator::GreaterThanOrEqual,
                         right: Box::new(ASTNode::Number(3000.0)),
                     }),
@@ -633,13 +1084,13 @@ mod tests {
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::LessThan,
                     right: Box::new(ASTNode::Number(50.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("volume".to_string())),
+                    left: Box::new(ASTNode::Identifier("volume".into())),
                     operator: Operator::Equal,
                     right: Box::new(ASTNode::Number(1000.0)),
                 }),
@@ -688,20 +1139,20 @@ mod tests {
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::LogicalOperation {
                 left: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier("price".to_string())),
+                    left: Box::new(ASTNode::Identifier("price".into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number(100.0)),
                 }),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::LogicalOperation {
                     left: Box::new(ASTNode::BinaryOperation {
-                        left: Box::new(ASTNode::Identifier("volume".to_string())),
+                        left: Box::new(ASTNode::Identifier("volume".into())),
                         operator: Operator::LessThan,
                         right: Box::new(ASTNode::Number(5000.0)),
                     }),
                     operator: LogicalOperator::Or,
                     right: Box::new(ASTNode::NotOperation(Box::new(ASTNode::BinaryOperation {
-                        left: Box::new(ASTNode::Identifier("open".to_string())),
+                        left: Box::new(ASTNode::Identifier("open".into())),
                         operator: Operator::LessThanOrEqual,
                         right: Box::new(ASTNode::Number(300.0)),
                     }))),
@@ -709,7 +1160,7 @@ mod tests {
             }),
             operator: LogicalOperator::Or,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("close".to_string())),
+                left: Box::new(ASTNode::Identifier("close".into())),
                 operator: Operator::GreaterThan,
                 right: Box::new(ASTNode::Number(1000.0)),
             }),
@@ -775,13 +1226,13 @@ mod tests {
 
         let expected_ast = ASTNode::LogicalOperation {
             left: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("price".to_string())),
+                left: Box::new(ASTNode::Identifier("price".into())),
                 operator: Operator::GreaterThan,
                 right: Box::new(ASTNode::Number(100.0)),
             }),
             operator: LogicalOperator::And,
             right: Box::new(ASTNode::BinaryOperation {
-                left: Box::new(ASTNode::Identifier("volume".to_string())),
+                left: Box::new(ASTNode::Identifier("volume".into())),
                 operator: Operator::LessThan,
                 right: Box::new(ASTNode::Number(5000.0)),
             }),
@@ -807,6 +1258,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_nesting_depth_limit() {
+        let nested = |depth: usize| format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(LogicParser::parse_expression(&nested(MAX_NESTING_DEPTH)).is_ok());
+        assert!(LogicParser::parse_expression(&nested(10_000)).is_err());
+        assert!(LogicParser::parse_expression(&format!("{}1", "-".repeat(10_000))).is_err());
+        assert!(LogicParser::parse_expression(&"-(".repeat(5_000)).is_err());
+        assert!(LogicParser::name_refs(&nested(10_000)).is_err());
+
+        // Sibling negations and groups do not accumulate
+        let flat = (0..500)
+            .map(|i| format!("-(x{} - -1)", i))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        assert!(LogicParser::parse_expression(&flat).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_input_does_not_panic() {
+        let long_number = "9".repeat(400);
+        let inputs = [
+            "",
+            " ",
+            "(",
+            ")",
+            ")(",
+            "((",
+            "-",
+            "--",
+            "!",
+            "NOT",
+            "1.",
+            ".5",
+            "1..2",
+            "a.",
+            "a..b",
+            "f(",
+            "f(a:",
+            "f(a: )",
+            "f(:1)",
+            "f(a: 1,)",
+            "f().",
+            "f().x.y",
+            "1 +",
+            "* 1",
+            "a > > b",
+            "a AND",
+            "OR a",
+            "\0",
+            "é > 1",
+            &long_number,
+        ];
+        for input in inputs {
+            let _ = LogicParser::parse_expression(input);
+            let _ = LogicParser::name_refs(input);
+        }
+    }
+
     #[test]
     fn test_very_large_expression() {
         let input = (0..100)
@@ -818,7 +1327,7 @@ mod tests {
 
         // Generate the expected AST structure programmatically
         let mut expected_ast = ASTNode::BinaryOperation {
-            left: Box::new(ASTNode::Identifier("price0".to_string())),
+            left: Box::new(ASTNode::Identifier("price0".into())),
             operator: Operator::GreaterThan,
             right: Box::new(ASTNode::Number(0.0)),
         };
@@ -828,7 +1337,7 @@ mod tests {
                 left: Box::new(expected_ast),
                 operator: LogicalOperator::And,
                 right: Box::new(ASTNode::BinaryOperation {
-                    left: Box::new(ASTNode::Identifier(format!("price{}", i))),
+                    left: Box::new(ASTNode::Identifier(format!("price{}", i).into())),
                     operator: Operator::GreaterThan,
                     right: Box::new(ASTNode::Number((i * 10) as f64)),
                 }),
@@ -0,0 +1,215 @@
+use crate::ast::{property_path, ASTNode, FunctionArgValue, FunctionArgs, Operator};
+use std::collections::HashMap;
+
+impl ASTNode {
+    /// Substitutes the variables bound in `context` and folds every operation whose operands
+    /// are all known, returning the residual expression.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::Parser;
+    /// use std::collections::HashMap;
+    ///
+    /// let ast = Parser::parse_expression("close > open + tick_size * 2").unwrap();
+    /// let context = HashMap::from([("tick_size".to_string(), 0.25)]);
+    /// assert_eq!(ast.partial_eval(&context).to_string(), "close > open + 0.5");
+    /// ```
+    ///
+    /// Evaluating the residual against the remaining variables gives the same result as
+    /// evaluating the original against all of them. Function calls and custom operators are
+    /// not invoked, since they live on the `Evaluator`, but their operands and identifier
+    /// arguments are substituted.
+    /// Operations that would fail, such as dividing by a known zero, are left in place so
+    /// the error is still reported at evaluation time, and so are equality comparisons,
+    /// whose result depends on the evaluator's tolerance.
+    pub fn partial_eval(&self, context: &HashMap<String, f64>) -> ASTNode {
+        // Each operation is visited twice: first to queue its operands, then, with `true`,
+        // to fold it or rebuild it from their residuals
+        let mut tasks = vec![(self, false)];
+        let mut residuals: Vec<ASTNode> = Vec::new();
+        while let Some((node, operands_done)) = tasks.pop() {
+            let residual = match node {
+                ASTNode::Group(inner) => {
+                    tasks.push((inner, false));
+                    continue;
+                }
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::CustomOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_done =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                    continue;
+                }
+                ASTNode::PropertyAccess { base, property } if !operands_done => {
+                    match property_path(base, property) {
+                        (ASTNode::Identifier(name), path) => context
+                            .get(&format!("{}.{}", name, path))
+                            .map_or_else(|| node.clone(), |value| ASTNode::Number(*value)),
+                        _ => {
+                            tasks.extend([(node, true), (&**base, false)]);
+                            continue;
+                        }
+                    }
+                }
+                ASTNode::Number(_) => node.clone(),
+                ASTNode::Identifier(ident) => context
+                    .get(ident.as_str())
+                    .map_or_else(|| node.clone(), |value| ASTNode::Number(*value)),
+                ASTNode::Negate(_) => match pop(&mut residuals) {
+                    ASTNode::Number(value) => ASTNode::Number(-value),
+                    inner => ASTNode::Negate(Box::new(inner)),
+                },
+                ASTNode::NotOperation(_) => match pop(&mut residuals) {
+                    ASTNode::Number(value) => ASTNode::Number((value == 0.0) as i32 as f64),
+                    inner => ASTNode::NotOperation(Box::new(inner)),
+                },
+                ASTNode::BinaryOperation { operator, .. } => {
+                    let right = pop(&mut residuals);
+                    let left = pop(&mut residuals);
+                    // Equality depends on the evaluator's tolerance, so it is left unfolded
+                    let foldable = !matches!(
+                        operator,
+                        Operator::Equal | Operator::NotEqual | Operator::ApproxEqual
+                    );
+                    match (&left, &right) {
+                        (ASTNode::Number(a), ASTNode::Number(b)) if foldable => {
+                            operator.apply(*a, *b).ok().map(ASTNode::Number)
+                        }
+                        _ => None,
+                    }
+                    .unwrap_or_else(|| ASTNode::BinaryOperation {
+                        left: Box::new(left),
+                        operator: *operator,
+                        right: Box::new(right),
+                    })
+                }
+                ASTNode::LogicalOperation { operator, .. } => {
+                    let right = pop(&mut residuals);
+                    let left = pop(&mut residuals);
+                    match (&left, &right) {
+                        (ASTNode::Number(a), ASTNode::Number(b)) => {
+                            operator.apply(*a, *b).ok().map(ASTNode::Number)
+                        }
+                        _ => None,
+                    }
+                    .unwrap_or_else(|| ASTNode::LogicalOperation {
+                        left: Box::new(left),
+                        operator: *operator,
+                        right: Box::new(right),
+                    })
+                }
+                ASTNode::CustomOperation { operator, .. } => {
+                    let right = pop(&mut residuals);
+                    ASTNode::CustomOperation {
+                        left: Box::new(pop(&mut residuals)),
+                        operator: operator.clone(),
+                        right: Box::new(right),
+                    }
+                }
+                ASTNode::FunctionCall { name, args } => ASTNode::FunctionCall {
+                    name: name.clone(),
+                    args: FunctionArgs {
+                        args: args
+                            .args
+                            .iter()
+                            .map(|(key, value)| {
+                                let value = match value {
+                                    FunctionArgValue::Identifier(ident) => context
+                                        .get(ident)
+                                        .map_or_else(|| value.clone(), |v| (*v).into()),
+                                    value => value.clone(),
+                                };
+                                (key.clone(), value)
+                            })
+                            .collect(),
+                    },
+                },
+                ASTNode::PropertyAccess { property, .. } => ASTNode::PropertyAccess {
+                    base: Box::new(pop(&mut residuals)),
+                    property: property.clone(),
+                },
+            };
+            residuals.push(residual);
+        }
+        pop(&mut residuals)
+    }
+}
+
+/// Pops the residual of an operand, which the traversal order guarantees is there.
+fn pop(residuals: &mut Vec<ASTNode>) -> ASTNode {
+    residuals
+        .pop()
+        .expect("operands are evaluated before their operation")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Evaluator, FunctionResult, Parser};
+    use std::collections::HashMap;
+
+    fn residual(input: &str, context: &[(&str, f64)]) -> String {
+        let context = context
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        Parser::parse_expression(input)
+            .unwrap()
+            .partial_eval(&context)
+            .to_string()
+    }
+
+    #[test]
+    fn test_partial_eval() {
+        assert_eq!(residual("a + b * c", &[("b", 2.0), ("c", 3.0)]), "a + 6");
+        assert_eq!(residual("a + b", &[("a", 1.0), ("b", 2.0)]), "3");
+        assert_eq!(residual("x > 1 AND y", &[("x", 5.0)]), "1 AND y");
+        assert_eq!(residual("NOT (x > 1) OR y", &[("x", 5.0)]), "0 OR y");
+        assert_eq!(residual("-(a * b)", &[("a", 2.0), ("b", 4.0)]), "-8");
+        assert_eq!(residual("a / b", &[("b", 0.0)]), "a / 0");
+        assert_eq!(residual("1 / b", &[("b", 0.0)]), "1 / 0");
+        assert_eq!(
+            residual("ema(period: p, source: close).value > 1", &[("p", 20.0)]),
+            "ema(period: 20, source: close).value > 1"
+        );
+        assert_eq!(residual("a + b", &[]), "a + b");
+    }
+
+    #[test]
+    fn test_partial_eval_preserves_result() {
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function("add", |args| {
+            Ok(FunctionResult::UnnamedF64(
+                args.get_number("a")? + args.get_number("b")?,
+            ))
+        });
+
+        let ast = Parser::parse_expression(
+            "add(a: close, b: offset) > open * (1 + pct) AND NOT volume < min_volume",
+        )
+        .unwrap();
+        let known = HashMap::from([
+            ("offset".to_string(), 0.5),
+            ("pct".to_string(), 0.02),
+            ("min_volume".to_string(), 1000.0),
+        ]);
+        let mut full = known.clone();
+        full.extend([
+            ("close".to_string(), 103.0),
+            ("open".to_string(), 100.0),
+            ("volume".to_string(), 5000.0),
+        ]);
+
+        let residual = ast.partial_eval(&known);
+        assert_eq!(
+            evaluator.evaluate_ast(&residual, &full).unwrap(),
+            evaluator.evaluate_ast(&ast, &full).unwrap()
+        );
+        assert_eq!(
+            residual.to_string(),
+            "add(a: close, b: 0.5) > open * 1.02 AND NOT volume < 1000"
+        );
+    }
+}
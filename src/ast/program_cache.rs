@@ -0,0 +1,161 @@
+use crate::ast::{CompiledExpression, Evaluator};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A least-recently-used cache of compiled expressions, keyed by expression source.
+///
+/// Cloning returns a handle to the same cache, so threads serving requests can share one.
+/// A cache holds programs compiled by one evaluator configuration; `Evaluator` clears its
+/// own whenever functions, operators or settings change.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, ProgramCache};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// let evaluator = Evaluator::new(100);
+/// let cache = ProgramCache::new(2);
+///
+/// let first = cache.get_or_compile(&evaluator, "close > 100").unwrap();
+/// let again = cache.get_or_compile(&evaluator, "close > 100").unwrap();
+/// assert!(Arc::ptr_eq(&first, &again));
+///
+/// let context = HashMap::from([("close".to_string(), 101.0)]);
+/// assert_eq!(again.evaluate(&context), Ok(1.0));
+/// ```
+#[derive(Clone)]
+pub struct ProgramCache {
+    capacity: usize,
+    programs: Arc<Mutex<LruCache<String, Arc<CompiledExpression>>>>,
+}
+
+impl ProgramCache {
+    /// Creates a cache holding up to `capacity` programs. A capacity of 0 disables caching.
+    pub fn new(capacity: usize) -> Self {
+        // A capacity of 0 never inserts, so the inner cache only needs to be valid
+        let bound = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            capacity,
+            programs: Arc::new(Mutex::new(LruCache::new(bound))),
+        }
+    }
+
+    /// Returns the cached program for `expression`, compiling it with `evaluator` on a miss
+    /// and evicting the least recently used program if the cache is full.
+    pub fn get_or_compile(
+        &self,
+        evaluator: &Evaluator,
+        expression: &str,
+    ) -> Result<Arc<CompiledExpression>, String> {
+        if let Some(program) = self.lock().get(expression) {
+            return Ok(program.clone());
+        }
+
+        // Compiled outside the lock, so hits on other expressions are not blocked
+        let program = Arc::new(evaluator.compile_expression(expression)?);
+        if self.capacity > 0 {
+            self.lock().put(expression.to_string(), program.clone());
+        }
+        Ok(program)
+    }
+
+    /// Maximum number of programs held.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of programs currently held.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no program is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discards every cached program.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LruCache<String, Arc<CompiledExpression>>> {
+        // Entries are inserted whole, so a panic cannot leave the cache inconsistent
+        self.programs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::FunctionResult;
+    use std::collections::HashMap;
+    use std::thread;
+
+    #[test]
+    fn test_least_recently_used_eviction() {
+        let evaluator = Evaluator::new(100);
+        let cache = ProgramCache::new(2);
+
+        let a = cache.get_or_compile(&evaluator, "a > 1").unwrap();
+        cache.get_or_compile(&evaluator, "b > 1").unwrap();
+        // Using `a` again makes `b` the least recently used
+        cache.get_or_compile(&evaluator, "a > 1").unwrap();
+        cache.get_or_compile(&evaluator, "c > 1").unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(
+            &a,
+            &cache.get_or_compile(&evaluator, "a > 1").unwrap()
+        ));
+
+        assert!(cache.get_or_compile(&evaluator, "a >").is_err());
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        let uncached = ProgramCache::new(0);
+        uncached.get_or_compile(&evaluator, "a > 1").unwrap();
+        assert!(uncached.is_empty());
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let evaluator = Arc::new(Evaluator::new(100));
+        let cache = ProgramCache::new(8);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let evaluator = evaluator.clone();
+                let cache = cache.clone();
+                thread::spawn(move || {
+                    let context = HashMap::from([("x".to_string(), i as f64)]);
+                    for j in 0..16 {
+                        let expression = format!("x + {}", j % 4);
+                        let program = cache.get_or_compile(&evaluator, &expression).unwrap();
+                        assert_eq!(program.evaluate(&context), Ok((i + j % 4) as f64));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_evaluator_cache_invalidation() {
+        let mut evaluator = Evaluator::new(10);
+        let context = HashMap::new();
+        evaluator.register_function("f", |_| Ok(FunctionResult::UnnamedF64(1.0)));
+        assert_eq!(evaluator.evaluate_expression_with("f()", &context), Ok(1.0));
+        assert_eq!(evaluator.program_cache().len(), 1);
+
+        evaluator.register_function("f", |_| Ok(FunctionResult::UnnamedF64(2.0)));
+        assert!(evaluator.program_cache().is_empty());
+        assert_eq!(evaluator.evaluate_expression_with("f()", &context), Ok(2.0));
+        assert_eq!(evaluator.program_cache().capacity(), 10);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::ast::Evaluator;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"QXRL";
+const VERSION: u8 = 1;
+
+// Entry tags. Expressions and variable names are written once and referred to by id.
+const EXPRESSION: u8 = 0;
+const NAME: u8 = 1;
+const EVALUATION: u8 = 2;
+
+/// One evaluation captured by a `SessionRecorder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub expression: String,
+    pub context: HashMap<String, f64>,
+    pub result: Result<f64, String>,
+    pub duration: Duration,
+}
+
+struct RecorderState {
+    writer: Box<dyn Write + Send>,
+    expressions: HashMap<String, u32>,
+    names: HashMap<String, u32>,
+    /// First write error, reported by `SessionRecorder::flush`.
+    error: Option<io::Error>,
+}
+
+/// Captures every `Evaluator::evaluate_expression` call, with its context, result and
+/// duration, into a compact binary log that `Evaluator::replay` can re-run, e.g. to check
+/// a new version of the crate against production traffic.
+///
+/// Cloning returns a handle to the same log. Write errors do not fail evaluation; the
+/// first one is returned by `flush`.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, SessionRecorder};
+/// use std::collections::HashMap;
+///
+/// let log = tempfile_path();
+/// # fn tempfile_path() -> std::path::PathBuf {
+/// #     std::env::temp_dir().join(format!("quantixis-doctest-{}.qxrl", std::process::id()))
+/// # }
+/// let recorder = SessionRecorder::new(std::fs::File::create(&log).unwrap()).unwrap();
+/// let mut evaluator = Evaluator::new(100);
+/// evaluator.set_recorder(recorder.clone());
+///
+/// let context = HashMap::from([("price".to_string(), 120.0)]);
+/// evaluator.evaluate_expression("price > 100", &context).unwrap();
+/// recorder.flush().unwrap();
+///
+/// // Later, with another build
+/// let report = Evaluator::new(100).replay(std::fs::File::open(&log).unwrap()).unwrap();
+/// assert_eq!(report.evaluations, 1);
+/// assert!(report.mismatches.is_empty());
+/// # std::fs::remove_file(log).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct SessionRecorder {
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl SessionRecorder {
+    /// Starts a log on `writer`. Wrap files in a `BufWriter`, as entries are written
+    /// as they are recorded.
+    pub fn new(mut writer: impl Write + Send + 'static) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(RecorderState {
+                writer: Box::new(writer),
+                expressions: HashMap::new(),
+                names: HashMap::new(),
+                error: None,
+            })),
+        })
+    }
+
+    /// Flushes the log, returning the first error hit while writing it.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        if let Some(err) = state.error.take() {
+            return Err(err);
+        }
+        state.writer.flush()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecorderState> {
+        // Entries are written whole, so a panic cannot leave one half written
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn record(
+        &self,
+        expression: &str,
+        context: &HashMap<String, f64>,
+        result: &Result<f64, String>,
+        duration: Duration,
+    ) {
+        let mut state = self.lock();
+        if state.error.is_some() {
+            return;
+        }
+
+        let mut entry = Vec::new();
+        let expression_id = intern(&mut state.expressions, expression, EXPRESSION, &mut entry);
+        let context: Vec<(u32, f64)> = context
+            .iter()
+            .map(|(name, value)| (intern(&mut state.names, name, NAME, &mut entry), *value))
+            .collect();
+
+        entry.push(EVALUATION);
+        entry.extend_from_slice(&expression_id.to_le_bytes());
+        entry.extend_from_slice(&(duration.as_nanos() as u64).to_le_bytes());
+        entry.extend_from_slice(&(context.len() as u32).to_le_bytes());
+        for (name_id, value) in context {
+            entry.extend_from_slice(&name_id.to_le_bytes());
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        match result {
+            Ok(value) => {
+                entry.push(0);
+                entry.extend_from_slice(&value.to_le_bytes());
+            }
+            Err(err) => {
+                entry.push(1);
+                write_string(&mut entry, err);
+            }
+        }
+
+        if let Err(err) = state.writer.write_all(&entry) {
+            state.error = Some(err);
+        }
+    }
+}
+
+/// Returns the id of `value`, appending its definition to `entry` on first use.
+fn intern(ids: &mut HashMap<String, u32>, value: &str, tag: u8, entry: &mut Vec<u8>) -> u32 {
+    if let Some(id) = ids.get(value) {
+        return *id;
+    }
+    let id = ids.len() as u32;
+    ids.insert(value.to_string(), id);
+    entry.push(tag);
+    entry.extend_from_slice(&id.to_le_bytes());
+    write_string(entry, value);
+    id
+}
+
+fn write_string(entry: &mut Vec<u8>, value: &str) {
+    entry.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    entry.extend_from_slice(value.as_bytes());
+}
+
+/// Reads the records of a log written by a `SessionRecorder`.
+pub struct SessionLog<R> {
+    reader: R,
+    expressions: Vec<String>,
+    names: Vec<String>,
+}
+
+impl<R: Read> SessionLog<R> {
+    /// Opens a log, checking its header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(invalid(
+                "Not a session log, or written by an unsupported version",
+            ));
+        }
+        Ok(Self {
+            reader,
+            expressions: Vec::new(),
+            names: Vec::new(),
+        })
+    }
+
+    /// Reads the next record, or `None` at the end of the log.
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        loop {
+            let mut tag = [0];
+            if self.reader.read(&mut tag)? == 0 {
+                return Ok(None);
+            }
+            match tag[0] {
+                EXPRESSION => {
+                    self.read_u32()?;
+                    let expression = self.read_string()?;
+                    self.expressions.push(expression);
+                }
+                NAME => {
+                    self.read_u32()?;
+                    let name = self.read_string()?;
+                    self.names.push(name);
+                }
+                EVALUATION => return self.read_evaluation().map(Some),
+                tag => return Err(invalid(&format!("Unknown entry tag {}", tag))),
+            }
+        }
+    }
+
+    fn read_evaluation(&mut self) -> io::Result<Record> {
+        let id = self.read_u32()?;
+        let expression = lookup(&self.expressions, id)?;
+        let duration = Duration::from_nanos(self.read_u64()?);
+        let len = self.read_u32()?;
+        let mut context = HashMap::with_capacity(len as usize);
+        for _ in 0..len {
+            let id = self.read_u32()?;
+            let name = lookup(&self.names, id)?;
+            context.insert(name, f64::from_bits(self.read_u64()?));
+        }
+        let mut tag = [0];
+        self.reader.read_exact(&mut tag)?;
+        let result = match tag[0] {
+            0 => Ok(f64::from_bits(self.read_u64()?)),
+            _ => Err(self.read_string()?),
+        };
+        Ok(Record {
+            expression,
+            context,
+            result,
+            duration,
+        })
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_string(&mut self) -> io::Result<String> {
+        let mut bytes = vec![0; self.read_u32()? as usize];
+        self.reader.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| invalid("Invalid UTF-8"))
+    }
+}
+
+impl<R: Read> Iterator for SessionLog<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn lookup(table: &[String], id: u32) -> io::Result<String> {
+    table
+        .get(id as usize)
+        .cloned()
+        .ok_or_else(|| invalid(&format!("Undefined id {}", id)))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A replayed evaluation whose result differs from the recorded one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub record: Record,
+    /// The result of the replay.
+    pub result: Result<f64, String>,
+}
+
+/// Outcome of `Evaluator::replay`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    /// Number of evaluations replayed.
+    pub evaluations: usize,
+    pub mismatches: Vec<Mismatch>,
+    /// Total duration of the evaluations when recorded.
+    pub recorded_time: Duration,
+    /// Total duration of the evaluations when replayed.
+    pub replay_time: Duration,
+}
+
+impl Evaluator {
+    /// Records every `evaluate_expression` call from now on.
+    pub fn set_recorder(&mut self, recorder: SessionRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Re-evaluates every record of a session log and reports the results that differ.
+    ///
+    /// Results match if both are the same number, both NaN, or the same error.
+    pub fn replay(&mut self, log: impl Read) -> io::Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        for record in SessionLog::new(log)? {
+            let record = record?;
+            let started = Instant::now();
+            let result = self.evaluate_expression(&record.expression, &record.context);
+            report.replay_time += started.elapsed();
+            report.recorded_time += record.duration;
+            report.evaluations += 1;
+
+            let matches = match (&record.result, &result) {
+                (Ok(recorded), Ok(replayed)) => {
+                    recorded == replayed || (recorded.is_nan() && replayed.is_nan())
+                }
+                (Err(recorded), Err(replayed)) => recorded == replayed,
+                _ => false,
+            };
+            if !matches {
+                report.mismatches.push(Mismatch { record, result });
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::FunctionResult;
+
+    /// A `Write` handle whose contents stay readable after the recorder takes it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = SharedBuffer::default();
+        let recorder = SessionRecorder::new(buffer.clone()).unwrap();
+        let mut evaluator = Evaluator::builder()
+            .with_function("double", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 2.0))
+            })
+            .build();
+        evaluator.set_recorder(recorder.clone());
+
+        let contexts = [
+            HashMap::from([("a".to_string(), 1.0), ("b".to_string(), 2.0)]),
+            HashMap::from([("a".to_string(), 3.0), ("b".to_string(), 0.0)]),
+        ];
+        for context in &contexts {
+            for expression in ["double(value: a) > b", "a / b"] {
+                let _ = evaluator.evaluate_expression(expression, context);
+            }
+        }
+        recorder.flush().unwrap();
+
+        let log = buffer.0.lock().unwrap().clone();
+        let records: Vec<Record> = SessionLog::new(log.as_slice())
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[3].expression, "a / b");
+        assert_eq!(records[3].context, contexts[1]);
+        assert_eq!(records[3].result, Err("Division by zero".to_string()));
+        assert_eq!(records[0].result, Ok(0.0));
+
+        // The same build reproduces every result
+        let report = evaluator.replay(log.as_slice()).unwrap();
+        assert_eq!(report.evaluations, 4);
+        assert!(report.mismatches.is_empty());
+
+        // A changed function is caught
+        let mut changed = Evaluator::builder()
+            .with_function("double", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 3.0))
+            })
+            .build();
+        let report = changed.replay(log.as_slice()).unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].record.context, contexts[0]);
+        assert_eq!(report.mismatches[0].result, Ok(1.0));
+
+        assert!(SessionLog::new(&b"QXRL\x07"[..]).is_err());
+        let truncated = &log[..log.len() - 3];
+        assert!(SessionLog::new(truncated)
+            .unwrap()
+            .any(|record| record.is_err()));
+    }
+}
@@ -0,0 +1,719 @@
+use crate::ast::{
+    evaluator::{bind_args, bound_identifiers},
+    property_path, unknown_identifier, ASTNode, Capability, ContextDiff, CostModel, Evaluator,
+    Function, FunctionArgs, FunctionResult, LogicalOperator, Operator, OperatorFunction, Symbol,
+};
+use std::collections::HashMap;
+
+/// One operation of a `RuleProgram`. Operands refer to earlier slots.
+enum Node {
+    Constant(f64),
+    Variable(Symbol),
+    Binary(Operator, usize, usize),
+    Logical(LogicalOperator, usize, usize),
+    Custom(OperatorFunction, usize, usize),
+    Not(usize),
+    Negate(usize),
+    Call {
+        function: Function,
+        args: FunctionArgs,
+        /// Arguments read from the context, as (parameter, identifier) pairs
+        identifiers: Vec<(String, String)>,
+        /// Whether the function is `Capability::Pure`, so that its result can be reused
+        pure: bool,
+    },
+    Property(usize, String),
+}
+
+/// Result of a node: a number, or the named values of a multi-value function call.
+enum Value {
+    Number(f64),
+    Named(HashMap<String, f64>),
+}
+
+/// A set of rules compiled into one program in which common subexpressions, including
+/// function calls, are computed once per evaluation.
+///
+/// Operands of commutative operators match in either order and `<` matches `>` with its
+/// operands swapped, so `close > sma` and `sma < close` share a node. Chains such as
+/// `a + b + c` are not regrouped, since that could change floating-point results.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, Parser};
+/// use std::collections::HashMap;
+///
+/// let evaluator = Evaluator::new(100);
+/// let rules = ["close > sma20", "close > sma50", "sma20 < close AND volume > 1000"]
+///     .map(|rule| Parser::parse_expression(rule).unwrap());
+/// let program = evaluator.compile_rules(&rules).unwrap();
+///
+/// let context = HashMap::from([
+///     ("close".to_string(), 105.0),
+///     ("sma20".to_string(), 100.0),
+///     ("sma50".to_string(), 110.0),
+///     ("volume".to_string(), 5000.0),
+/// ]);
+/// let matches = program.evaluate(&context).unwrap();
+/// assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 2]);
+/// ```
+pub struct RuleProgram {
+    nodes: Vec<Node>,
+    outputs: Vec<usize>,
+    epsilon: Option<f64>,
+    cost: f64,
+}
+
+/// The context and cached node values that `RuleProgram::evaluate_incremental` carries
+/// from one evaluation to the next. A state belongs to one program.
+#[derive(Default)]
+pub struct IncrementalState {
+    context: HashMap<String, f64>,
+    values: Vec<Value>,
+    matches: Option<RuleMatches>,
+}
+
+impl IncrementalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The context as of the last evaluation, with every diff applied.
+    pub fn context(&self) -> &HashMap<String, f64> {
+        &self.context
+    }
+
+    /// The matches of the last successful evaluation.
+    pub fn matches(&self) -> Option<&RuleMatches> {
+        self.matches.as_ref()
+    }
+}
+
+/// Which rules of a `RuleProgram` matched, as a bitmap indexed by rule position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatches {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl RuleMatches {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn set(&mut self, rule: usize) {
+        self.words[rule / 64] |= 1 << (rule % 64);
+    }
+
+    /// Returns whether the rule at `rule` matched.
+    pub fn is_match(&self, rule: usize) -> bool {
+        rule < self.len && self.words[rule / 64] & (1 << (rule % 64)) != 0
+    }
+
+    /// Number of rules evaluated.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there were no rules.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of rules that matched.
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Iterates over the positions of the matching rules in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(|rule| self.is_match(*rule))
+    }
+
+    /// The raw bitmap, with rule `i` at bit `i % 64` of word `i / 64`.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl RuleProgram {
+    /// Evaluates every rule against the context. A rule matches if it evaluates to a
+    /// non-zero value. Fails if any rule fails to evaluate.
+    pub fn evaluate(&self, context: &HashMap<String, f64>) -> Result<RuleMatches, String> {
+        let mut values: Vec<Value> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let value = self.compute(node, &values, context)?;
+            values.push(value);
+        }
+        self.matches(&values)
+    }
+
+    /// Applies `diff` to the context kept in `state` and re-evaluates only what depends
+    /// on the variables that changed, reusing the values cached from the previous call.
+    /// When no variable the program reads changed, nothing is recomputed and the previous
+    /// matches are returned.
+    ///
+    /// Calls to functions that are not `Capability::Pure` are always re-evaluated. The
+    /// first call, and any call after a failed one, evaluates the whole program.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::{ContextDiff, CostModel, Evaluator, IncrementalState, Parser};
+    /// use std::collections::HashMap;
+    ///
+    /// let evaluator = Evaluator::new(100);
+    /// let rules = ["close > sma20", "sma20 > sma50"].map(|rule| Parser::parse_expression(rule).unwrap());
+    /// let program = evaluator.compile_rules(&rules).unwrap();
+    ///
+    /// let context = HashMap::from([
+    ///     ("close".to_string(), 99.0),
+    ///     ("sma20".to_string(), 100.0),
+    ///     ("sma50".to_string(), 95.0),
+    /// ]);
+    /// let mut state = IncrementalState::new();
+    /// let diff = ContextDiff::between(&HashMap::new(), &context);
+    /// let matches = program.evaluate_incremental(&mut state, &diff).unwrap();
+    /// assert_eq!(matches.iter().collect::<Vec<_>>(), vec![1]);
+    ///
+    /// // A tick only moves `close`, so `sma20 > sma50` is not recomputed
+    /// let mut tick = ContextDiff::new();
+    /// tick.set("close", 101.0);
+    /// let matches = program.evaluate_incremental(&mut state, &tick).unwrap();
+    /// assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 1]);
+    /// ```
+    pub fn evaluate_incremental(
+        &self,
+        state: &mut IncrementalState,
+        diff: &ContextDiff,
+    ) -> Result<RuleMatches, String> {
+        let touched = diff.apply(&mut state.context);
+
+        // Cleared until the evaluation succeeds, so that a failure forces a full one next
+        let previous = state.matches.take();
+        let full = previous.is_none();
+        if full {
+            state.values.clear();
+        }
+
+        let mut dirty = vec![full; self.nodes.len()];
+        for (slot, node) in self.nodes.iter().enumerate() {
+            if !full {
+                dirty[slot] = match node {
+                    Node::Constant(_) => false,
+                    Node::Variable(name) => touched.contains(name.as_str()),
+                    Node::Binary(_, left, right)
+                    | Node::Logical(_, left, right)
+                    | Node::Custom(_, left, right) => dirty[*left] || dirty[*right],
+                    Node::Not(inner) | Node::Negate(inner) | Node::Property(inner, _) => {
+                        dirty[*inner]
+                    }
+                    Node::Call {
+                        identifiers, pure, ..
+                    } => !pure || identifiers.iter().any(|(_, ident)| touched.contains(ident)),
+                };
+                if !dirty[slot] {
+                    continue;
+                }
+            }
+
+            let value = self.compute(node, &state.values, &state.context)?;
+            if full {
+                state.values.push(value);
+            } else {
+                state.values[slot] = value;
+            }
+        }
+
+        let matches = match previous {
+            Some(matches) if !self.outputs.iter().any(|output| dirty[*output]) => matches,
+            _ => self.matches(&state.values)?,
+        };
+        state.matches = Some(matches.clone());
+        Ok(matches)
+    }
+
+    /// Computes one node from the values of the earlier ones.
+    fn compute(
+        &self,
+        node: &Node,
+        values: &[Value],
+        context: &HashMap<String, f64>,
+    ) -> Result<Value, String> {
+        Ok(match node {
+            Node::Constant(value) => Value::Number(*value),
+            Node::Variable(name) => Value::Number(
+                context
+                    .get(name.as_str())
+                    .copied()
+                    .ok_or_else(|| unknown_identifier(name, context.keys()))?,
+            ),
+            Node::Binary(operator, left, right) => Value::Number(operator.apply_with_tolerance(
+                number(&values[*left])?,
+                number(&values[*right])?,
+                self.epsilon,
+            )?),
+            Node::Logical(operator, left, right) => {
+                Value::Number(operator.apply(number(&values[*left])?, number(&values[*right])?)?)
+            }
+            Node::Custom(function, left, right) => {
+                Value::Number(function(number(&values[*left])?, number(&values[*right])?)?)
+            }
+            Node::Not(inner) => Value::Number((number(&values[*inner])? == 0.0) as i32 as f64),
+            Node::Negate(inner) => Value::Number(-number(&values[*inner])?),
+            Node::Call {
+                function,
+                args,
+                identifiers,
+                ..
+            } => {
+                let call_args = bind_args(args, identifiers, |ident| context.get(ident).copied())
+                    .map_err(|ident| unknown_identifier(ident, context.keys()))?;
+                match function(&call_args)? {
+                    FunctionResult::UnnamedF64(value) => Value::Number(value),
+                    FunctionResult::NamedF64Map(map) => Value::Named(map),
+                }
+            }
+            Node::Property(base, property) => match &values[*base] {
+                Value::Named(map) => match map.get(property) {
+                    Some(value) => Value::Number(*value),
+                    None => return Err(format!("Property {} not found in result", property)),
+                },
+                Value::Number(_) => {
+                    return Err("Expected multi-value, got single value".to_string())
+                }
+            },
+        })
+    }
+
+    fn matches(&self, values: &[Value]) -> Result<RuleMatches, String> {
+        let mut matches = RuleMatches::new(self.outputs.len());
+        for (rule, output) in self.outputs.iter().enumerate() {
+            if number(&values[*output])? != 0.0 {
+                matches.set(rule);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Number of rules in the program.
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns `true` if the program has no rules.
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Estimated cost of one evaluation with the default `CostModel`. Shared
+    /// subexpressions count once, so this is at most the sum of the rules' own costs.
+    pub fn estimated_cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// Number of distinct operations left after sharing common subexpressions.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+fn number(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(value) => Ok(*value),
+        Value::Named(_) => Err("Expected single value, got multi-value".to_string()),
+    }
+}
+
+/// Identifies a node by its operation and the slots of its operands, so that looking up a
+/// subtree takes constant time whatever its size.
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    /// The bits of the number, so that `0` and `-0` stay apart
+    Constant(u64),
+    Variable(Symbol),
+    Binary(Operator, usize, usize),
+    Logical(LogicalOperator, usize, usize),
+    Custom(String, usize, usize),
+    Not(usize),
+    Negate(usize),
+    /// The function name and its arguments as displayed, sorted by name
+    Call(Symbol, String),
+    Property(usize, String),
+}
+
+/// Builds the node list, reusing the slot of any subtree that was already added.
+struct ProgramBuilder<'a> {
+    evaluator: &'a Evaluator,
+    nodes: Vec<Node>,
+    slots: HashMap<Key, usize>,
+    cost: f64,
+}
+
+impl ProgramBuilder<'_> {
+    /// Adds a rule, operands before their operation, and returns its slot. The tree is
+    /// walked with an explicit stack so that deep rules cannot overflow the stack.
+    fn add(&mut self, ast: &ASTNode) -> Result<usize, String> {
+        let mut tasks = vec![(ast, false)];
+        let mut operands: Vec<usize> = Vec::new();
+        while let Some((node, operands_added)) = tasks.pop() {
+            match node {
+                // Groups take the slot of their contents
+                ASTNode::Group(inner) => tasks.push((inner, false)),
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::CustomOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_added =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                }
+                _ => {
+                    let slot = self.add_node(node, &mut operands)?;
+                    operands.push(slot);
+                }
+            }
+        }
+        Ok(operands.pop().expect("every rule has a root operation"))
+    }
+
+    /// Adds a node whose operands were added last, popping their slots.
+    fn add_node(&mut self, ast: &ASTNode, operands: &mut Vec<usize>) -> Result<usize, String> {
+        let mut pop = || operands.pop().expect("operands are added first");
+        let (key, node) = match ast {
+            ASTNode::Number(value) => (Key::Constant(value.to_bits()), Node::Constant(*value)),
+            ASTNode::Identifier(name) => {
+                (Key::Variable(name.clone()), Node::Variable(name.clone()))
+            }
+            ASTNode::BinaryOperation { operator, .. } => {
+                let (right, left) = (pop(), pop());
+                // Only reorderings that give identical results share a slot
+                let key = match operator {
+                    Operator::LessThan => Key::Binary(Operator::GreaterThan, right, left),
+                    Operator::LessThanOrEqual => {
+                        Key::Binary(Operator::GreaterThanOrEqual, right, left)
+                    }
+                    Operator::Add
+                    | Operator::Multiply
+                    | Operator::Equal
+                    | Operator::NotEqual
+                    | Operator::ApproxEqual => {
+                        Key::Binary(*operator, left.min(right), left.max(right))
+                    }
+                    _ => Key::Binary(*operator, left, right),
+                };
+                (key, Node::Binary(*operator, left, right))
+            }
+            ASTNode::LogicalOperation { operator, .. } => {
+                let (right, left) = (pop(), pop());
+                (
+                    Key::Logical(*operator, left.min(right), left.max(right)),
+                    Node::Logical(*operator, left, right),
+                )
+            }
+            ASTNode::CustomOperation { operator, .. } => {
+                let (right, left) = (pop(), pop());
+                (
+                    Key::Custom(operator.clone(), left, right),
+                    Node::Custom(
+                        self.evaluator.custom_operator(operator)?.clone(),
+                        left,
+                        right,
+                    ),
+                )
+            }
+            ASTNode::NotOperation(_) => {
+                let inner = pop();
+                (Key::Not(inner), Node::Not(inner))
+            }
+            ASTNode::Negate(_) => {
+                let inner = pop();
+                (Key::Negate(inner), Node::Negate(inner))
+            }
+            ASTNode::FunctionCall { name, args } => {
+                let key = Key::Call(name.clone(), args.to_string());
+                if let Some(slot) = self.slots.get(&key) {
+                    return Ok(*slot);
+                }
+                let function = self.evaluator.function(name)?.clone();
+                let identifiers = bound_identifiers(self.evaluator.function_info(name), args);
+                let pure = self
+                    .evaluator
+                    .function_info(name)
+                    .is_none_or(|info| info.capability == Capability::Pure);
+                (
+                    key,
+                    Node::Call {
+                        function,
+                        args: args.clone(),
+                        identifiers,
+                        pure,
+                    },
+                )
+            }
+            ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                (call @ ASTNode::FunctionCall { name, .. }, path) => {
+                    self.evaluator.check_output(name, &path)?;
+                    let call = self.add_node(call, operands)?;
+                    (
+                        Key::Property(call, path.clone()),
+                        Node::Property(call, path),
+                    )
+                }
+                (ASTNode::Identifier(name), path) => {
+                    let name: Symbol = format!("{}.{}", name, path).into();
+                    (Key::Variable(name.clone()), Node::Variable(name))
+                }
+                _ => return Err("Base must be a function call or identifier".to_string()),
+            },
+            ASTNode::Group(_) => unreachable!("groups take the slot of their contents"),
+        };
+
+        if let Some(slot) = self.slots.get(&key) {
+            return Ok(*slot);
+        }
+        self.nodes.push(node);
+        self.cost += self.evaluator.node_cost(ast, &CostModel::default());
+        let slot = self.nodes.len() - 1;
+        self.slots.insert(key, slot);
+        Ok(slot)
+    }
+}
+
+impl Evaluator {
+    /// Compiles a set of rules into a single `RuleProgram` that shares common
+    /// subexpressions between them.
+    pub fn compile_rules(&self, rules: &[ASTNode]) -> Result<RuleProgram, String> {
+        for rule in rules {
+            self.check_units(rule)?;
+        }
+        let mut builder = ProgramBuilder {
+            evaluator: self,
+            nodes: Vec::new(),
+            slots: HashMap::new(),
+            cost: 0.0,
+        };
+        let outputs = rules
+            .iter()
+            .map(|rule| builder.add(rule))
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        Ok(RuleProgram {
+            nodes: builder.nodes,
+            outputs,
+            epsilon: self.epsilon,
+            cost: builder.cost,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionInfo, Parser};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn parse_all(rules: &[&str]) -> Vec<ASTNode> {
+        rules
+            .iter()
+            .map(|rule| Parser::parse_expression(rule).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rule_program_matches_individual_evaluation() {
+        let mut evaluator = Evaluator::new(100);
+        let rules = parse_all(&[
+            "close > sma20",
+            "close > sma50",
+            "sma20 < close AND volume > 1000",
+            "(close - sma20) / sma20 > 0.1",
+            "NOT close > sma50 OR volume < 10",
+        ]);
+        let program = evaluator.compile_rules(&rules).unwrap();
+        assert_eq!(program.len(), 5);
+
+        let context = HashMap::from([
+            ("close".to_string(), 105.0),
+            ("sma20".to_string(), 100.0),
+            ("sma50".to_string(), 110.0),
+            ("volume".to_string(), 5000.0),
+        ]);
+        let matches = program.evaluate(&context).unwrap();
+        for (i, rule) in rules.iter().enumerate() {
+            let expected = evaluator.evaluate_ast(rule, &context).unwrap() != 0.0;
+            assert_eq!(matches.is_match(i), expected, "rule {}", i);
+        }
+        assert_eq!(matches.count(), 3);
+        assert_eq!(matches.as_words(), [0b10101]);
+        assert!(!matches.is_match(5));
+    }
+
+    #[test]
+    fn test_shared_subexpressions() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function("bands", move |args| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let value = args.get_number("value")?;
+            Ok(FunctionResult::NamedF64Map(HashMap::from([
+                ("upper".to_string(), value + 1.0),
+                ("lower".to_string(), value - 1.0),
+            ])))
+        });
+
+        let rules = parse_all(&[
+            "price > bands(value: mid).upper",
+            "price < bands(value: mid).lower",
+            "bands(value: mid).upper < price AND price > 0",
+        ]);
+        let program = evaluator.compile_rules(&rules).unwrap();
+        // price, mid-call, upper, >, lower, <, 0, > 0, AND
+        assert_eq!(program.node_count(), 9);
+        let separate: f64 = rules
+            .iter()
+            .map(|rule| evaluator.estimated_cost(rule))
+            .sum();
+        assert!(program.estimated_cost() < separate / 2.0);
+
+        let context = HashMap::from([("price".to_string(), 12.0), ("mid".to_string(), 10.0)]);
+        let matches = program.evaluate(&context).unwrap();
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_sharing_keeps_results_exact() {
+        let mut evaluator = Evaluator::new(100);
+        // 0.1 + 0.2 + 0.3 rounds above 0.6, while 0.3 + 0.2 + 0.1 is exactly 0.6
+        let rules = parse_all(&["c + b + a > 0.6", "a + b + c > 0.6", "b + a + c > 0.6"]);
+        let program = evaluator.compile_rules(&rules).unwrap();
+        let context = HashMap::from([
+            ("a".to_string(), 0.3),
+            ("b".to_string(), 0.2),
+            ("c".to_string(), 0.1),
+        ]);
+        for (i, rule) in rules.iter().enumerate() {
+            let expected = evaluator.evaluate_ast(rule, &context).unwrap() != 0.0;
+            let compiled = evaluator.compile(rule).unwrap().evaluate(&context).unwrap() != 0.0;
+            assert_eq!(compiled, expected, "rule {}", i);
+        }
+        let matches = program.evaluate(&context).unwrap();
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0]);
+        // a, b, c, `c + b`, `a + b` shared with `b + a`, two full sums, 0.6 and two `>`
+        assert_eq!(program.node_count(), 10);
+    }
+
+    #[test]
+    fn test_many_rules() {
+        let evaluator = Evaluator::new(100);
+        let sources: Vec<String> = (0..200).map(|i| format!("close > {}", i)).collect();
+        let rules: Vec<ASTNode> = sources
+            .iter()
+            .map(|rule| Parser::parse_expression(rule).unwrap())
+            .collect();
+        let program = evaluator.compile_rules(&rules).unwrap();
+
+        let context = HashMap::from([("close".to_string(), 100.5)]);
+        let matches = program.evaluate(&context).unwrap();
+        assert_eq!(matches.len(), 200);
+        assert_eq!(matches.count(), 101);
+        assert_eq!(matches.as_words().len(), 4);
+        assert!(matches.is_match(100) && !matches.is_match(101));
+    }
+
+    #[test]
+    fn test_evaluate_incremental() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function("sma", move |args| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(FunctionResult::UnnamedF64(args.get_number("value")? - 1.0))
+        });
+        let rules = parse_all(&["close > sma(value: mid)", "mid > 10", "close > 100"]);
+        let program = evaluator.compile_rules(&rules).unwrap();
+
+        let context = HashMap::from([("close".to_string(), 50.0), ("mid".to_string(), 20.0)]);
+        let mut state = IncrementalState::new();
+        let diff = ContextDiff::between(&HashMap::new(), &context);
+        let matches = program.evaluate_incremental(&mut state, &diff).unwrap();
+        assert_eq!(matches, program.evaluate(&context).unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Only `close` changes: the call is reused
+        let mut tick = ContextDiff::new();
+        tick.set("close", 150.0);
+        let matches = program.evaluate_incremental(&mut state, &tick).unwrap();
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Unchanged and unrelated values skip evaluation entirely
+        tick.set("other", 1.0);
+        assert_eq!(program.evaluate_incremental(&mut state, &tick), Ok(matches));
+        assert_eq!(state.context().len(), 3);
+
+        let mut tick = ContextDiff::new();
+        tick.set("mid", 5.0);
+        let matches = program.evaluate_incremental(&mut state, &tick).unwrap();
+        assert_eq!(matches, program.evaluate(state.context()).unwrap());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // A failure forces a full evaluation once the context is valid again
+        let mut tick = ContextDiff::new();
+        tick.remove("close");
+        assert!(program.evaluate_incremental(&mut state, &tick).is_err());
+        assert!(state.matches().is_none());
+        let mut tick = ContextDiff::new();
+        tick.set("close", 10.0);
+        let matches = program.evaluate_incremental(&mut state, &tick).unwrap();
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_evaluate_incremental_impure_functions() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function_with_info(
+            FunctionInfo::new("now").capability(Capability::ReadsClock),
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(FunctionResult::UnnamedF64(1.0))
+            },
+        );
+        let program = evaluator.compile_rules(&parse_all(&["now() > a"])).unwrap();
+
+        let mut state = IncrementalState::new();
+        let mut diff = ContextDiff::new();
+        diff.set("a", 0.0);
+        program.evaluate_incremental(&mut state, &diff).unwrap();
+        program.evaluate_incremental(&mut state, &diff).unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_rule_program_errors() {
+        let evaluator = Evaluator::new(100);
+        assert!(evaluator
+            .compile_rules(&parse_all(&["missing() > 1"]))
+            .is_err());
+
+        let program = evaluator
+            .compile_rules(&parse_all(&["a > 1", "b > 1"]))
+            .unwrap();
+        let err = program
+            .evaluate(&HashMap::from([("a".to_string(), 2.0)]))
+            .unwrap_err();
+        assert!(err.contains("Identifier 'b' not found"));
+        assert!(evaluator.compile_rules(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use crate::ast::{ASTNode, Evaluator, RuleMatches, RuleProgram};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// A compile error for one rule of a `RuleSet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleError {
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+/// One compiled version of a `RuleSet`. Evaluations hold on to the version they started
+/// with, so a swap never changes the rules under them.
+pub struct RuleSetVersion {
+    version: u64,
+    names: Vec<String>,
+    program: RuleProgram,
+}
+
+impl RuleSetVersion {
+    /// Starts at 1 and increases with every successful swap.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Names of the rules, in the order they were given.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn program(&self) -> &RuleProgram {
+        &self.program
+    }
+
+    /// Evaluates every rule and returns the names of those that matched.
+    pub fn evaluate(&self, context: &HashMap<String, f64>) -> Result<Vec<&str>, String> {
+        let matches = self.program.evaluate(context)?;
+        Ok(matches
+            .iter()
+            .map(|rule| self.names[rule].as_str())
+            .collect())
+    }
+
+    /// Names of the rules set in `matches`, which must come from this version's program.
+    pub fn matched<'a>(&'a self, matches: &'a RuleMatches) -> impl Iterator<Item = &'a str> {
+        matches.iter().map(|rule| self.names[rule].as_str())
+    }
+}
+
+/// A named set of rules, compiled into one `RuleProgram`, that can be replaced while
+/// evaluations are in flight.
+///
+/// `swap` compiles the new rules first and only replaces the live version if every rule
+/// compiles, so a bad reload reports its errors and keeps serving the previous rules.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, RuleSet};
+/// use std::collections::HashMap;
+///
+/// let evaluator = Evaluator::new(100);
+/// let rules = RuleSet::compile(&evaluator, &[("breakout", "close > high_20")]).unwrap();
+/// let context = HashMap::from([("close".to_string(), 105.0), ("high_20".to_string(), 100.0)]);
+///
+/// let live = rules.current();
+/// assert_eq!(live.evaluate(&context).unwrap(), vec!["breakout"]);
+///
+/// let errors = rules
+///     .swap(&evaluator, &[("breakout", "close >"), ("dip", "close < low_20")])
+///     .err()
+///     .unwrap();
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].name, "breakout");
+/// assert_eq!(rules.current().version(), 1);
+///
+/// rules.swap(&evaluator, &[("dip", "close < high_20")]).unwrap();
+/// assert_eq!(rules.current().evaluate(&context).unwrap(), Vec::<&str>::new());
+/// // Evaluations that started before the swap keep their version
+/// assert_eq!(live.evaluate(&context).unwrap(), vec!["breakout"]);
+/// ```
+pub struct RuleSet {
+    live: RwLock<Arc<RuleSetVersion>>,
+}
+
+impl RuleSet {
+    /// Compiles `rules`, given as `(name, expression)` pairs, reporting every rule that
+    /// fails to compile.
+    pub fn compile(evaluator: &Evaluator, rules: &[(&str, &str)]) -> Result<Self, Vec<RuleError>> {
+        let mut live = compile_version(evaluator, rules)?;
+        live.version = 1;
+        Ok(Self {
+            live: RwLock::new(Arc::new(live)),
+        })
+    }
+
+    /// The live version. Holding it keeps it alive after a swap.
+    pub fn current(&self) -> Arc<RuleSetVersion> {
+        self.live
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Compiles `rules` and, if they all compile, makes them the live version, returning
+    /// the previous one. Otherwise the live version is left as it is.
+    pub fn swap(
+        &self,
+        evaluator: &Evaluator,
+        rules: &[(&str, &str)],
+    ) -> Result<Arc<RuleSetVersion>, Vec<RuleError>> {
+        // Compiled outside the lock, so evaluations are never blocked on a compile
+        let mut next = compile_version(evaluator, rules)?;
+
+        let mut live = self
+            .live
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        next.version = live.version + 1;
+        Ok(std::mem::replace(&mut *live, Arc::new(next)))
+    }
+}
+
+fn compile_version(
+    evaluator: &Evaluator,
+    rules: &[(&str, &str)],
+) -> Result<RuleSetVersion, Vec<RuleError>> {
+    let mut errors = Vec::new();
+    let mut names = HashSet::new();
+    let mut asts: Vec<ASTNode> = Vec::with_capacity(rules.len());
+    for (name, expression) in rules {
+        let error = |message| RuleError {
+            name: name.to_string(),
+            message,
+        };
+        if !names.insert(*name) {
+            errors.push(error("Duplicate rule name".to_string()));
+            continue;
+        }
+        match evaluator
+            .validate(expression)
+            .and_then(|_| evaluator.parse_expression(expression))
+        {
+            Ok(ast) => asts.push(ast),
+            Err(message) => errors.push(error(message)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let program = evaluator.compile_rules(&asts).map_err(|_| {
+        // Compile one by one to attribute the failure
+        rules
+            .iter()
+            .zip(&asts)
+            .filter_map(|((name, _), ast)| {
+                let message = evaluator.compile_rules(std::slice::from_ref(ast)).err()?;
+                Some(RuleError {
+                    name: name.to_string(),
+                    message,
+                })
+            })
+            .collect::<Vec<_>>()
+    })?;
+    Ok(RuleSetVersion {
+        version: 0,
+        names: rules.iter().map(|(name, _)| name.to_string()).collect(),
+        program,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_rule_set_errors() {
+        let evaluator = Evaluator::new(100);
+        let errors = RuleSet::compile(
+            &evaluator,
+            &[
+                ("a", "close > 1"),
+                ("b", "missing(x: close) > 1"),
+                ("a", "close > 2"),
+                ("c", "close >"),
+            ],
+        )
+        .err()
+        .unwrap();
+        let names: Vec<&str> = errors.iter().map(|error| error.name.as_str()).collect();
+        assert_eq!(names, ["b", "a", "c"]);
+        assert!(errors[0].message.contains("missing"), "{}", errors[0]);
+        assert_eq!(errors[1].to_string(), "a: Duplicate rule name");
+    }
+
+    #[test]
+    fn test_swap_during_evaluation() {
+        let evaluator = Evaluator::new(100);
+        let rules = Arc::new(RuleSet::compile(&evaluator, &[("up", "close > 0")]).unwrap());
+        let context = HashMap::from([("close".to_string(), 1.0)]);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let rules = rules.clone();
+                let context = context.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        let live = rules.current();
+                        let matched = live.evaluate(&context).unwrap();
+                        // Every version matches exactly its own single rule
+                        assert_eq!(matched, [live.names()[0].as_str()]);
+                    }
+                })
+            })
+            .collect();
+        for i in 0..100 {
+            let name = format!("up_{}", i);
+            let previous = rules.swap(&evaluator, &[(&name, "close > 0")]).unwrap();
+            assert_eq!(previous.version(), i + 1);
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(rules.current().version(), 101);
+        assert_eq!(rules.current().names(), ["up_99"]);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::ast::{
+    property_path, ASTNode, FunctionArgValue, FunctionArgs, LogicalOperator, Operator,
+    DEFAULT_EPSILON,
+};
+
+/// SQL dialect targeted by `to_sql`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SqlDialect {
+    Postgres,
+    ClickHouse,
+}
+
+/// Translates an expression into a SQL boolean condition, for use in a `WHERE` clause.
+///
+/// Variables become columns of the same name, so a screener rule can be pushed down to the
+/// database instead of being evaluated row by row in process. Numeric results are treated
+/// as true when non-zero, as by the evaluator. Of the functions, only `abs`, `hour`,
+/// `minute` and `day_of_week` have SQL counterparts; other functions and custom operators
+/// are rejected.
+///
+/// ```
+/// use quantixis_rs::ast::{to_sql, Parser, SqlDialect};
+///
+/// let ast = Parser::parse_expression("price > 100 AND abs(value: change) >= 2%").unwrap();
+/// assert_eq!(
+///     to_sql(&ast, SqlDialect::Postgres).unwrap(),
+///     r#""price" > 100 AND ABS("change") >= 0.02"#
+/// );
+/// ```
+pub fn to_sql(ast: &ASTNode, dialect: SqlDialect) -> Result<String, String> {
+    let sql = Translator { dialect }.translate(ast)?;
+    Ok(sql.into_condition().text)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Boolean,
+}
+
+// SQL operator precedences, from loosest to tightest
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const COMPARISON: u8 = 4;
+const ADDITIVE: u8 = 5;
+const MULTIPLICATIVE: u8 = 6;
+const UNARY: u8 = 7;
+const ATOM: u8 = 8;
+
+/// A translated subexpression and the precedence of its outermost operator.
+#[derive(Clone)]
+struct Sql {
+    text: String,
+    kind: Kind,
+    precedence: u8,
+}
+
+impl Sql {
+    fn number(text: String, precedence: u8) -> Self {
+        Self {
+            text,
+            kind: Kind::Number,
+            precedence,
+        }
+    }
+
+    fn boolean(text: String, precedence: u8) -> Self {
+        Self {
+            text,
+            kind: Kind::Boolean,
+            precedence,
+        }
+    }
+
+    /// Returns the text, parenthesized unless it binds at least as tightly as `precedence`.
+    fn into_operand(self, precedence: u8) -> String {
+        if self.precedence >= precedence {
+            self.text
+        } else {
+            format!("({})", self.text)
+        }
+    }
+
+    /// Converts a number into a condition, true when non-zero.
+    fn into_condition(self) -> Sql {
+        match self.kind {
+            Kind::Boolean => self,
+            Kind::Number => {
+                let mut text = self.into_operand(ADDITIVE);
+                text.push_str(" <> 0");
+                Sql::boolean(text, COMPARISON)
+            }
+        }
+    }
+}
+
+struct Translator {
+    dialect: SqlDialect,
+}
+
+impl Translator {
+    fn translate(&self, ast: &ASTNode) -> Result<Sql, String> {
+        // Each operation is visited twice: first to queue its operands, then, with `true`,
+        // to combine their translations
+        let mut tasks = vec![(ast, false)];
+        let mut translated: Vec<Sql> = Vec::new();
+        while let Some((node, operands_done)) = tasks.pop() {
+            let sql = match node {
+                ASTNode::Group(inner) => {
+                    tasks.push((inner, false));
+                    continue;
+                }
+                ASTNode::BinaryOperation { .. }
+                | ASTNode::LogicalOperation { .. }
+                | ASTNode::NotOperation(_)
+                | ASTNode::Negate(_)
+                    if !operands_done =>
+                {
+                    tasks.push((node, true));
+                    tasks.extend(node.operands().rev().map(|operand| (operand, false)));
+                    continue;
+                }
+                ASTNode::Number(n) => self.number(*n)?,
+                ASTNode::Identifier(name) => Sql::number(self.column(name), ATOM),
+                ASTNode::BinaryOperation { operator, .. } => {
+                    let right = pop(&mut translated);
+                    let left = pop(&mut translated);
+                    self.binary(*operator, left, right)
+                }
+                ASTNode::LogicalOperation { operator, .. } => {
+                    let (keyword, precedence) = match operator {
+                        LogicalOperator::And => (" AND ", AND),
+                        LogicalOperator::Or => (" OR ", OR),
+                    };
+                    let right = pop(&mut translated).into_condition();
+                    let left = pop(&mut translated).into_condition();
+                    // AND and OR are associative, so chains need no parentheses
+                    let mut text = left.into_operand(precedence);
+                    text.push_str(keyword);
+                    text.push_str(&right.into_operand(precedence));
+                    Sql::boolean(text, precedence)
+                }
+                ASTNode::NotOperation(_) => {
+                    let inner = pop(&mut translated).into_condition();
+                    Sql::boolean(format!("NOT {}", inner.into_operand(NOT)), NOT)
+                }
+                ASTNode::Negate(_) => {
+                    let inner = self.numeric_operand(pop(&mut translated), ATOM);
+                    Sql::number(format!("-{}", inner), UNARY)
+                }
+                ASTNode::CustomOperation { operator, .. } => {
+                    return Err(format!("Operator {} cannot be translated to SQL", operator))
+                }
+                ASTNode::FunctionCall { name, args } => self.function(name, args)?,
+                ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                    (ASTNode::Identifier(name), path) => {
+                        Sql::number(self.column(&format!("{}.{}", name, path)), ATOM)
+                    }
+                    _ => {
+                        return Err(
+                            "Property access on a function result cannot be translated to SQL"
+                                .to_string(),
+                        )
+                    }
+                },
+            };
+            translated.push(sql);
+        }
+        Ok(pop(&mut translated))
+    }
+
+    /// Combines the translated operands of an arithmetic or comparison operator.
+    fn binary(&self, operator: Operator, left: Sql, right: Sql) -> Sql {
+        if operator == Operator::ApproxEqual {
+            let (abs_l, abs_r) = (
+                self.numeric_operand(left.clone(), OR),
+                self.numeric_operand(right.clone(), OR),
+            );
+            let (l, r) = (
+                self.numeric_operand(left, ADDITIVE),
+                self.numeric_operand(right, UNARY),
+            );
+            return Sql::boolean(
+                format!(
+                    "ABS({} - {}) <= {} * GREATEST(ABS({}), ABS({}), 1)",
+                    l, r, DEFAULT_EPSILON, abs_l, abs_r
+                ),
+                COMPARISON,
+            );
+        }
+        let (symbol, precedence) = sql_operator(&operator);
+        let mut right = self.numeric_operand(right, precedence + 1);
+        if symbol == "-" && right.starts_with('-') {
+            // `--` would start a comment
+            right = format!("({})", right);
+        }
+        let mut text = self.numeric_operand(left, precedence);
+        text.push(' ');
+        text.push_str(symbol);
+        text.push(' ');
+        text.push_str(&right);
+        match precedence {
+            COMPARISON => Sql::boolean(text, precedence),
+            _ => Sql::number(text, precedence),
+        }
+    }
+
+    fn number(&self, n: f64) -> Result<Sql, String> {
+        if !n.is_finite() {
+            return Err(format!("Number {} cannot be translated to SQL", n));
+        }
+        let precedence = if n < 0.0 { UNARY } else { ATOM };
+        Ok(Sql::number(n.to_string(), precedence))
+    }
+
+    fn column(&self, name: &str) -> String {
+        match self.dialect {
+            SqlDialect::Postgres => format!("\"{}\"", name.replace('"', "\"\"")),
+            SqlDialect::ClickHouse => format!("`{}`", name.replace('`', "``")),
+        }
+    }
+
+    /// Returns an operand of an arithmetic or comparison operator binding with
+    /// `precedence`, converting conditions to 1 or 0.
+    fn numeric_operand(&self, sql: Sql, precedence: u8) -> String {
+        match (sql.kind, self.dialect) {
+            (Kind::Boolean, SqlDialect::Postgres) => format!("CAST({} AS INTEGER)", sql.text),
+            // ClickHouse conditions are already 1 or 0
+            (Kind::Boolean, SqlDialect::ClickHouse) | (Kind::Number, _) => {
+                sql.into_operand(precedence)
+            }
+        }
+    }
+
+    fn function(&self, name: &str, args: &FunctionArgs) -> Result<Sql, String> {
+        let (param, postgres, clickhouse) = match name {
+            "abs" => ("value", "ABS({})", "abs({})"),
+            "hour" => (
+                "ts",
+                "EXTRACT(HOUR FROM TO_TIMESTAMP({}) AT TIME ZONE 'UTC')",
+                "toHour(toDateTime({}, 'UTC'))",
+            ),
+            "minute" => (
+                "ts",
+                "EXTRACT(MINUTE FROM TO_TIMESTAMP({}) AT TIME ZONE 'UTC')",
+                "toMinute(toDateTime({}, 'UTC'))",
+            ),
+            "day_of_week" => (
+                "ts",
+                "(EXTRACT(ISODOW FROM TO_TIMESTAMP({}) AT TIME ZONE 'UTC') - 1)",
+                "(toDayOfWeek(toDateTime({}, 'UTC')) - 1)",
+            ),
+            _ => return Err(format!("Function {} cannot be translated to SQL", name)),
+        };
+
+        let arg = match args.args.get(param) {
+            Some(FunctionArgValue::Number(n)) => self.number(*n)?.text,
+            Some(FunctionArgValue::Identifier(ident)) => self.column(ident),
+            Some(_) => {
+                return Err(format!(
+                    "Argument {} of {} cannot be translated to SQL",
+                    param, name
+                ))
+            }
+            None => return Err(format!("Missing argument {} of {}", param, name)),
+        };
+        let template = match self.dialect {
+            SqlDialect::Postgres => postgres,
+            SqlDialect::ClickHouse => clickhouse,
+        };
+        Ok(Sql::number(template.replace("{}", &arg), ATOM))
+    }
+}
+
+/// Pops a translated operand, which the traversal order guarantees is there.
+fn pop(translated: &mut Vec<Sql>) -> Sql {
+    translated
+        .pop()
+        .expect("operands are translated before their operation")
+}
+
+/// Returns the SQL symbol and precedence of an operator other than `~=`.
+fn sql_operator(operator: &Operator) -> (&'static str, u8) {
+    match operator {
+        Operator::Add => ("+", ADDITIVE),
+        Operator::Subtract => ("-", ADDITIVE),
+        Operator::Multiply => ("*", MULTIPLICATIVE),
+        Operator::Divide => ("/", MULTIPLICATIVE),
+        Operator::Modulo => ("%", MULTIPLICATIVE),
+        Operator::GreaterThan => (">", COMPARISON),
+        Operator::LessThan => ("<", COMPARISON),
+        Operator::GreaterThanOrEqual => (">=", COMPARISON),
+        Operator::LessThanOrEqual => ("<=", COMPARISON),
+        Operator::Equal => ("=", COMPARISON),
+        Operator::NotEqual => ("<>", COMPARISON),
+        Operator::ApproxEqual => unreachable!("translated separately"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Parser;
+
+    fn postgres(input: &str) -> Result<String, String> {
+        to_sql(
+            &Parser::parse_expression(input).unwrap(),
+            SqlDialect::Postgres,
+        )
+    }
+
+    fn clickhouse(input: &str) -> Result<String, String> {
+        to_sql(
+            &Parser::parse_expression(input).unwrap(),
+            SqlDialect::ClickHouse,
+        )
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let cases = [
+            (
+                "(price > 100 AND volume < 5000) OR volume >= 3000",
+                r#""price" > 100 AND "volume" < 5000 OR "volume" >= 3000"#,
+            ),
+            (
+                "price > 100 AND (volume < 5000 OR volume >= 3000)",
+                r#""price" > 100 AND ("volume" < 5000 OR "volume" >= 3000)"#,
+            ),
+            ("a AND b OR c", r#""a" <> 0 AND "b" <> 0 OR "c" <> 0"#),
+            (
+                "(close - open) / open * 100 > -2",
+                r#"("close" - "open") / "open" * 100 > -2"#,
+            ),
+            ("a - (b - c) - -d", r#""a" - ("b" - "c") - (-"d") <> 0"#),
+            ("-(-a) * -2", r#"-(-"a") * -2 <> 0"#),
+            ("NOT price == 1.5", r#"NOT "price" = 1.5"#),
+            ("NOT (a > 1 AND b)", r#"NOT ("a" > 1 AND "b" <> 0)"#),
+            ("a != b", r#""a" <> "b""#),
+            (
+                "(a > b) + (c > d) >= 1",
+                r#"CAST("a" > "b" AS INTEGER) + CAST("c" > "d" AS INTEGER) >= 1"#,
+            ),
+            ("bar.close > bar.open", r#""bar.close" > "bar.open""#),
+            (
+                "a + 1 ~= b",
+                r#"ABS("a" + 1 - "b") <= 0.000000001 * GREATEST(ABS("a" + 1), ABS("b"), 1)"#,
+            ),
+            (
+                "day_of_week(ts: time) < 5",
+                r#"(EXTRACT(ISODOW FROM TO_TIMESTAMP("time") AT TIME ZONE 'UTC') - 1) < 5"#,
+            ),
+            ("price - 1", r#""price" - 1 <> 0"#),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(postgres(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_to_sql_clickhouse() {
+        assert_eq!(
+            clickhouse("(a > b) + 1 > 1 AND hour(ts: time) >= 9").unwrap(),
+            "(`a` > `b`) + 1 > 1 AND toHour(toDateTime(`time`, 'UTC')) >= 9"
+        );
+    }
+
+    #[test]
+    fn test_to_sql_errors() {
+        assert_eq!(
+            postgres("simple_moving_average(data: close) > 1").unwrap_err(),
+            "Function simple_moving_average cannot be translated to SQL"
+        );
+        assert_eq!(
+            postgres("pivot_points(values: hlc).support1 > 1").unwrap_err(),
+            "Property access on a function result cannot be translated to SQL"
+        );
+        assert_eq!(
+            postgres("abs(x: 1) > 1").unwrap_err(),
+            "Missing argument value of abs"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A shared name, such as a variable or function name in an `ASTNode`.
+///
+/// The name is reference counted, so cloning a symbol or an AST copies no strings, and
+/// it is freed with the last symbol holding it. Symbols dereference to `str` and compare
+/// equal to strings, so code reading names keeps working with `&str`.
+///
+/// ```
+/// use quantixis_rs::ast::Symbol;
+///
+/// let close = Symbol::new("close");
+/// assert_eq!(close, Symbol::from(String::from("close")));
+/// assert_eq!(close, "close");
+/// assert_eq!(close.len(), 5);
+/// ```
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// Returns a symbol holding a copy of `name`.
+    pub fn new(name: &str) -> Symbol {
+        Symbol(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+/// Orders alphabetically, like the names themselves.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol(name.into())
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_symbols() {
+        let a = Symbol::new("symbol_test_a");
+        let b = Symbol::new("symbol_test_b");
+        assert_eq!(a, Symbol::from("symbol_test_a".to_string()));
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        assert!(a < b);
+        assert_eq!(format!("{} {:?}", a, b), "symbol_test_a \"symbol_test_b\"");
+
+        let names: HashSet<Symbol> = [a.clone(), b, Symbol::new("symbol_test_a")].into();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&a));
+    }
+
+    #[test]
+    fn test_symbols_are_freed() {
+        let symbol = Symbol::new("symbol_test_c");
+        let weak = Arc::downgrade(&symbol.0);
+        let copy = symbol.clone();
+        drop(symbol);
+        assert!(weak.upgrade().is_some());
+        drop(copy);
+        assert!(weak.upgrade().is_none());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::ast::{ASTNode, Evaluator, NameKind, Parser};
+use std::collections::HashMap;
+
+/// An expression with `$`-prefixed parameters that are bound before evaluation, so one
+/// template yields a family of expressions without formatting strings.
+///
+/// ```
+/// use quantixis_rs::ast::Template;
+/// use std::collections::HashMap;
+///
+/// let template = Template::parse("ema(source: close, period: $period) > close * $mult").unwrap();
+/// assert_eq!(template.parameters(), ["period", "mult"]);
+///
+/// let values = HashMap::from([("period".to_string(), 20.0), ("mult".to_string(), 1.02)]);
+/// let ast = template.instantiate(&values).unwrap();
+/// assert_eq!(ast.to_string(), "ema(period: 20, source: close) > close * 1.02");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    ast: ASTNode,
+    parameters: Vec<String>,
+}
+
+impl Template {
+    /// Parses a template written in the default syntax, collecting its parameters.
+    ///
+    /// Use `Evaluator::parse_template` for templates that use an evaluator's configured
+    /// keywords or custom operators.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        Evaluator::new(0).parse_template(source)
+    }
+
+    /// Lists the parameter names, without the `$` prefix, in order of first appearance.
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    /// Returns the template's AST, in which parameters are `$`-prefixed identifiers.
+    pub fn ast(&self) -> &ASTNode {
+        &self.ast
+    }
+
+    /// Substitutes every parameter and returns the resulting expression, with constant
+    /// subexpressions folded.
+    ///
+    /// Fails if a parameter has no value, or if a value is given for a name that is not a
+    /// parameter of the template.
+    pub fn instantiate(&self, values: &HashMap<String, f64>) -> Result<ASTNode, String> {
+        if let Some(missing) = self.parameters.iter().find(|p| !values.contains_key(*p)) {
+            return Err(format!("Missing value for template parameter ${}", missing));
+        }
+        if let Some(unknown) = values.keys().find(|name| !self.parameters.contains(name)) {
+            return Err(format!("Unknown template parameter ${}", unknown));
+        }
+
+        let bindings = values
+            .iter()
+            .map(|(name, value)| (format!("${}", name), *value))
+            .collect();
+        Ok(self.ast.partial_eval(&bindings))
+    }
+}
+
+impl Evaluator {
+    /// Parses a template, accepting the configured keywords and the registered custom
+    /// operators.
+    pub fn parse_template(&self, source: &str) -> Result<Template, String> {
+        let ast = Parser::parse_with(source, &self.keywords, &|symbol| {
+            self.operators
+                .get(symbol)
+                .map(|operator| operator.precedence)
+        })?;
+
+        let mut parameters: Vec<String> = Vec::new();
+        for name_ref in Parser::name_refs_with_keywords(source, &self.keywords)? {
+            if name_ref.kind != NameKind::Parameter {
+                continue;
+            }
+            let name = name_ref.name.trim_start_matches('$');
+            if !parameters.iter().any(|p| p == name) {
+                parameters.push(name.to_string());
+            }
+        }
+
+        Ok(Template { ast, parameters })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{FunctionResult, Keyword, Keywords, COMPARISON_PRECEDENCE};
+
+    #[test]
+    fn test_template_parameters() {
+        let template = Template::parse("$a + x * $b > $a AND f(n: $c, m: y)").unwrap();
+        assert_eq!(template.parameters(), ["a", "b", "c"]);
+        assert!(Template::parse("x > 1").unwrap().parameters().is_empty());
+        assert!(Template::parse("x > $").is_err());
+        assert!(Template::parse("x > $1").is_err());
+    }
+
+    #[test]
+    fn test_template_instantiate() {
+        let mut evaluator = Evaluator::new(100);
+        evaluator.register_function("scale", |args| {
+            Ok(FunctionResult::UnnamedF64(
+                args.get_number("value")? * args.get_number("factor")?,
+            ))
+        });
+
+        let template =
+            Template::parse("scale(value: close, factor: $factor) > $threshold").unwrap();
+        let context = HashMap::from([("close".to_string(), 10.0)]);
+
+        let results: Vec<f64> = [(2.0, 15.0), (1.0, 15.0)]
+            .into_iter()
+            .map(|(factor, threshold)| {
+                let values = HashMap::from([
+                    ("factor".to_string(), factor),
+                    ("threshold".to_string(), threshold),
+                ]);
+                let ast = template.instantiate(&values).unwrap();
+                evaluator.compile(&ast).unwrap().evaluate(&context).unwrap()
+            })
+            .collect();
+        assert_eq!(results, vec![1.0, 0.0]);
+
+        let err = template
+            .instantiate(&HashMap::from([("factor".to_string(), 2.0)]))
+            .unwrap_err();
+        assert_eq!(err, "Missing value for template parameter $threshold");
+
+        let err = template
+            .instantiate(&HashMap::from([
+                ("factor".to_string(), 2.0),
+                ("threshold".to_string(), 1.0),
+                ("thresold".to_string(), 1.0),
+            ]))
+            .unwrap_err();
+        assert_eq!(err, "Unknown template parameter $thresold");
+    }
+
+    #[test]
+    fn test_template_with_evaluator_syntax() {
+        let mut evaluator = Evaluator::new(100);
+        evaluator.set_keywords(Keywords::default().alias("ET", Keyword::And).unwrap());
+        evaluator
+            .register_operator(
+                ">~",
+                COMPARISON_PRECEDENCE,
+                |a, b| Ok((a > b) as i32 as f64),
+            )
+            .unwrap();
+
+        let template = evaluator
+            .parse_template("close >~ $level ET volume > $volume")
+            .unwrap();
+        assert_eq!(template.parameters(), ["level", "volume"]);
+        assert!(Template::parse("close >~ $level ET volume > $volume").is_err());
+
+        let values = HashMap::from([("level".to_string(), 100.0), ("volume".to_string(), 1000.0)]);
+        let context = HashMap::from([("close".to_string(), 101.0), ("volume".to_string(), 2000.0)]);
+        let ast = template.instantiate(&values).unwrap();
+        assert_eq!(evaluator.evaluate(&ast, &context), Ok(1.0));
+    }
+
+    #[test]
+    fn test_unbound_parameter_is_reported() {
+        let mut evaluator = Evaluator::new(100);
+        let err = evaluator
+            .evaluate_expression("x > $limit", &HashMap::from([("x".to_string(), 1.0)]))
+            .unwrap_err();
+        assert!(err.contains("Identifier '$limit' not found in context"));
+        assert!(err.contains("^^^^^^"));
+    }
+}
@@ -0,0 +1,23 @@
+pub fn rule(
+    evaluator: &quantixis_rs::ast::Evaluator,
+    variables: &dyn quantixis_rs::ast::VariableProvider,
+) -> Result<f64, String> {
+    use quantixis_rs::ast::{FunctionArgValue, FunctionArgs, FunctionResult};
+    use std::collections::HashMap;
+
+    let t0 = variables.get("close").ok_or_else(|| "Identifier 'close' not found in context".to_string())?;
+    let t1 = variables.get("open").ok_or_else(|| "Identifier 'open' not found in context".to_string())?;
+    let t2 = t0 - t1;
+    let t3 = if t1 == 0.0 { return Err("Division by zero".to_string()); } else { t2 / t1 };
+    let t4 = (t3 > 0.02) as i32 as f64;
+    let mut args5 = FunctionArgs::with_args(HashMap::from([("data".to_string(), FunctionArgValue::Identifier("close".to_string())), ("width".to_string(), FunctionArgValue::Number(2.0))]));
+    if let Some(value) = variables.get("close") { args5.insert("data", value); }
+    let t5 = match evaluator.call_function("bands", &args5)? { FunctionResult::NamedF64Map(map) => map.get("upper").copied().ok_or_else(|| "Property upper not found in result".to_string())?, FunctionResult::UnnamedF64(_) => return Err("Expected multi-value, got single value".to_string()) };
+    let t6 = variables.get("bar.high").ok_or_else(|| "Identifier 'bar.high' not found in context".to_string())?;
+    let t7 = (t5 >= t6) as i32 as f64;
+    let t8 = (t4 != 0.0 && t7 != 0.0) as i32 as f64;
+    let t9 = variables.get("volume").ok_or_else(|| "Identifier 'volume' not found in context".to_string())?;
+    let t10 = (t9 == 0.0) as i32 as f64;
+    let t11 = (t8 != 0.0 || t10 != 0.0) as i32 as f64;
+    Ok(t11)
+}
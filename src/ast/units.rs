@@ -0,0 +1,241 @@
+use crate::ast::{property_path, ASTNode, Evaluator, Operator};
+use std::collections::HashMap;
+
+/// The units of the context variables, such as `price`, `percent` or `shares`, for
+/// catching rules that mix them up, like `close + rsi`.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, Units};
+/// use std::collections::HashMap;
+///
+/// let units = Units::new()
+///     .unit("close", "price")
+///     .unit("sma", "price")
+///     .unit("rsi", "percent");
+/// let mut evaluator = Evaluator::builder().with_units(units).build();
+///
+/// let context = HashMap::from([
+///     ("close".to_string(), 101.0),
+///     ("sma".to_string(), 100.0),
+///     ("rsi".to_string(), 55.0),
+/// ]);
+/// assert_eq!(evaluator.evaluate_expression("close > sma * 1.01", &context), Ok(0.0));
+/// assert_eq!(
+///     evaluator.evaluate_expression("close > sma + rsi", &context),
+///     Err("Unit error: '+' mixes price and percent in 'sma + rsi'".to_string())
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Units {
+    units: HashMap<String, String>,
+}
+
+/// The unit of a subexpression.
+#[derive(Debug, Clone, PartialEq)]
+enum Dimension {
+    /// A plain number, such as a literal or a comparison result, which goes with any unit
+    Scalar,
+    Unit(String),
+    /// Not tracked, such as a function result or a product of units
+    Unknown,
+}
+
+impl Units {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the unit of `variable`. Variables without a unit are not checked.
+    pub fn unit(mut self, variable: &str, unit: &str) -> Self {
+        self.units.insert(variable.to_string(), unit.to_string());
+        self
+    }
+
+    /// Returns the unit declared for `variable`.
+    pub fn get(&self, variable: &str) -> Option<&str> {
+        self.units.get(variable).map(String::as_str)
+    }
+
+    /// Checks that `ast` only adds, subtracts and compares values of the same unit.
+    ///
+    /// Literals go with any unit, so `close * 1.02` is a price. Dividing a unit by itself
+    /// gives a plain ratio; other products and quotients, function results and custom
+    /// operators are not checked further.
+    pub fn check(&self, ast: &ASTNode) -> Result<(), String> {
+        // Post-order walk with an explicit stack, like `ASTNode::value_type`
+        let mut tasks = vec![(ast, false)];
+        let mut dimensions: Vec<Dimension> = Vec::new();
+        let pop = |dimensions: &mut Vec<Dimension>| {
+            dimensions
+                .pop()
+                .ok_or_else(|| "Unexpected end of expression".to_string())
+        };
+
+        while let Some((node, operands_checked)) = tasks.pop() {
+            let dimension = match node {
+                ASTNode::BinaryOperation { left, right, .. }
+                | ASTNode::LogicalOperation { left, right, .. }
+                | ASTNode::CustomOperation { left, right, .. }
+                    if !operands_checked =>
+                {
+                    tasks.extend([(node, true), (&**right, false), (&**left, false)]);
+                    continue;
+                }
+                ASTNode::NotOperation(inner) | ASTNode::Negate(inner) | ASTNode::Group(inner)
+                    if !operands_checked =>
+                {
+                    tasks.extend([(node, true), (&**inner, false)]);
+                    continue;
+                }
+                ASTNode::BinaryOperation { operator, .. } => {
+                    let (right, left) = (pop(&mut dimensions)?, pop(&mut dimensions)?);
+                    match operator {
+                        Operator::Multiply => match (left, right) {
+                            (Dimension::Scalar, other) | (other, Dimension::Scalar) => other,
+                            _ => Dimension::Unknown,
+                        },
+                        Operator::Divide => match (left, right) {
+                            (Dimension::Unit(left), Dimension::Unit(right)) if left == right => {
+                                Dimension::Scalar
+                            }
+                            (other, Dimension::Scalar) => other,
+                            _ => Dimension::Unknown,
+                        },
+                        Operator::Modulo => left,
+                        _ => {
+                            let combined = match (left, right) {
+                                (Dimension::Unit(left), Dimension::Unit(right))
+                                    if left != right =>
+                                {
+                                    return Err(format!(
+                                        "Unit error: '{}' mixes {} and {} in '{}'",
+                                        operator, left, right, node
+                                    ));
+                                }
+                                (Dimension::Scalar, other) | (other, Dimension::Scalar) => other,
+                                (Dimension::Unknown, _) | (_, Dimension::Unknown) => {
+                                    Dimension::Unknown
+                                }
+                                (unit, _) => unit,
+                            };
+                            match operator {
+                                Operator::Add | Operator::Subtract => combined,
+                                _ => Dimension::Scalar,
+                            }
+                        }
+                    }
+                }
+                ASTNode::LogicalOperation { .. } => {
+                    pop(&mut dimensions)?;
+                    pop(&mut dimensions)?;
+                    Dimension::Scalar
+                }
+                ASTNode::CustomOperation { .. } => {
+                    pop(&mut dimensions)?;
+                    pop(&mut dimensions)?;
+                    Dimension::Unknown
+                }
+                ASTNode::NotOperation(_) => {
+                    pop(&mut dimensions)?;
+                    Dimension::Scalar
+                }
+                ASTNode::Negate(_) | ASTNode::Group(_) => pop(&mut dimensions)?,
+                ASTNode::Number(_) => Dimension::Scalar,
+                ASTNode::Identifier(name) => self.dimension(name),
+                ASTNode::PropertyAccess { base, property } => match property_path(base, property) {
+                    (ASTNode::Identifier(name), path) => {
+                        self.dimension(&format!("{}.{}", name, path))
+                    }
+                    _ => Dimension::Unknown,
+                },
+                ASTNode::FunctionCall { .. } => Dimension::Unknown,
+            };
+            dimensions.push(dimension);
+        }
+
+        pop(&mut dimensions).map(|_| ())
+    }
+
+    fn dimension(&self, variable: &str) -> Dimension {
+        match self.units.get(variable) {
+            Some(unit) => Dimension::Unit(unit.clone()),
+            None => Dimension::Unknown,
+        }
+    }
+}
+
+impl Evaluator {
+    /// Switches to strict units: expressions that add, subtract or compare variables of
+    /// different units fail to parse, validate, evaluate and compile, including ASTs built
+    /// without `Evaluator::parse_expression`. See `Units::check` for the rules.
+    pub fn set_units(&mut self, units: Units) {
+        self.units = Some(units);
+        self.programs.clear();
+    }
+
+    /// Checks `ast` against the units set with `set_units`, if any.
+    pub(crate) fn check_units(&self, ast: &ASTNode) -> Result<(), String> {
+        match &self.units {
+            Some(units) => units.check(ast),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Parser;
+
+    #[test]
+    fn test_unit_check() {
+        let units = Units::new()
+            .unit("close", "price")
+            .unit("open", "price")
+            .unit("change", "percent")
+            .unit("volume", "shares")
+            .unit("quote.bid", "price");
+        let check = |expression| units.check(&Parser::parse_expression(expression).unwrap());
+
+        assert!(check("close - open > 0").is_ok());
+        assert!(check("(close - open) / open * 100 > change").is_ok());
+        assert!(check("close - open > change").is_err());
+        assert!(check("(close - open) / open > change").is_ok());
+        assert!(check("close * 1.02 < -open AND volume > 1000").is_ok());
+        assert!(check("close * volume > 1000000").is_ok());
+        assert!(check("quote.bid < close").is_ok());
+        assert!(check("sma(period: 20) < close + other").is_ok());
+        assert_eq!(
+            check("quote.bid >= volume"),
+            Err("Unit error: '>=' mixes price and shares in 'quote.bid >= volume'".to_string())
+        );
+        assert_eq!(
+            check("(close + change) * 2"),
+            Err("Unit error: '+' mixes price and percent in 'close + change'".to_string())
+        );
+        assert_eq!(units.get("close"), Some("price"));
+    }
+
+    #[test]
+    fn test_strict_units() {
+        let mut evaluator = Evaluator::new(100);
+        let context = HashMap::from([("a".to_string(), 1.0), ("b".to_string(), 2.0)]);
+        assert_eq!(evaluator.evaluate_expression("a + b", &context), Ok(3.0));
+
+        evaluator.set_units(Units::new().unit("a", "price").unit("b", "shares"));
+        assert!(evaluator.evaluate_expression("a + b", &context).is_err());
+        assert!(evaluator.validate("a < b").is_err());
+        assert_eq!(evaluator.evaluate_expression("a * b", &context), Ok(2.0));
+
+        // ASTs parsed without the evaluator are checked too
+        let ast = Parser::parse_expression("a + b").unwrap();
+        let error = Err("Unit error: '+' mixes price and shares in 'a + b'".to_string());
+        assert_eq!(evaluator.evaluate(&ast, &context), error);
+        assert_eq!(evaluator.evaluate_ast(&ast, &context), error);
+        assert!(evaluator.compile(&ast).is_err());
+        assert!(evaluator.compile_rules(std::slice::from_ref(&ast)).is_err());
+        let (a, b) = ([1.0], [2.0]);
+        let columns = HashMap::from([("a".to_string(), &a[..]), ("b".to_string(), &b[..])]);
+        assert!(evaluator.evaluate_columns(&ast, &columns).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::ast::{ASTNode, Evaluator, Operator};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Instant;
+
+/// The type of an expression's result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Number,
+    Boolean,
+    Map,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Number => "number",
+            ValueType::Boolean => "boolean",
+            ValueType::Map => "map",
+        })
+    }
+}
+
+/// A typed result, as returned by `Evaluator::evaluate_typed`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Boolean(bool),
+    /// The named results of a multi-output expression.
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Number(_) => ValueType::Number,
+            Value::Boolean(_) => ValueType::Boolean,
+            Value::Map(_) => ValueType::Map,
+        }
+    }
+
+    /// Returns the value in the untyped representation, with booleans as 1.0 or 0.0, or
+    /// `None` for a map.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            Value::Boolean(value) => Some(*value as i32 as f64),
+            Value::Map(_) => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns a named result of a multi-output expression.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Map(values) => values.get(name),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Map(values) => {
+                let entries: Vec<String> = values
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+impl ASTNode {
+    /// Checks the expression's types and returns the type of its result.
+    ///
+    /// Comparisons, `AND`, `OR` and `NOT` are boolean; arithmetic, variables, functions
+    /// and custom operators are numbers. Arithmetic and ordering comparisons only accept
+    /// numbers, so `(a > b) + 1` is an error, and `==`/`!=` need operands of the same type.
+    /// The exception is `*`, where a boolean masks the other operand, as in the
+    /// `(rsi < 30) * 2` that `weight(rsi < 30, 2)` expands to. Logical operators still
+    /// accept numbers, which are true when non-zero.
+    pub fn value_type(&self) -> Result<ValueType, String> {
+        // Post-order walk with an explicit stack, like `Evaluator::evaluate`
+        let mut tasks = vec![(self, false)];
+        let mut types: Vec<ValueType> = Vec::new();
+
+        while let Some((node, operands_checked)) = tasks.pop() {
+            let value_type = match node {
+                ASTNode::BinaryOperation { left, right, .. }
+                | ASTNode::LogicalOperation { left, right, .. }
+                | ASTNode::CustomOperation { left, right, .. }
+                    if !operands_checked =>
+                {
+                    tasks.extend([(node, true), (&**right, false), (&**left, false)]);
+                    continue;
+                }
+                ASTNode::NotOperation(inner) | ASTNode::Negate(inner) | ASTNode::Group(inner)
+                    if !operands_checked =>
+                {
+                    tasks.extend([(node, true), (&**inner, false)]);
+                    continue;
+                }
+                ASTNode::BinaryOperation { operator, .. } => {
+                    let (right, left) = (pop(&mut types)?, pop(&mut types)?);
+                    match operator {
+                        Operator::Equal | Operator::NotEqual if left == right => ValueType::Boolean,
+                        Operator::Equal | Operator::NotEqual => {
+                            return Err(format!(
+                                "Type error: cannot compare a {} with a {} in '{}'",
+                                left, right, node
+                            ));
+                        }
+                        Operator::Multiply => ValueType::Number,
+                        _ if left == ValueType::Boolean || right == ValueType::Boolean => {
+                            return Err(format!(
+                                "Type error: '{}' expects numbers, got a boolean in '{}'",
+                                operator, node
+                            ));
+                        }
+                        Operator::Add
+                        | Operator::Subtract
+                        | Operator::Divide
+                        | Operator::Modulo => ValueType::Number,
+                        _ => ValueType::Boolean,
+                    }
+                }
+                ASTNode::LogicalOperation { .. } => {
+                    pop(&mut types)?;
+                    pop(&mut types)?;
+                    ValueType::Boolean
+                }
+                ASTNode::CustomOperation { .. } => {
+                    pop(&mut types)?;
+                    pop(&mut types)?;
+                    ValueType::Number
+                }
+                ASTNode::NotOperation(_) => {
+                    pop(&mut types)?;
+                    ValueType::Boolean
+                }
+                ASTNode::Negate(_) => match pop(&mut types)? {
+                    ValueType::Number => ValueType::Number,
+                    other => {
+                        return Err(format!(
+                            "Type error: '-' expects a number, got a {} in '{}'",
+                            other, node
+                        ))
+                    }
+                },
+                ASTNode::Group(_) => pop(&mut types)?,
+                ASTNode::Number(_)
+                | ASTNode::Identifier(_)
+                | ASTNode::FunctionCall { .. }
+                | ASTNode::PropertyAccess { .. } => ValueType::Number,
+            };
+            types.push(value_type);
+        }
+
+        pop(&mut types)
+    }
+}
+
+fn pop(types: &mut Vec<ValueType>) -> Result<ValueType, String> {
+    types
+        .pop()
+        .ok_or_else(|| "Unexpected end of expression".to_string())
+}
+
+impl Evaluator {
+    /// Evaluates an expression to a typed `Value`: comparisons and logical operations give
+    /// `Value::Boolean` rather than 1.0 or 0.0, and mixing booleans into arithmetic is
+    /// rejected before evaluation. See `ASTNode::value_type` for the rules.
+    ///
+    /// A map of named expressions, such as `{signal: close > sma, strength: close - sma}`,
+    /// evaluates every entry and returns them as a `Value::Map`, so that one evaluation
+    /// gives both a decision and its diagnostics.
+    ///
+    /// ```
+    /// use quantixis_rs::ast::{Evaluator, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut evaluator = Evaluator::new(100);
+    /// let context = HashMap::from([("a".to_string(), 2.0), ("b".to_string(), 1.0)]);
+    ///
+    /// assert_eq!(evaluator.evaluate_typed("a > b", &context), Ok(Value::Boolean(true)));
+    /// assert_eq!(evaluator.evaluate_typed("a - b", &context), Ok(Value::Number(1.0)));
+    /// assert!(evaluator.evaluate_typed("(a > b) + 1", &context).is_err());
+    ///
+    /// let outputs = evaluator
+    ///     .evaluate_typed("{signal: a > b, strength: (a - b) / b}", &context)
+    ///     .unwrap();
+    /// assert_eq!(outputs.get("signal"), Some(&Value::Boolean(true)));
+    /// assert_eq!(outputs.to_string(), "{signal: true, strength: 1}");
+    /// ```
+    pub fn evaluate_typed(
+        &mut self,
+        expression: &str,
+        context: &HashMap<String, f64>,
+    ) -> Result<Value, String> {
+        if !expression.trim_start().starts_with('{') {
+            let started = Instant::now();
+            let ast = self.parse_expression(expression)?;
+            let value_type = ast.value_type()?;
+            let value = self.evaluate_parsed(expression, &ast, context, started)?;
+            return Ok(typed(value, value_type));
+        }
+
+        let mut values = BTreeMap::new();
+        for (name, ast) in self.parse_outputs(expression)? {
+            let value = ast
+                .value_type()
+                .and_then(|value_type| Ok(typed(self.evaluate_ast(&ast, context)?, value_type)))
+                .map_err(|err| format!("{}: {}", name, err))?;
+            values.insert(name, value);
+        }
+        Ok(Value::Map(values))
+    }
+}
+
+fn typed(value: f64, value_type: ValueType) -> Value {
+    match value_type {
+        ValueType::Boolean => Value::Boolean(value != 0.0),
+        _ => Value::Number(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Parser;
+
+    #[test]
+    fn test_value_type() {
+        let value_type = |expression| Parser::parse_expression(expression)?.value_type();
+
+        assert_eq!(value_type("a + f(x: 1) * 2"), Ok(ValueType::Number));
+        assert_eq!(value_type("-(a)"), Ok(ValueType::Number));
+        assert_eq!(value_type("a >= 1 AND NOT b"), Ok(ValueType::Boolean));
+        assert_eq!(value_type("(a > 1) == (b > 1)"), Ok(ValueType::Boolean));
+        assert_eq!(value_type("a OR b"), Ok(ValueType::Boolean));
+
+        assert_eq!(
+            value_type("(a > b) * 2 + (c > d) * 1"),
+            Ok(ValueType::Number)
+        );
+        assert_eq!(
+            value_type("(a > b) + 1"),
+            Err("Type error: '+' expects numbers, got a boolean in '(a > b) + 1'".to_string())
+        );
+        assert_eq!(
+            value_type("a == (b > 1)"),
+            Err("Type error: cannot compare a number with a boolean in 'a == (b > 1)'".to_string())
+        );
+        assert!(value_type("-(a AND b)").is_err());
+        assert!(value_type("(a AND b) > 0").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_typed() {
+        let mut evaluator = Evaluator::new(100);
+        let context = HashMap::from([("a".to_string(), 2.0), ("b".to_string(), 0.0)]);
+
+        let value = evaluator
+            .evaluate_typed("a > 1 AND NOT b", &context)
+            .unwrap();
+        assert_eq!(value, Value::Boolean(true));
+        assert_eq!(value.as_f64(), Some(1.0));
+        assert_eq!(value.to_string(), "true");
+        assert_eq!(
+            evaluator.evaluate_typed("a / 4", &context),
+            Ok(Value::Number(0.5))
+        );
+        assert_eq!(
+            evaluator.evaluate_typed("a / b", &context),
+            Err("Division by zero".to_string())
+        );
+
+        // Diagnostics and metrics are those of `evaluate_expression`
+        let metrics = evaluator.enable_metrics();
+        let err = evaluator.evaluate_typed("a > c", &context).unwrap_err();
+        assert!(err.starts_with("error: Identifier 'c' not found in context"));
+        assert_eq!(metrics.get("a > c").unwrap().evaluations, 1);
+    }
+
+    #[test]
+    fn test_evaluate_outputs() {
+        let mut evaluator = Evaluator::new(100);
+        let context = HashMap::from([("close".to_string(), 102.0), ("sma".to_string(), 100.0)]);
+
+        let outputs = evaluator
+            .evaluate_typed(
+                "{ signal: close > sma AND NOT close > 110, strength: (close - sma) / sma }",
+                &context,
+            )
+            .unwrap();
+        assert_eq!(outputs.value_type(), ValueType::Map);
+        assert_eq!(outputs.get("signal"), Some(&Value::Boolean(true)));
+        assert_eq!(outputs.get("strength"), Some(&Value::Number(0.02)));
+        assert_eq!(outputs.as_f64(), None);
+
+        assert_eq!(
+            evaluator.evaluate_typed("{a: close, b: close / 0}", &context),
+            Err("b: Division by zero".to_string())
+        );
+        assert_eq!(
+            evaluator.evaluate_typed("{a: (close > 1) + 2}", &context),
+            Err(
+                "a: Type error: '+' expects numbers, got a boolean in '(close > 1) + 2'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            evaluator.evaluate_typed("{a: close, a: sma}", &context),
+            Err("Duplicate output: a".to_string())
+        );
+        assert!(evaluator.evaluate_typed("{}", &context).is_err());
+        assert!(evaluator
+            .evaluate_expression("{a: close}", &context)
+            .is_err());
+    }
+}
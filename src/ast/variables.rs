@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+/// A source of variable values, queried by name only for the variables an expression uses.
+///
+/// Implement this to fetch values lazily, e.g. from a database or a cache, instead of
+/// materializing every possible variable in a `HashMap` up front.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, VariableProvider};
+///
+/// struct Quotes;
+///
+/// impl VariableProvider for Quotes {
+///     fn get(&self, name: &str) -> Option<f64> {
+///         match name {
+///             "bid" => Some(99.5),
+///             "ask" => Some(100.5),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let evaluator = Evaluator::new(100);
+/// let spread = evaluator.evaluate_expression_with("ask - bid", &Quotes).unwrap();
+/// assert_eq!(spread, 1.0);
+/// ```
+pub trait VariableProvider {
+    /// Returns the value of `name`, or `None` if it is not defined.
+    fn get(&self, name: &str) -> Option<f64>;
+
+    /// Names offered as suggestions when a variable is missing. Empty by default.
+    fn names(&self) -> Vec<&String> {
+        Vec::new()
+    }
+}
+
+impl VariableProvider for HashMap<String, f64> {
+    fn get(&self, name: &str) -> Option<f64> {
+        HashMap::get(self, name).copied()
+    }
+
+    fn names(&self) -> Vec<&String> {
+        self.keys().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Evaluator, FunctionResult};
+    use std::cell::RefCell;
+
+    /// Computes `a_<n>` as `n` on demand and records every lookup.
+    struct Computed {
+        lookups: RefCell<Vec<String>>,
+    }
+
+    impl VariableProvider for Computed {
+        fn get(&self, name: &str) -> Option<f64> {
+            self.lookups.borrow_mut().push(name.to_string());
+            name.strip_prefix("a_")?.parse().ok()
+        }
+    }
+
+    #[test]
+    fn test_variables_are_fetched_lazily() {
+        let evaluator = Evaluator::builder()
+            .with_function("double", |args| {
+                Ok(FunctionResult::UnnamedF64(args.get_number("value")? * 2.0))
+            })
+            .build();
+        let variables = Computed {
+            lookups: RefCell::new(Vec::new()),
+        };
+
+        let result = evaluator
+            .evaluate_expression_with("a_1 + double(value: a_20) > a_3", &variables)
+            .unwrap();
+        assert_eq!(result, 1.0);
+        assert_eq!(*variables.lookups.borrow(), ["a_1", "a_20", "a_3"]);
+
+        let err = evaluator
+            .evaluate_expression_with("a_1 + b", &variables)
+            .unwrap_err();
+        assert_eq!(err, "Identifier 'b' not found in context");
+    }
+
+    #[test]
+    fn test_hash_map_provider_suggests_names() {
+        let evaluator = Evaluator::new(100);
+        let context = HashMap::from([("price".to_string(), 1.0)]);
+        let err = evaluator
+            .evaluate_expression_with("prise > 0", &context)
+            .unwrap_err();
+        assert!(err.ends_with("Did you mean 'price'?"));
+    }
+}
@@ -0,0 +1,114 @@
+//! Command-line front end for evaluating and inspecting expressions.
+//!
+//! ```text
+//! quantixis eval "price > 100" --ctx price=120
+//! quantixis compile "price > 100" --emit ast
+//! quantixis check strategy.rules
+//! ```
+
+use quantixis_rs::ast::{Evaluator, Parser};
+use quantixis_rs::functions::register_functions;
+use std::collections::HashMap;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage:
+  quantixis eval <expression> [--ctx name=value]...
+  quantixis compile <expression> [--emit ast]
+  quantixis check <file>
+
+Rule files contain one expression per line. Blank lines and lines starting with '#' are skipped.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+
+    match args {
+        [command, expression, rest @ ..] if command == "eval" => {
+            let context = parse_context(rest)?;
+            let result = evaluator.evaluate_expression(expression, &context)?;
+            println!("{}", result);
+            Ok(ExitCode::SUCCESS)
+        }
+        [command, expression, rest @ ..] if command == "compile" => {
+            match rest {
+                [] => {}
+                [flag, target] if flag == "--emit" && target == "ast" => {}
+                [flag, target] if flag == "--emit" => {
+                    return Err(format!(
+                        "Unsupported emit target '{}' (expected 'ast')",
+                        target
+                    ))
+                }
+                _ => return Err(USAGE.to_string()),
+            }
+            let ast = Parser::parse_expression(expression)?;
+            evaluator.compile(&ast)?;
+            println!("{:#?}", ast);
+            Ok(ExitCode::SUCCESS)
+        }
+        [command, path] if command == "check" => check(&evaluator, path),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Parses `--ctx name=value` pairs into an evaluation context.
+fn parse_context(args: &[String]) -> Result<HashMap<String, f64>, String> {
+    let mut context = HashMap::new();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag != "--ctx" {
+            return Err(format!("Unexpected argument '{}'\n\n{}", flag, USAGE));
+        }
+        let binding = args
+            .next()
+            .ok_or_else(|| "--ctx expects a name=value pair".to_string())?;
+        let (name, value) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid binding '{}', expected name=value", binding))?;
+        let value = value
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid number '{}' for '{}'", value, name))?;
+        context.insert(name.trim().to_string(), value);
+    }
+    Ok(context)
+}
+
+/// Validates every rule in a file, reporting all failures rather than stopping at the first.
+fn check(evaluator: &Evaluator, path: &str) -> Result<ExitCode, String> {
+    let source =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let mut rules = 0;
+    let mut failures = 0;
+    for (index, line) in source.lines().enumerate() {
+        let rule = line.trim();
+        if rule.is_empty() || rule.starts_with('#') {
+            continue;
+        }
+        rules += 1;
+        if let Err(err) = evaluator.validate(rule) {
+            failures += 1;
+            eprintln!("{}:{}: {}\n", path, index + 1, err);
+        }
+    }
+
+    println!("{} rules checked, {} failed", rules, failures);
+    Ok(if failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
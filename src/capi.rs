@@ -0,0 +1,201 @@
+//! C ABI for embedding the evaluator in non-Rust hosts. See `include/quantixis.h`.
+//!
+//! Functions returning `int32_t` use `0` for success and `-1` for failure, in which case
+//! `quantixis_last_error` describes what went wrong on the calling thread.
+
+use crate::ast::{CompiledExpression, Evaluator, Parser};
+use crate::functions::register_functions;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+use std::sync::OnceLock;
+
+/// A compiled expression together with its bound variables. Opaque to C.
+pub struct QuantixisExpression {
+    compiled: CompiledExpression,
+    context: HashMap<String, f64>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    // Interior NULs would truncate the message on the C side anyway
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn evaluator() -> &'static Evaluator {
+    static EVALUATOR: OnceLock<Evaluator> = OnceLock::new();
+    EVALUATOR.get_or_init(|| {
+        let mut evaluator = Evaluator::new(100);
+        register_functions(&mut evaluator);
+        evaluator
+    })
+}
+
+unsafe fn read_str<'a>(value: *const c_char, what: &str) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{} must not be NULL", what));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| format!("{} must be valid UTF-8", what))
+}
+
+/// Parses and compiles an expression. Returns NULL on failure.
+///
+/// # Safety
+///
+/// `expression` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn quantixis_compile(expression: *const c_char) -> *mut QuantixisExpression {
+    let result = read_str(expression, "expression").and_then(|expression| {
+        let ast = Parser::parse_expression(expression)?;
+        evaluator().compile(&ast)
+    });
+
+    match result {
+        Ok(compiled) => Box::into_raw(Box::new(QuantixisExpression {
+            compiled,
+            context: HashMap::new(),
+        })),
+        Err(err) => {
+            set_last_error(&err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Binds a numeric variable used by subsequent evaluations of `expression`.
+///
+/// # Safety
+///
+/// `expression` must be NULL or a pointer returned by `quantixis_compile` that has not been
+/// freed, and `name` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn quantixis_bind_f64(
+    expression: *mut QuantixisExpression,
+    name: *const c_char,
+    value: f64,
+) -> i32 {
+    let Some(expression) = expression.as_mut() else {
+        set_last_error("expression must not be NULL");
+        return -1;
+    };
+    match read_str(name, "name") {
+        Ok(name) => {
+            expression.context.insert(name.to_string(), value);
+            0
+        }
+        Err(err) => {
+            set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Evaluates `expression` against its bound variables and writes the result to `out`.
+///
+/// # Safety
+///
+/// `expression` must be NULL or a live pointer returned by `quantixis_compile`, and `out`
+/// must be NULL or valid for writing a `double`.
+#[no_mangle]
+pub unsafe extern "C" fn quantixis_eval(
+    expression: *const QuantixisExpression,
+    out: *mut f64,
+) -> i32 {
+    let Some(expression) = expression.as_ref() else {
+        set_last_error("expression must not be NULL");
+        return -1;
+    };
+    if out.is_null() {
+        set_last_error("out must not be NULL");
+        return -1;
+    }
+
+    match expression.compiled.evaluate(&expression.context) {
+        Ok(value) => {
+            *out = value;
+            0
+        }
+        Err(err) => {
+            set_last_error(&err);
+            -1
+        }
+    }
+}
+
+/// Returns the last error raised on this thread, or NULL if there was none.
+///
+/// The string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn quantixis_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Frees an expression returned by `quantixis_compile`. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `expression` must be NULL or a pointer returned by `quantixis_compile` that has not
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn quantixis_free(expression: *mut QuantixisExpression) {
+    if !expression.is_null() {
+        drop(Box::from_raw(expression));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(quantixis_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_compile_bind_eval() {
+        let source = CString::new("price > 100 AND volume < 5000").unwrap();
+        let price = CString::new("price").unwrap();
+        let volume = CString::new("volume").unwrap();
+
+        unsafe {
+            let expression = quantixis_compile(source.as_ptr());
+            assert!(!expression.is_null());
+
+            let mut out = 0.0;
+            assert_eq!(quantixis_bind_f64(expression, price.as_ptr(), 120.0), 0);
+            assert_eq!(quantixis_eval(expression, &mut out), -1);
+            assert!(last_error().contains("volume"));
+
+            assert_eq!(quantixis_bind_f64(expression, volume.as_ptr(), 3000.0), 0);
+            assert_eq!(quantixis_eval(expression, &mut out), 0);
+            assert_eq!(out, 1.0);
+
+            quantixis_free(expression);
+        }
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let source = CString::new("price >").unwrap();
+        unsafe {
+            assert!(quantixis_compile(source.as_ptr()).is_null());
+            assert!(last_error().starts_with("Parse error"));
+
+            assert!(quantixis_compile(ptr::null()).is_null());
+            assert_eq!(last_error(), "expression must not be NULL");
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use crate::ast::Evaluator;
+
+/// Generates a Markdown reference page for every function registered on the evaluator.
+pub fn markdown(evaluator: &Evaluator) -> String {
+    let mut out = String::from("# Function Reference\n");
+
+    for info in evaluator.functions() {
+        let signature = info
+            .params
+            .iter()
+            .map(|param| format!("{}: ...", param.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("\n## `{}`\n\n", info.name));
+        out.push_str(&format!("```\n{}({})\n```\n", info.name, signature));
+
+        if let Some(description) = &info.description {
+            out.push_str(&format!("\n{}\n", description));
+        }
+
+        if !info.params.is_empty() {
+            out.push_str("\n| Parameter | Default | Description |\n| --- | --- | --- |\n");
+            for param in &info.params {
+                let default = param
+                    .default
+                    .map_or_else(|| "required".to_string(), |value| value.to_string());
+                out.push_str(&format!(
+                    "| `{}` | {} | {} |\n",
+                    param.name,
+                    default,
+                    param.description.as_deref().unwrap_or("")
+                ));
+            }
+        }
+
+        if !info.outputs.is_empty() {
+            let outputs: Vec<String> = info
+                .outputs
+                .iter()
+                .map(|output| format!("`{}`", output))
+                .collect();
+            out.push_str(&format!("\nOutputs: {}\n", outputs.join(", ")));
+        }
+    }
+
+    out
+}
@@ -0,0 +1,180 @@
+//! Simple statistics over an array argument, so that they need no custom Rust function, and
+//! `count_if`, `all` and `any` for rules like "X happened in the last N bars".
+
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
+use crate::Evaluator;
+
+pub fn register(evaluator: &mut Evaluator) {
+    register_aggregation(
+        evaluator,
+        "sum",
+        "Sum of the values, 0 if there are none.",
+        sum,
+    );
+    register_aggregation(evaluator, "mean", "Arithmetic mean of the values.", mean);
+    register_aggregation(evaluator, "min", "Smallest of the values.", min);
+    register_aggregation(evaluator, "max", "Largest of the values.", max);
+    register_aggregation(evaluator, "count", "Number of values.", count);
+    register_aggregation(evaluator, "first", "First of the values.", first);
+    register_aggregation(evaluator, "last", "Last of the values.", last);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("nth")
+            .description("Value at `index`, counted from 0, or from the end if negative.")
+            .param("values")
+            .param_description("Series of numbers")
+            .param("index")
+            .param_description("Position, e.g. -2 for the second to last value"),
+        nth,
+    );
+    register_window(
+        evaluator,
+        "count_if",
+        "Number of the last `period` conditions that held.",
+        count_if,
+    );
+    register_window(
+        evaluator,
+        "all",
+        "1 if each of the last `period` conditions held, otherwise 0.",
+        all,
+    );
+    register_window(
+        evaluator,
+        "any",
+        "1 if any of the last `period` conditions held, otherwise 0.",
+        any,
+    );
+}
+
+/// Registers a function of the single `values` parameter.
+fn register_aggregation(
+    evaluator: &mut Evaluator,
+    name: &str,
+    description: &str,
+    function: fn(&FunctionArgs) -> Result<FunctionResult, String>,
+) {
+    evaluator.register_function_with_info(
+        FunctionInfo::new(name)
+            .description(description)
+            .param("values")
+            .param_description("Series of numbers"),
+        function,
+    );
+}
+
+/// Registers a function of a series of conditions and an optional window.
+fn register_window(
+    evaluator: &mut Evaluator,
+    name: &str,
+    description: &str,
+    function: fn(&FunctionArgs) -> Result<FunctionResult, String>,
+) {
+    evaluator.register_function_with_info(
+        FunctionInfo::new(name)
+            .description(description)
+            .param("values")
+            .param_description(
+                "Conditions, oldest first, with non-zero for true, e.g. `evaluate_columns` results",
+            )
+            .param("period")
+            .param_description("Number of most recent bars to look at, all of them if omitted"),
+        function,
+    );
+}
+
+/// The last `period` values, or all of them without a `period` argument.
+fn window(args: &FunctionArgs) -> Result<&[f64], String> {
+    let values = args.get_array("values")?;
+    if !args.contains_key("period") {
+        return Ok(values);
+    }
+    let period = args.get_number("period")? as usize;
+    if values.len() < period {
+        return Err("Insufficient data for the specified period".to_string());
+    }
+    Ok(&values[values.len() - period..])
+}
+
+fn non_empty<'a>(args: &'a FunctionArgs, name: &str) -> Result<&'a [f64], String> {
+    let values = args.get_array("values")?;
+    if values.is_empty() {
+        return Err(format!("{} of an empty array", name));
+    }
+    Ok(values)
+}
+
+pub fn sum(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = args.get_array("values")?;
+    Ok(FunctionResult::UnnamedF64(
+        values.iter().fold(0.0, |sum, value| sum + value),
+    ))
+}
+
+pub fn mean(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = non_empty(args, "Mean")?;
+    Ok(FunctionResult::UnnamedF64(
+        values.iter().sum::<f64>() / values.len() as f64,
+    ))
+}
+
+pub fn min(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = non_empty(args, "Minimum")?;
+    Ok(FunctionResult::UnnamedF64(
+        values.iter().copied().fold(f64::INFINITY, f64::min),
+    ))
+}
+
+pub fn max(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = non_empty(args, "Maximum")?;
+    Ok(FunctionResult::UnnamedF64(
+        values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+    ))
+}
+
+pub fn count(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = args.get_array("values")?;
+    Ok(FunctionResult::UnnamedF64(values.len() as f64))
+}
+
+pub fn first(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = non_empty(args, "First")?;
+    Ok(FunctionResult::UnnamedF64(values[0]))
+}
+
+pub fn last(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = non_empty(args, "Last")?;
+    Ok(FunctionResult::UnnamedF64(values[values.len() - 1]))
+}
+
+pub fn nth(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let values = args.get_array("values")?;
+    let index = args.get_number("index")?;
+    let position = if index < 0.0 {
+        values.len() as f64 + index
+    } else {
+        index
+    };
+    if position.fract() != 0.0 || position < 0.0 || position >= values.len() as f64 {
+        return Err(format!(
+            "Index {} out of bounds for {} values",
+            index,
+            values.len()
+        ));
+    }
+    Ok(FunctionResult::UnnamedF64(values[position as usize]))
+}
+
+pub fn count_if(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let held = window(args)?.iter().filter(|value| **value != 0.0).count();
+    Ok(FunctionResult::UnnamedF64(held as f64))
+}
+
+pub fn all(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let held = window(args)?.iter().all(|value| *value != 0.0);
+    Ok(FunctionResult::UnnamedF64(held as i32 as f64))
+}
+
+pub fn any(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let held = window(args)?.iter().any(|value| *value != 0.0);
+    Ok(FunctionResult::UnnamedF64(held as i32 as f64))
+}
@@ -0,0 +1,99 @@
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
+use crate::Evaluator;
+
+pub fn register(evaluator: &mut Evaluator) {
+    evaluator.register_function_with_info(
+        FunctionInfo::new("abs")
+            .description("Absolute value of a number.")
+            .param("value")
+            .param_description("Input number"),
+        abs,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("round")
+            .description("Rounds half away from zero to `digits` decimals.")
+            .param("value")
+            .param_description("Input number")
+            .param_with_default("digits", 0.0)
+            .param_description("Number of decimals, negative to round to tens, hundreds, ..."),
+        round,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("trunc")
+            .description("Integer part of a number, rounding toward zero.")
+            .param("value")
+            .param_description("Input number"),
+        trunc,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("round_to_tick")
+            .description(
+                "Rounds a price to the nearest multiple of the tick size, half away from zero.",
+            )
+            .param("price")
+            .param_description("Price to round")
+            .param("tick_size")
+            .param_description("Minimum price increment, e.g. 0.01 or 0.25"),
+        round_to_tick,
+    );
+}
+
+pub fn abs(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let value = args.get_number("value")?;
+    Ok(FunctionResult::UnnamedF64(value.abs()))
+}
+
+/// Rounds half away from zero, treating values within float error of a half as the half,
+/// so that e.g. `1.005 * 100`, computed as `100.49999999999999`, rounds up.
+fn round_half_away(value: f64) -> f64 {
+    (value + 1e-9_f64.copysign(value)).round()
+}
+
+/// Rounds to `digits` decimals, or to a multiple of `10^-digits` for negative `digits`.
+fn round_to_digits(value: f64, digits: i32) -> f64 {
+    let scale = 10f64.powi(digits.abs());
+    if digits >= 0 {
+        round_half_away(value * scale) / scale
+    } else {
+        round_half_away(value / scale) * scale
+    }
+}
+
+pub fn round(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let value = args.get_number("value")?;
+    let digits = args.get_number("digits").unwrap_or(0.0);
+    if digits.fract() != 0.0 || digits.abs() > 15.0 {
+        return Err(format!("Invalid number of digits: {}", digits));
+    }
+    Ok(FunctionResult::UnnamedF64(round_to_digits(
+        value,
+        digits as i32,
+    )))
+}
+
+pub fn trunc(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let value = args.get_number("value")?;
+    Ok(FunctionResult::UnnamedF64(value.trunc()))
+}
+
+pub fn round_to_tick(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let price = args.get_number("price")?;
+    let tick_size = args.get_number("tick_size")?;
+    if !(tick_size > 0.0 && tick_size.is_finite()) {
+        return Err(format!("Invalid tick size: {}", tick_size));
+    }
+
+    let ticks = round_half_away(price / tick_size);
+    // `ticks * tick_size` carries the float error of the tick size, e.g. 3 * 0.1 is
+    // 0.30000000000000004, so round it to the decimals the tick size has
+    let decimals = (0..=15)
+        .find(|digits| {
+            let scaled = tick_size * 10f64.powi(*digits);
+            (scaled - scaled.round()).abs() < 1e-9 * scaled.max(1.0)
+        })
+        .unwrap_or(15);
+    Ok(FunctionResult::UnnamedF64(round_to_digits(
+        ticks * tick_size,
+        decimals,
+    )))
+}
@@ -1,5 +1,9 @@
+pub mod aggregation;
+pub mod math;
 pub mod momentum;
 pub mod other;
+pub mod runtime;
+pub mod time;
 pub mod trend;
 pub mod volatility;
 pub mod volume;
@@ -7,7 +11,15 @@ pub mod volume;
 use crate::ast::Evaluator;
 
 pub fn register_functions(evaluator: &mut Evaluator) {
+    aggregation::register(evaluator);
+    math::register(evaluator);
+    time::register(evaluator);
+    register_indicators(evaluator);
+}
+
+pub fn register_indicators(evaluator: &mut Evaluator) {
     momentum::register(evaluator);
+    other::register(evaluator);
     trend::register(evaluator);
     volatility::register(evaluator);
     volume::register(evaluator);
@@ -1,18 +1,112 @@
-use crate::ast::{FunctionArgs, FunctionResult};
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
 use crate::Evaluator;
 
 pub fn register(evaluator: &mut Evaluator) {
-    evaluator.register_function("rate_of_change", rate_of_change);
-    evaluator.register_function("stochastic", stochastic);
-    evaluator.register_function("momentum", momentum);
-    evaluator.register_function("commodity_channel_index", commodity_channel_index);
-    evaluator.register_function("chande_momentum_oscillator", chande_momentum_oscillator);
-    evaluator.register_function("relative_vigor_index", relative_vigor_index);
-    evaluator.register_function("williams_percent_r", williams_percent_r);
-    evaluator.register_function("awesome_osc", awesome_oscillator);
-    evaluator.register_function("ad_oscillator", ad_oscillator);
-    evaluator.register_function("klinger_oscillator", klinger_oscillator);
-    evaluator.register_function("choppiness_index", choppiness_index);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("rate_of_change")
+            .description(
+                "Percentage change between the latest value and the value `period` bars back.",
+            )
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        rate_of_change,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("stochastic")
+            .description("Stochastic oscillator %K over `period` bars.")
+            .param("values")
+            .param_description("Flattened (high, low, close) triples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        stochastic,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("momentum")
+            .description("Difference between the latest value and the value `period` bars back.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        momentum,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("commodity_channel_index")
+            .description("Commodity Channel Index (CCI).")
+            .param("values")
+            .param_description("Flattened (high, low, close) triples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        commodity_channel_index,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("chande_momentum_oscillator")
+            .description("Chande Momentum Oscillator (CMO).")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        chande_momentum_oscillator,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("relative_vigor_index")
+            .description("Relative Vigor Index (RVI).")
+            .param("values")
+            .param_description("Flattened (open, high, low, close) quadruples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        relative_vigor_index,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("williams_percent_r")
+            .description("Williams %R.")
+            .param("values")
+            .param_description("Flattened (high, low, close) triples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        williams_percent_r,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("awesome_osc")
+            .description("Awesome Oscillator: short minus long SMA of the bar midpoints.")
+            .param("values")
+            .param_description("Flattened (high, low) pairs")
+            .param_with_default("short_period", 5.0)
+            .param_description("Fast SMA window")
+            .param_with_default("long_period", 34.0)
+            .param_description("Slow SMA window"),
+        awesome_oscillator,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("ad_oscillator")
+            .description("Accumulation/Distribution line over `period` bars.")
+            .param("values")
+            .param_description("Flattened (high, low, close, volume) quadruples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        ad_oscillator,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("klinger_oscillator")
+            .description("Klinger Volume Oscillator: fast minus slow EMA of money flow volume.")
+            .param("values")
+            .param_description("Flattened (high, low, close, volume) quadruples")
+            .param_with_default("fast_period", 34.0)
+            .param_description("Fast EMA window")
+            .param_with_default("slow_period", 55.0)
+            .param_description("Slow EMA window"),
+        klinger_oscillator,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("choppiness_index")
+            .description("Choppiness Index.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        choppiness_index,
+    );
 }
 
 pub fn rate_of_change(args: &FunctionArgs) -> Result<FunctionResult, String> {
@@ -1,9 +1,21 @@
-use crate::ast::{FunctionArgs, FunctionResult};
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
 use crate::Evaluator;
 use std::collections::HashMap;
 
 pub fn register(evaluator: &mut Evaluator) {
-    evaluator.register_function("pivot_points", pivot_points);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("pivot_points")
+            .description(
+                "Classic pivot points. Returns `support1`, `resistance1`, `support2` and `resistance2`.",
+            )
+            .param("values")
+            .param_description("A (high, low, close) triple")
+            .output("support1")
+            .output("resistance1")
+            .output("support2")
+            .output("resistance2"),
+        pivot_points,
+    );
 }
 
 pub fn pivot_points(args: &FunctionArgs) -> Result<FunctionResult, String> {
@@ -0,0 +1,115 @@
+//! Functions that read the evaluation environment rather than their arguments: `random()`,
+//! `now()` and `bar_index()`. None of them is pure, so `RuleProgram::evaluate_incremental`
+//! calls them again every time and deterministic evaluators reject them.
+
+use crate::ast::{Capability, FunctionInfo, FunctionResult};
+use crate::Evaluator;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Clock = Arc<dyn Fn() -> f64 + Send + Sync>;
+
+/// The state behind the runtime functions. Clones share it, so a handle kept after
+/// `register` seeds, steers or advances the functions of the evaluator.
+#[derive(Clone)]
+pub struct Runtime {
+    rng: Arc<Mutex<u64>>,
+    clock: Clock,
+    bar: Arc<AtomicU64>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runtime {
+    /// Seeds `random()` from the current time and reads `now()` from the system clock.
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Self {
+            rng: Arc::new(Mutex::new(seed)),
+            clock: Arc::new(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+            }),
+            bar: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Makes `random()` repeat the same sequence for the same seed.
+    pub fn with_seed(self, seed: u64) -> Self {
+        *self
+            .rng
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = seed;
+        self
+    }
+
+    /// Reads `now()` from `clock`, in Unix seconds, e.g. the bar time in a backtest.
+    pub fn with_clock(mut self, clock: impl Fn() -> f64 + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets the value of `bar_index()`.
+    pub fn set_bar_index(&self, index: u64) {
+        self.bar.store(index, Ordering::SeqCst);
+    }
+
+    /// Moves `bar_index()` to the next bar, returning the new index. Call it once per bar
+    /// when evaluating rules over a rolling window.
+    pub fn next_bar(&self) -> u64 {
+        self.bar.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Registers `random()`, `now()` and `bar_index()` backed by this runtime.
+    pub fn register(&self, evaluator: &mut Evaluator) {
+        let rng = self.rng.clone();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("random")
+                .description("Uniformly distributed number in `[0, 1)`.")
+                .capability(Capability::Random),
+            move |_| {
+                let mut state = rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                Ok(FunctionResult::UnnamedF64(next_random(&mut state)))
+            },
+        );
+
+        let clock = self.clock.clone();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("now")
+                .description("Current time as a Unix timestamp in seconds.")
+                .capability(Capability::ReadsClock),
+            move |_| Ok(FunctionResult::UnnamedF64(clock())),
+        );
+
+        let bar = self.bar.clone();
+        evaluator.register_function_with_info(
+            FunctionInfo::new("bar_index")
+                .description("Position of the current bar, counted from 0.")
+                .capability(Capability::ReadsClock),
+            move |_| Ok(FunctionResult::UnnamedF64(bar.load(Ordering::SeqCst) as f64)),
+        );
+    }
+}
+
+/// Registers the runtime functions with a fresh `Runtime`.
+pub fn register(evaluator: &mut Evaluator) {
+    Runtime::new().register(evaluator);
+}
+
+/// Advances a SplitMix64 generator and maps its output to `[0, 1)`.
+fn next_random(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,82 @@
+//! Calendar helpers for timestamps given as Unix seconds. All of them work in UTC.
+
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
+use crate::Evaluator;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+pub fn register(evaluator: &mut Evaluator) {
+    evaluator.register_function_with_info(
+        FunctionInfo::new("hour")
+            .description("Hour of the day, from 0 to 23.")
+            .param("ts")
+            .param_description("Unix timestamp in seconds"),
+        hour,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("minute")
+            .description("Minute of the hour, from 0 to 59.")
+            .param("ts")
+            .param_description("Unix timestamp in seconds"),
+        minute,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("day_of_week")
+            .description("Day of the week, from 0 for Monday to 6 for Sunday.")
+            .param("ts")
+            .param_description("Unix timestamp in seconds"),
+        day_of_week,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("is_between_time")
+            .description(
+                "1 if the time of day is in `[start, end)`, otherwise 0. A window with `start` after `end` wraps past midnight.",
+            )
+            .param("ts")
+            .param_description("Unix timestamp in seconds")
+            .param("start")
+            .param_description("Start of the window as a time of day, e.g. `9h30m`")
+            .param("end")
+            .param_description("End of the window as a time of day, e.g. `16h`"),
+        is_between_time,
+    );
+}
+
+/// Seconds elapsed since midnight.
+fn time_of_day(ts: f64) -> f64 {
+    ts.rem_euclid(SECONDS_PER_DAY)
+}
+
+pub fn hour(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let ts = args.get_number("ts")?;
+    Ok(FunctionResult::UnnamedF64(
+        (time_of_day(ts) / 3_600.0).floor(),
+    ))
+}
+
+pub fn minute(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let ts = args.get_number("ts")?;
+    Ok(FunctionResult::UnnamedF64(
+        (time_of_day(ts) % 3_600.0 / 60.0).floor(),
+    ))
+}
+
+pub fn day_of_week(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let ts = args.get_number("ts")?;
+    // 1970-01-01 was a Thursday
+    let days = (ts / SECONDS_PER_DAY).floor();
+    Ok(FunctionResult::UnnamedF64((days + 3.0).rem_euclid(7.0)))
+}
+
+pub fn is_between_time(args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let time = time_of_day(args.get_number("ts")?);
+    let start = args.get_number("start")?;
+    let end = args.get_number("end")?;
+
+    let inside = if start <= end {
+        start <= time && time < end
+    } else {
+        time >= start || time < end
+    };
+    Ok(FunctionResult::UnnamedF64(inside as i32 as f64))
+}
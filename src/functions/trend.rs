@@ -1,16 +1,71 @@
-use crate::ast::{FunctionArgs, FunctionResult};
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
 use crate::Evaluator;
 
 pub fn register(evaluator: &mut Evaluator) {
-    evaluator.register_function("simple_moving_average", simple_moving_average);
-    evaluator.register_function("exponential_moving_average", exponential_moving_average);
-    evaluator.register_function("relative_strength_index", relative_strength_index);
-    evaluator.register_function(
-        "moving_average_convergence_divergence",
+    evaluator.register_function_with_info(
+        FunctionInfo::new("simple_moving_average")
+            .description("Simple moving average of the first `period` values.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        simple_moving_average,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("exponential_moving_average")
+            .description("Exponential moving average of the series.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Smoothing window in bars"),
+        exponential_moving_average,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("relative_strength_index")
+            .description("Relative Strength Index (RSI).")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        relative_strength_index,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("moving_average_convergence_divergence")
+            .description("MACD histogram: MACD line minus its signal line.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("short_period", 12.0)
+            .param_description("Fast EMA window")
+            .param_with_default("long_period", 26.0)
+            .param_description("Slow EMA window")
+            .param_with_default("signal_period", 9.0)
+            .param_description("Signal EMA window"),
         moving_average_convergence_divergence,
     );
-    evaluator.register_function("ichimoku_cloud", ichimoku_cloud);
-    evaluator.register_function("parabolic_sar", parabolic_sar);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("ichimoku_cloud")
+            .description("Ichimoku cloud thickness: Senkou Span A minus Senkou Span B.")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("conversion_period", 9.0)
+            .param_description("Tenkan-sen window")
+            .param_with_default("base_period", 26.0)
+            .param_description("Kijun-sen window")
+            .param_with_default("span_b_period", 52.0)
+            .param_description("Senkou Span B window"),
+        ichimoku_cloud,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("parabolic_sar")
+            .description("Parabolic Stop and Reverse (SAR).")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("acceleration_factor", 0.02)
+            .param_description("Acceleration factor step")
+            .param_with_default("max_acceleration", 0.2)
+            .param_description("Acceleration factor cap"),
+        parabolic_sar,
+    );
 }
 
 pub fn simple_moving_average(args: &FunctionArgs) -> Result<FunctionResult, String> {
@@ -90,7 +145,7 @@ pub fn moving_average_convergence_divergence(
     let long_ema = calculate_ema(values, long_period)?;
     let macd = short_ema - long_ema;
 
-    let signal = calculate_ema(&vec![macd], signal_period)?;
+    let signal = calculate_ema(&[macd], signal_period)?;
     let histogram = macd - signal;
 
     Ok(FunctionResult::UnnamedF64(histogram))
@@ -154,16 +209,16 @@ pub fn parabolic_sar(args: &FunctionArgs) -> Result<FunctionResult, String> {
     let mut af = acceleration_factor; // Start with the initial AF
 
     // Calculate SAR for each subsequent value
-    for i in 1..values.len() {
+    for &value in &values[1..] {
         let sar_next = sar + af * (ep - sar); // Next SAR value
         sar = sar_next;
 
         // Check if we need to adjust the direction (reversal)
-        if values[i] > ep {
-            ep = values[i]; // Update EP for an uptrend
+        if value > ep {
+            ep = value; // Update EP for an uptrend
             af = (af + acceleration_factor).min(max_acceleration);
         } else {
-            ep = values[i]; // Update EP for a downtrend
+            ep = value; // Update EP for a downtrend
             af = (af + acceleration_factor).min(max_acceleration);
         }
     }
@@ -1,9 +1,17 @@
-use crate::ast::{FunctionArgs, FunctionResult};
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
 use crate::Evaluator;
 // use std::collections::HashMap;
 
 pub fn register(evaluator: &mut Evaluator) {
-    evaluator.register_function("average_true_range", average_true_range);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("average_true_range")
+            .description("Average True Range (ATR).")
+            .param("values")
+            .param_description("Price series")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        average_true_range,
+    );
     // evaluator.register_function("bollinger_bands", bollinger_bands);
 }
 
@@ -1,9 +1,25 @@
-use crate::ast::{FunctionArgs, FunctionResult};
+use crate::ast::{FunctionArgs, FunctionInfo, FunctionResult};
 use crate::Evaluator;
 
 pub fn register(evaluator: &mut Evaluator) {
-    evaluator.register_function("on_balance_volume", on_balance_volume);
-    evaluator.register_function("chaikin_money_flow", chaikin_money_flow);
+    evaluator.register_function_with_info(
+        FunctionInfo::new("on_balance_volume")
+            .description("On-Balance Volume (OBV).")
+            .param("values")
+            .param_description("Flattened (high, low, close, volume) quadruples")
+            .param_with_default("period", 14.0)
+            .param_description("Lookback window in bars"),
+        on_balance_volume,
+    );
+    evaluator.register_function_with_info(
+        FunctionInfo::new("chaikin_money_flow")
+            .description("Chaikin Money Flow (CMF).")
+            .param("values")
+            .param_description("Flattened (high, low, close, volume) quadruples")
+            .param_with_default("period", 20.0)
+            .param_description("Lookback window in bars"),
+        chaikin_money_flow,
+    );
 }
 
 pub fn on_balance_volume(args: &FunctionArgs) -> Result<FunctionResult, String> {
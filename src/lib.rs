@@ -1,5 +1,18 @@
+// Lets code generated for this crate, e.g. by `Evaluator::generate_rust`, compile in its tests
+#[cfg(test)]
+extern crate self as quantixis_rs;
+
 pub mod ast;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod docs;
 pub mod functions;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+#[cfg(feature = "wasm-sandbox")]
+pub mod sandbox;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use ast::{Evaluator, Parser};
 use functions::register_functions;
@@ -14,3 +27,8 @@ pub fn evaluate_expression(
     register_functions(&mut evaluator);
     evaluator.evaluate(&ast, context)
 }
+
+// Runs the examples in the README as doctests
+#[doc = include_str!("../README.md")]
+#[cfg(doctest)]
+pub struct ReadmeDoctests;
@@ -0,0 +1,350 @@
+//! Indicator packs loaded at runtime from dynamic libraries. See `include/quantixis_plugin.h`.
+//!
+//! A plugin exports `quantixis_plugin_register`, which receives a `PluginRegistrar` and calls
+//! its `register_function` once per function. Functions take their arguments as an array of
+//! `PluginArg`, a number being an array of length 1, write a single result to `out` and
+//! return `0` for success or `-1` for failure. A failing function may write a NUL-terminated
+//! message of up to `PLUGIN_ERROR_CAPACITY` bytes to `error`.
+
+use crate::ast::{Capability, FunctionArgValue, FunctionArgs, FunctionInfo, FunctionResult};
+use crate::Evaluator;
+use libloading::Library;
+use std::ffi::{c_char, c_void, CStr, CString, OsStr};
+use std::sync::Arc;
+
+/// Version of the plugin ABI, passed to plugins in `PluginRegistrar::abi_version`.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Size of the buffer a failing plugin function may write its error message to.
+pub const PLUGIN_ERROR_CAPACITY: usize = 256;
+
+/// A named argument: `len` numbers starting at `values`.
+#[repr(C)]
+pub struct PluginArg {
+    pub name: *const c_char,
+    pub values: *const f64,
+    pub len: usize,
+}
+
+/// A function implemented by a plugin.
+pub type PluginFunction = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    args: *const PluginArg,
+    len: usize,
+    out: *mut f64,
+    error: *mut c_char,
+) -> i32;
+
+/// Handed to the plugin's entry point to register its functions.
+#[repr(C)]
+pub struct PluginRegistrar {
+    pub abi_version: u32,
+    pub context: *mut c_void,
+    pub register_function: unsafe extern "C" fn(
+        context: *mut c_void,
+        name: *const c_char,
+        function: PluginFunction,
+        user_data: *mut c_void,
+    ) -> i32,
+}
+
+/// The entry point every plugin exports as `quantixis_plugin_register`.
+pub type PluginEntryPoint = unsafe extern "C" fn(registrar: *const PluginRegistrar) -> i32;
+
+/// A function registered by a plugin, with the plugin's pointer to its state.
+#[derive(Clone, Copy)]
+struct Registration {
+    function: PluginFunction,
+    user_data: *mut c_void,
+}
+
+// Plugins promise that their functions may be called from any thread, see `Plugin::load`
+unsafe impl Send for Registration {}
+unsafe impl Sync for Registration {}
+
+/// A loaded plugin, whose functions can be registered into any number of evaluators.
+///
+/// The plugin ABI cannot say what a function does, so plugin functions are registered as
+/// `Capability::Network`, the most privileged, unless `Plugin::capability` vouches for less.
+pub struct Plugin {
+    functions: Vec<(String, Registration)>,
+    capability: Capability,
+    /// Keeps the library loaded for as long as an evaluator holds one of its functions
+    library: Option<Arc<Library>>,
+}
+
+impl Plugin {
+    /// Loads the dynamic library at `path` and collects the functions it registers.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialization code. The library must export
+    /// `quantixis_plugin_register` with the `PluginEntryPoint` signature, and its functions
+    /// must follow the ABI described in `include/quantixis_plugin.h` and be safe to call from
+    /// several threads at once.
+    pub unsafe fn load(path: impl AsRef<OsStr>) -> Result<Plugin, String> {
+        let path = path.as_ref();
+        let library = Library::new(path)
+            .map_err(|err| format!("Failed to load plugin {}: {}", path.to_string_lossy(), err))?;
+        let entry_point = *library
+            .get::<PluginEntryPoint>(b"quantixis_plugin_register\0")
+            .map_err(|err| format!("Invalid plugin {}: {}", path.to_string_lossy(), err))?;
+        let mut plugin = Self::from_entry_point(entry_point)?;
+        plugin.library = Some(Arc::new(library));
+        Ok(plugin)
+    }
+
+    /// Collects the functions registered by a plugin's entry point, e.g. one linked
+    /// statically.
+    ///
+    /// # Safety
+    ///
+    /// As for `Plugin::load`, the functions must follow the plugin ABI.
+    pub unsafe fn from_entry_point(entry_point: PluginEntryPoint) -> Result<Plugin, String> {
+        let mut functions: Vec<(String, Registration)> = Vec::new();
+        let registrar = PluginRegistrar {
+            abi_version: PLUGIN_ABI_VERSION,
+            context: &mut functions as *mut Vec<(String, Registration)> as *mut c_void,
+            register_function,
+        };
+        if entry_point(&registrar) != 0 {
+            return Err("Plugin failed to register its functions".to_string());
+        }
+        Ok(Plugin {
+            functions,
+            capability: Capability::Network,
+            library: None,
+        })
+    }
+
+    /// Declares the capability of every function of the plugin, e.g. `Capability::Pure`
+    /// for a pack of indicators that only compute on their arguments.
+    pub fn capability(mut self, capability: Capability) -> Self {
+        self.capability = capability;
+        self
+    }
+
+    /// Names of the functions the plugin registered.
+    pub fn function_names(&self) -> Vec<&str> {
+        self.functions
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Registers the plugin's functions with `evaluator`.
+    pub fn register(&self, evaluator: &mut Evaluator) {
+        for (name, registration) in &self.functions {
+            let registration = *registration;
+            let library = self.library.clone();
+            evaluator.register_function_with_info(
+                FunctionInfo::new(name).capability(self.capability),
+                move |args| {
+                    let _loaded = &library;
+                    call(registration, args)
+                },
+            );
+        }
+    }
+}
+
+unsafe extern "C" fn register_function(
+    context: *mut c_void,
+    name: *const c_char,
+    function: PluginFunction,
+    user_data: *mut c_void,
+) -> i32 {
+    let functions = &mut *(context as *mut Vec<(String, Registration)>);
+    if name.is_null() {
+        return -1;
+    }
+    match CStr::from_ptr(name).to_str() {
+        Ok(name) => {
+            functions.push((
+                name.to_string(),
+                Registration {
+                    function,
+                    user_data,
+                },
+            ));
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Calls a plugin function, passing each argument as an array of numbers.
+fn call(registration: Registration, args: &FunctionArgs) -> Result<FunctionResult, String> {
+    let mut names = Vec::with_capacity(args.args.len());
+    let mut values: Vec<Vec<f64>> = Vec::with_capacity(args.args.len());
+    for (name, value) in &args.args {
+        names.push(CString::new(name.as_str()).map_err(|err| err.to_string())?);
+        values.push(match value {
+            FunctionArgValue::Number(value) => vec![*value],
+            FunctionArgValue::Boolean(value) => vec![*value as i32 as f64],
+            FunctionArgValue::Array(values) => values.clone(),
+            _ => {
+                return Err(format!(
+                    "Argument '{}' must be a number or an array for a plugin function",
+                    name
+                ))
+            }
+        });
+    }
+    let plugin_args: Vec<PluginArg> = names
+        .iter()
+        .zip(&values)
+        .map(|(name, values)| PluginArg {
+            name: name.as_ptr(),
+            values: values.as_ptr(),
+            len: values.len(),
+        })
+        .collect();
+
+    let mut out = 0.0;
+    let mut error = [0 as c_char; PLUGIN_ERROR_CAPACITY];
+    let status = unsafe {
+        (registration.function)(
+            registration.user_data,
+            plugin_args.as_ptr(),
+            plugin_args.len(),
+            &mut out,
+            error.as_mut_ptr(),
+        )
+    };
+    if status == 0 {
+        return Ok(FunctionResult::UnnamedF64(out));
+    }
+    // Terminate the message in case the plugin filled the whole buffer
+    error[PLUGIN_ERROR_CAPACITY - 1] = 0;
+    let message = unsafe { CStr::from_ptr(error.as_ptr()) }.to_string_lossy();
+    Err(if message.is_empty() {
+        "Plugin function failed".to_string()
+    } else {
+        message.into_owned()
+    })
+}
+
+impl Evaluator {
+    /// Loads a plugin and registers its functions as `Capability::Network`, returning their
+    /// names.
+    ///
+    /// # Safety
+    ///
+    /// See `Plugin::load`.
+    pub unsafe fn load_plugin(&mut self, path: impl AsRef<OsStr>) -> Result<Vec<String>, String> {
+        let plugin = Plugin::load(path)?;
+        plugin.register(self);
+        Ok(plugin
+            .function_names()
+            .into_iter()
+            .map(String::from)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::ptr;
+
+    /// `scale(values, factor)`: the sum of `values` times `factor`, or times the `f64`
+    /// behind `user_data` if `factor` is omitted
+    unsafe extern "C" fn scale(
+        user_data: *mut c_void,
+        args: *const PluginArg,
+        len: usize,
+        out: *mut f64,
+        error: *mut c_char,
+    ) -> i32 {
+        let args = std::slice::from_raw_parts(args, len);
+        let mut sum = None;
+        let mut factor = *(user_data as *const f64);
+        for arg in args {
+            let values = std::slice::from_raw_parts(arg.values, arg.len);
+            match CStr::from_ptr(arg.name).to_bytes() {
+                b"values" => sum = Some(values.iter().sum::<f64>()),
+                b"factor" => factor = values[0],
+                _ => {}
+            }
+        }
+        match sum {
+            Some(sum) => {
+                *out = sum * factor;
+                0
+            }
+            None => {
+                let message = b"Missing values\0";
+                ptr::copy_nonoverlapping(message.as_ptr() as *const c_char, error, message.len());
+                -1
+            }
+        }
+    }
+
+    static DEFAULT_FACTOR: f64 = 2.0;
+
+    unsafe extern "C" fn entry_point(registrar: *const PluginRegistrar) -> i32 {
+        let registrar = &*registrar;
+        if registrar.abi_version != PLUGIN_ABI_VERSION {
+            return -1;
+        }
+        (registrar.register_function)(
+            registrar.context,
+            c"pack.scale".as_ptr(),
+            scale,
+            &DEFAULT_FACTOR as *const f64 as *mut c_void,
+        )
+    }
+
+    unsafe extern "C" fn failing_entry_point(_: *const PluginRegistrar) -> i32 {
+        -1
+    }
+
+    #[test]
+    fn test_plugin_functions() {
+        let plugin = unsafe { Plugin::from_entry_point(entry_point) }.unwrap();
+        assert_eq!(plugin.function_names(), vec!["pack.scale"]);
+
+        let mut evaluator = Evaluator::new(100);
+        plugin.register(&mut evaluator);
+        let context = HashMap::from([("x".to_string(), 3.0)]);
+        assert_eq!(
+            evaluator.evaluate_expression("pack.scale(values: x, factor: 10)", &context),
+            Ok(30.0)
+        );
+        assert_eq!(
+            evaluator.evaluate_expression("scale(values: x)", &context),
+            Ok(6.0)
+        );
+        assert_eq!(
+            evaluator.evaluate_expression("scale(factor: 1)", &context),
+            Err("Missing values".to_string())
+        );
+        let args = FunctionArgs::with_args(HashMap::from([(
+            "values".to_string(),
+            FunctionArgValue::Array(vec![1.0, 2.0, 3.0]),
+        )]));
+        assert_eq!(
+            evaluator.call_function("scale", &args),
+            Ok(FunctionResult::UnnamedF64(12.0))
+        );
+
+        evaluator.set_max_capability(Capability::Pure);
+        assert!(evaluator
+            .evaluate_expression("scale(values: x)", &context)
+            .unwrap_err()
+            .contains("Function 'pack.scale' is network"));
+        let plugin = unsafe { Plugin::from_entry_point(entry_point) }
+            .unwrap()
+            .capability(Capability::Pure);
+        plugin.register(&mut evaluator);
+        assert_eq!(
+            evaluator.evaluate_expression("scale(values: x)", &context),
+            Ok(6.0)
+        );
+
+        assert!(unsafe { Plugin::from_entry_point(failing_entry_point) }.is_err());
+        assert!(unsafe { Plugin::load("/nonexistent/libplugin.so") }.is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! User functions implemented as WebAssembly modules, for services that run functions
+//! supplied by their tenants.
+//!
+//! Each call runs in a fresh instance with its own fuel and memory limits, so a function
+//! cannot loop forever, exhaust memory or keep state between calls. Modules cannot import
+//! anything, so they have no access to the host.
+
+use crate::ast::{FunctionInfo, FunctionResult};
+use crate::Evaluator;
+use std::sync::Arc;
+use wasmtime::{
+    Config, Engine, ExternType, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap,
+    Val, ValType,
+};
+
+/// Compiles WebAssembly functions and registers them with an evaluator.
+///
+/// ```
+/// use quantixis_rs::ast::{Evaluator, FunctionInfo};
+/// use quantixis_rs::sandbox::WasmSandbox;
+/// use std::collections::HashMap;
+///
+/// let module = r#"(module
+///     (func (export "spread") (param $ask f64) (param $bid f64) (result f64)
+///         (f64.sub (local.get $ask) (local.get $bid))))"#;
+///
+/// let mut evaluator = Evaluator::new(100);
+/// let sandbox = WasmSandbox::new().unwrap().fuel(10_000);
+/// let info = FunctionInfo::new("tenant.spread").param("ask").param("bid");
+/// sandbox.register(&mut evaluator, info, module.as_bytes(), "spread").unwrap();
+///
+/// let context = HashMap::from([("ask".to_string(), 100.5), ("bid".to_string(), 99.5)]);
+/// let spread = evaluator.evaluate_expression("tenant.spread(ask: ask, bid: bid)", &context);
+/// assert_eq!(spread, Ok(1.0));
+/// ```
+#[derive(Clone)]
+pub struct WasmSandbox {
+    engine: Engine,
+    fuel: u64,
+    max_memory: usize,
+}
+
+impl WasmSandbox {
+    /// Creates a sandbox allowing 1,000,000 units of fuel, roughly one per instruction, and
+    /// 16 MiB of memory per call.
+    pub fn new() -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Ok(Self {
+            engine: Engine::new(&config).map_err(|err| err.to_string())?,
+            fuel: 1_000_000,
+            max_memory: 16 << 20,
+        })
+    }
+
+    /// Sets the fuel available to each call.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Sets the memory available to each call, in bytes.
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// Registers the function `export` of a module, given in binary or text format, under
+    /// `info.name`.
+    ///
+    /// The export must take one `f64` per parameter of `info`, in order, and return one
+    /// `f64`. Omitted arguments take their declared default.
+    pub fn register(
+        &self,
+        evaluator: &mut Evaluator,
+        info: FunctionInfo,
+        wasm: &[u8],
+        export: &str,
+    ) -> Result<(), String> {
+        let module = Module::new(&self.engine, wasm).map_err(|err| err.to_string())?;
+        if module.imports().len() > 0 {
+            return Err("WebAssembly functions cannot import anything".to_string());
+        }
+        match module.get_export(export) {
+            Some(ExternType::Func(signature))
+                if signature.params().len() == info.params.len()
+                    && signature
+                        .params()
+                        .all(|param| matches!(param, ValType::F64))
+                    && signature.results().len() == 1
+                    && signature
+                        .results()
+                        .all(|result| matches!(result, ValType::F64)) => {}
+            Some(_) => {
+                return Err(format!(
+                    "Export '{}' must take {} f64 parameters and return one f64",
+                    export,
+                    info.params.len()
+                ))
+            }
+            None => return Err(format!("Module has no export '{}'", export)),
+        }
+
+        let function = Arc::new(SandboxedFunction {
+            sandbox: self.clone(),
+            module,
+            export: export.to_string(),
+            name: info.name.clone(),
+            params: info
+                .params
+                .iter()
+                .map(|param| (param.name.clone(), param.default))
+                .collect(),
+        });
+        evaluator.register_function_with_info(info, move |args| {
+            let values = function
+                .params
+                .iter()
+                .map(|(name, default)| match (args.contains_key(name), default) {
+                    (false, Some(default)) => Ok(*default),
+                    _ => args.get_number(name),
+                })
+                .collect::<Result<Vec<f64>, String>>()?;
+            function.call(&values).map(FunctionResult::UnnamedF64)
+        });
+        Ok(())
+    }
+}
+
+struct SandboxedFunction {
+    sandbox: WasmSandbox,
+    module: Module,
+    export: String,
+    name: String,
+    /// Parameter names with their defaults, in the order of the export's parameters
+    params: Vec<(String, Option<f64>)>,
+}
+
+impl SandboxedFunction {
+    fn call(&self, values: &[f64]) -> Result<f64, String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.sandbox.max_memory)
+            .instances(1)
+            .build();
+        let mut store: Store<StoreLimits> = Store::new(&self.sandbox.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.sandbox.fuel)
+            .map_err(|err| err.to_string())?;
+
+        let params: Vec<Val> = values
+            .iter()
+            .map(|value| Val::F64(value.to_bits()))
+            .collect();
+        let mut results = [Val::F64(0)];
+        Instance::new(&mut store, &self.module, &[])
+            .and_then(|instance| {
+                instance
+                    .get_func(&mut store, &self.export)
+                    .ok_or_else(|| wasmtime::Error::msg("missing export"))?
+                    .call(&mut store, &params, &mut results)
+            })
+            .map_err(|err| match err.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => format!("Function '{}' ran out of fuel", self.name),
+                _ => format!("Function '{}' failed: {}", self.name, err),
+            })?;
+        Ok(results[0].unwrap_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const MODULE: &str = r#"(module
+        (memory 1)
+        (func (export "scale") (param $x f64) (param $factor f64) (result f64)
+            (f64.mul (local.get $x) (local.get $factor)))
+        (func (export "spin") (param $x f64) (result f64)
+            (loop $forever (br $forever))
+            (local.get $x))
+        (func (export "grow") (param $pages f64) (result f64)
+            (f64.convert_i32_s (memory.grow (i32.trunc_f64_s (local.get $pages)))))
+        (func (export "wrong") (param $x i32) (result i32)
+            (local.get $x)))"#;
+
+    #[test]
+    fn test_sandboxed_functions() {
+        let sandbox = WasmSandbox::new().unwrap().fuel(10_000).max_memory(4 << 16);
+        let mut evaluator = Evaluator::new(100);
+        let register = |evaluator: &mut Evaluator, info, export| {
+            sandbox.register(evaluator, info, MODULE.as_bytes(), export)
+        };
+        register(
+            &mut evaluator,
+            FunctionInfo::new("scale")
+                .param("x")
+                .param_with_default("factor", 2.0),
+            "scale",
+        )
+        .unwrap();
+        register(&mut evaluator, FunctionInfo::new("spin").param("x"), "spin").unwrap();
+        register(
+            &mut evaluator,
+            FunctionInfo::new("grow").param("pages"),
+            "grow",
+        )
+        .unwrap();
+
+        let context = HashMap::from([("x".to_string(), 3.0)]);
+        let mut evaluate = |expression| evaluator.evaluate_expression(expression, &context);
+        assert_eq!(evaluate("scale(x: x, factor: 10)"), Ok(30.0));
+        assert_eq!(evaluate("scale(x: x)"), Ok(6.0));
+        assert!(evaluate("scale(factor: 1)").is_err());
+        assert_eq!(
+            evaluate("spin(x: 1)"),
+            Err("Function 'spin' ran out of fuel".to_string())
+        );
+        // One page is initial, so three more fit in four and a fourth does not
+        assert_eq!(evaluate("grow(pages: 3)"), Ok(1.0));
+        assert_eq!(evaluate("grow(pages: 4)"), Ok(-1.0));
+
+        assert_eq!(
+            register(
+                &mut evaluator,
+                FunctionInfo::new("wrong").param("x"),
+                "wrong"
+            ),
+            Err("Export 'wrong' must take 1 f64 parameters and return one f64".to_string())
+        );
+        assert!(register(&mut evaluator, FunctionInfo::new("missing"), "missing").is_err());
+        let importing = r#"(module (import "env" "f" (func)))"#;
+        assert!(sandbox
+            .register(
+                &mut evaluator,
+                FunctionInfo::new("importing"),
+                importing.as_bytes(),
+                "f"
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::ast::{Evaluator, Parser};
+use crate::functions::register_functions;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Parses an expression and returns its AST in debug form.
+#[wasm_bindgen]
+pub fn parse(expression: &str) -> Result<String, JsValue> {
+    Parser::parse_expression(expression)
+        .map(|ast| format!("{:#?}", ast))
+        .map_err(|err| JsValue::from_str(&err))
+}
+
+/// Checks that an expression parses and only calls built-in functions.
+#[wasm_bindgen]
+pub fn validate(expression: &str) -> Result<(), JsValue> {
+    validate_expression(expression).map_err(|err| JsValue::from_str(&err))
+}
+
+/// Evaluates an expression against a JSON object of numeric variables,
+/// e.g. `{"price": 120, "volume": 3000}`.
+#[wasm_bindgen]
+pub fn evaluate_json_context(expression: &str, context: &str) -> Result<f64, JsValue> {
+    evaluate_json(expression, context).map_err(|err| JsValue::from_str(&err))
+}
+
+fn default_evaluator() -> Evaluator {
+    let mut evaluator = Evaluator::new(100);
+    register_functions(&mut evaluator);
+    evaluator
+}
+
+fn validate_expression(expression: &str) -> Result<(), String> {
+    default_evaluator().validate(expression)
+}
+
+fn evaluate_json(expression: &str, context: &str) -> Result<f64, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(context).map_err(|e| format!("Invalid JSON context: {}", e))?;
+    let object = json
+        .as_object()
+        .ok_or_else(|| "JSON context must be an object".to_string())?;
+
+    let context = object
+        .iter()
+        .map(|(key, value)| {
+            value
+                .as_f64()
+                .map(|number| (key.clone(), number))
+                .ok_or_else(|| format!("Context value '{}' must be a number", key))
+        })
+        .collect::<Result<HashMap<String, f64>, String>>()?;
+
+    default_evaluator().evaluate_expression(expression, &context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_json() {
+        let result = evaluate_json(
+            "price > 100 AND volume < 5000",
+            r#"{"price": 120, "volume": 3000}"#,
+        );
+        assert_eq!(result, Ok(1.0));
+
+        assert!(evaluate_json("price > 100", "[1, 2]").is_err());
+        assert!(evaluate_json("price > 100", r#"{"price": "high"}"#).is_err());
+    }
+
+    #[test]
+    fn test_validate_expression() {
+        assert!(validate_expression("abs(value: price) > 1").is_ok());
+        assert!(validate_expression("price >").is_err());
+
+        let err = validate_expression("ab(value: price) > 1").unwrap_err();
+        assert!(err.contains("Did you mean 'abs'?"));
+    }
+}